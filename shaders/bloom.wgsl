@@ -0,0 +1,61 @@
+// Bloom in three steps: keep the bright parts of the image, blur them in a
+// smaller target one axis at a time, then add them back on top of the
+// source. Expects fullscreen.wgsl to be prepended
+
+[[block]]
+struct Bloom {
+    // Texel size of the texture being sampled
+    texel_size: vec2<f32>;
+    // Blur axis, (1, 0) or (0, 1)
+    direction: vec2<f32>;
+    threshold: f32;
+    intensity: f32;
+    radius: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: Bloom;
+[[group(0), binding(1)]] var source_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var source_sampler: sampler;
+// Only bound for fs_composite
+[[group(0), binding(3)]] var bloom_texture: texture_2d<f32>;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(fragment)]]
+fn fs_bright(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    // Average a 2x2 block while downsampling to avoid flickering
+    let offset = settings.texel_size * 0.5;
+    let color = (
+        textureSample(source_texture, source_sampler, in.uv + vec2<f32>(-offset.x, -offset.y)).rgb +
+        textureSample(source_texture, source_sampler, in.uv + vec2<f32>(offset.x, -offset.y)).rgb +
+        textureSample(source_texture, source_sampler, in.uv + vec2<f32>(-offset.x, offset.y)).rgb +
+        textureSample(source_texture, source_sampler, in.uv + vec2<f32>(offset.x, offset.y)).rgb
+    ) * 0.25;
+
+    let brightness = luminance(color);
+    let contribution = max(brightness - settings.threshold, 0.0)
+        / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_blur(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    // 9-tap gaussian folded into 5 bilinear samples
+    let step = settings.direction * settings.texel_size * settings.radius;
+    var color = textureSample(source_texture, source_sampler, in.uv).rgb
+        * 0.2270270270;
+    color = color + textureSample(source_texture, source_sampler, in.uv + step * 1.3846153846).rgb * 0.3162162162;
+    color = color + textureSample(source_texture, source_sampler, in.uv - step * 1.3846153846).rgb * 0.3162162162;
+    color = color + textureSample(source_texture, source_sampler, in.uv + step * 3.2307692308).rgb * 0.0702702703;
+    color = color + textureSample(source_texture, source_sampler, in.uv - step * 3.2307692308).rgb * 0.0702702703;
+    return vec4<f32>(color, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_composite(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let source = textureSample(source_texture, source_sampler, in.uv);
+    let bloom = textureSample(bloom_texture, source_sampler, in.uv).rgb;
+    return vec4<f32>(source.rgb + bloom * settings.intensity, source.a);
+}
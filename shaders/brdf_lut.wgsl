@@ -0,0 +1,54 @@
+// Integrates the split-sum specular BRDF: scale (r) and bias (g) applied
+// to F0, for the cosine between normal and view (u) and the roughness (v)
+
+let SAMPLE_COUNT: u32 = 512u;
+
+[[group(0), binding(0)]] var output: texture_storage_2d<rgba16float, write>;
+
+fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (i32(id.x) >= size.x || i32(id.y) >= size.y) {
+        return;
+    }
+
+    let n_dot_v = (f32(id.x) + 0.5) / f32(size.x);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let view = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let halfway = importance_sample_ggx(
+            hammersley(i, SAMPLE_COUNT),
+            normal,
+            roughness
+        );
+        let light = normalize(2.0 * dot(view, halfway) * halfway - view);
+        let n_dot_l = max(light.z, 0.0);
+        let n_dot_h = max(halfway.z, 0.0);
+        let v_dot_h = max(dot(view, halfway), 0.0);
+
+        if (n_dot_l > 0.0) {
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness)
+                * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fresnel) * visibility;
+            bias = bias + fresnel * visibility;
+        }
+    }
+
+    let count = f32(SAMPLE_COUNT);
+    textureStore(
+        output,
+        vec2<i32>(id.xy),
+        vec4<f32>(scale / count, bias / count, 0.0, 1.0)
+    );
+}
@@ -0,0 +1,47 @@
+// Remaps colors through a lookup table stored as a horizontal strip of
+// `size` slices of size x size texels, blue selecting the slice. Expects
+// fullscreen.wgsl to be prepended
+
+[[block]]
+struct ColorGrading {
+    size: f32;
+    // Blend between the source (0) and the graded color (1)
+    strength: f32;
+    // 1 if sampled colors are linear and have to be encoded for the lookup
+    decode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: ColorGrading;
+[[group(0), binding(1)]] var source_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var source_sampler: sampler;
+[[group(0), binding(3)]] var lut_texture: texture_2d<f32>;
+
+fn lut_uv(color: vec3<f32>, slice: f32) -> vec2<f32> {
+    let size = settings.size;
+    let x = (slice * size + color.r * (size - 1.0) + 0.5) / (size * size);
+    let y = (color.g * (size - 1.0) + 0.5) / size;
+    return vec2<f32>(x, y);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let source = textureSample(source_texture, source_sampler, in.uv);
+    var color = clamp(source.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (settings.decode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+
+    let blue = color.b * (settings.size - 1.0);
+    let slice = floor(blue);
+    let next_slice = min(slice + 1.0, settings.size - 1.0);
+    var graded = mix(
+        textureSample(lut_texture, source_sampler, lut_uv(color, slice)).rgb,
+        textureSample(lut_texture, source_sampler, lut_uv(color, next_slice)).rgb,
+        blue - slice,
+    );
+
+    if (settings.decode_srgb == 1u) {
+        graded = srgb_to_linear(graded);
+    }
+    return vec4<f32>(mix(source.rgb, graded, settings.strength), source.a);
+}
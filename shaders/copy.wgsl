@@ -0,0 +1,9 @@
+// Copies a texture to the target. Expects fullscreen.wgsl to be prepended
+
+[[group(0), binding(0)]] var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]] var source_sampler: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(source_texture, source_sampler, in.uv);
+}
@@ -0,0 +1,35 @@
+// Colored lines of the debug drawing, on top of the scene
+
+[[block]]
+struct View {
+    view_proj: mat4x4<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> view: View;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = view.view_proj * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var color = in.color.rgb;
+    if (view.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, in.color.a);
+}
@@ -0,0 +1,99 @@
+// Lighting passes of the deferred renderer. `fs_ambient` runs once over the
+// whole screen, then every point light is drawn as a cube enclosing its
+// radius with `vs_light`/`fs_light`, added on top. Expects fullscreen.wgsl
+// to be prepended
+
+[[block]]
+struct Camera {
+    view_proj: mat4x4<f32>;
+    position: vec4<f32>;
+    ambient: vec4<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> camera: Camera;
+[[group(1), binding(0)]] var albedo_texture: texture_2d<f32>;
+[[group(1), binding(1)]] var normal_texture: texture_2d<f32>;
+[[group(1), binding(2)]] var position_texture: texture_2d<f32>;
+[[group(1), binding(3)]] var material_texture: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn fs_ambient(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let texel = vec2<i32>(in.position.xy);
+    let albedo = textureLoad(albedo_texture, texel, 0);
+    return vec4<f32>(albedo.rgb * camera.ambient.rgb, 1.0);
+}
+
+struct LightOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    // xyz = position, w = radius
+    [[location(0), interpolate(flat)]] light: vec4<f32>;
+    // rgb = color, a = intensity
+    [[location(1), interpolate(flat)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_light(
+    [[builtin(vertex_index)]] index: u32,
+    [[location(0)]] light: vec4<f32>,
+    [[location(1)]] color: vec4<f32>,
+) -> LightOutput {
+    // Corner i of the cube has x, y and z in bits 0, 1 and 2. Faces wind
+    // counter-clockwise seen from outside
+    var corners = array<u32, 36>(
+        5u, 1u, 3u, 5u, 3u, 7u,
+        0u, 4u, 6u, 0u, 6u, 2u,
+        6u, 7u, 3u, 6u, 3u, 2u,
+        0u, 1u, 5u, 0u, 5u, 4u,
+        4u, 5u, 7u, 4u, 7u, 6u,
+        1u, 0u, 2u, 1u, 2u, 3u,
+    );
+    let corner = corners[index];
+    let offset = vec3<f32>(
+        f32(corner & 1u),
+        f32((corner >> 1u) & 1u),
+        f32((corner >> 2u) & 1u),
+    ) * 2.0 - 1.0;
+
+    var out: LightOutput;
+    out.position = camera.view_proj * vec4<f32>(light.xyz + offset * light.w, 1.0);
+    out.light = light;
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_light(in: LightOutput) -> [[location(0)]] vec4<f32> {
+    let texel = vec2<i32>(in.position.xy);
+    let position = textureLoad(position_texture, texel, 0);
+    let to_light = in.light.xyz - position.xyz;
+    let distance = length(to_light);
+    if (position.w == 0.0 || distance >= in.light.w) {
+        discard;
+    }
+
+    let albedo = textureLoad(albedo_texture, texel, 0).rgb;
+    let normal = normalize(textureLoad(normal_texture, texel, 0).xyz);
+    let material = textureLoad(material_texture, texel, 0);
+    let roughness = material.x;
+    let metallic = material.y;
+
+    let l = to_light / distance;
+    let v = normalize(camera.position.xyz - position.xyz);
+    let h = normalize(l + v);
+    let n_dot_l = max(dot(normal, l), 0.0);
+
+    // Inverse square falloff, windowed to reach zero at the radius
+    let ratio = distance / in.light.w;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    let attenuation = window * window / (distance * distance + 1.0);
+
+    let alpha = max(roughness * roughness, 0.01);
+    let shininess = 2.0 / (alpha * alpha) - 2.0;
+    let specular_color = mix(vec3<f32>(0.04), albedo, metallic);
+    let specular = specular_color * (shininess + 8.0) / 8.0
+        * pow(max(dot(normal, h), 0.0), shininess);
+    let diffuse = albedo * (1.0 - metallic);
+
+    let radiance = in.color.rgb * in.color.a * attenuation * n_dot_l;
+    return vec4<f32>((diffuse + specular) * radiance, 1.0);
+}
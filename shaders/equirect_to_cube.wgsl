@@ -0,0 +1,48 @@
+// Projects an equirectangular image onto the six faces of a cubemap, one
+// invocation per texel of the output
+
+[[group(0), binding(0)]] var equirect: texture_2d<f32>;
+[[group(0), binding(1)]] var output: texture_storage_2d_array<rgba16float, write>;
+
+// Float textures of 32 bits per channel can't be filtered by samplers
+fn load_bilinear(uv: vec2<f32>) -> vec4<f32> {
+    let size = textureDimensions(equirect);
+    let texel = uv * vec2<f32>(size) - vec2<f32>(0.5);
+    let base = vec2<i32>(floor(texel));
+    let t = texel - floor(texel);
+
+    var texels: array<vec4<f32>, 4>;
+    for (var i = 0; i < 4; i = i + 1) {
+        var coords = base + vec2<i32>(i % 2, i / 2);
+        // Wraps around horizontally, stops at the poles
+        coords.x = (coords.x + size.x) % size.x;
+        coords.y = clamp(coords.y, 0, size.y - 1);
+        texels[i] = textureLoad(equirect, coords, 0);
+    }
+
+    return mix(
+        mix(texels[0], texels[1], t.x),
+        mix(texels[2], texels[3], t.x),
+        t.y
+    );
+}
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (i32(id.x) >= size.x || i32(id.y) >= size.y) {
+        return;
+    }
+
+    let direction = cube_direction(id, u32(size.x));
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI
+    );
+    textureStore(
+        output,
+        vec2<i32>(id.xy),
+        i32(id.z),
+        vec4<f32>(load_bilinear(uv).rgb, 1.0)
+    );
+}
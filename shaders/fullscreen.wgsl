@@ -0,0 +1,18 @@
+// Full-screen triangle. Draw three vertices without a vertex buffer and the
+// triangle covers the whole target, with `uv` going from (0, 0) in the
+// top-left corner to (1, 1) in the bottom-right one
+
+struct FullscreenOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: FullscreenOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
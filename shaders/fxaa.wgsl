@@ -0,0 +1,59 @@
+// Fast approximate anti-aliasing, after Timothy Lottes' FXAA 3.11 console
+// variant. Expects fullscreen.wgsl to be prepended
+
+[[block]]
+struct Fxaa {
+    texel_size: vec2<f32>;
+    // Edges with less contrast than this are left alone
+    edge_threshold: f32;
+    edge_threshold_min: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: Fxaa;
+[[group(0), binding(1)]] var source_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var source_sampler: sampler;
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let texel = settings.texel_size;
+    let center = textureSampleLevel(source_texture, source_sampler, in.uv, 0.0);
+    let luma_m = luma(center.rgb);
+    let luma_nw = luma(textureSampleLevel(source_texture, source_sampler, in.uv + vec2<f32>(-texel.x, -texel.y), 0.0).rgb);
+    let luma_ne = luma(textureSampleLevel(source_texture, source_sampler, in.uv + vec2<f32>(texel.x, -texel.y), 0.0).rgb);
+    let luma_sw = luma(textureSampleLevel(source_texture, source_sampler, in.uv + vec2<f32>(-texel.x, texel.y), 0.0).rgb);
+    let luma_se = luma(textureSampleLevel(source_texture, source_sampler, in.uv + vec2<f32>(texel.x, texel.y), 0.0).rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    let contrast = luma_max - luma_min;
+    if (contrast < max(settings.edge_threshold_min, luma_max * settings.edge_threshold)) {
+        return center;
+    }
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.03125, 0.0078125);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-8.0), vec2<f32>(8.0)) * texel;
+
+    let color_a = 0.5 * (
+        textureSampleLevel(source_texture, source_sampler, in.uv + direction * (1.0 / 3.0 - 0.5), 0.0).rgb +
+        textureSampleLevel(source_texture, source_sampler, in.uv + direction * (2.0 / 3.0 - 0.5), 0.0).rgb
+    );
+    let color_b = color_a * 0.5 + 0.25 * (
+        textureSampleLevel(source_texture, source_sampler, in.uv - direction * 0.5, 0.0).rgb +
+        textureSampleLevel(source_texture, source_sampler, in.uv + direction * 0.5, 0.0).rgb
+    );
+
+    let luma_b = luma(color_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4<f32>(color_a, center.a);
+    }
+    return vec4<f32>(color_b, center.a);
+}
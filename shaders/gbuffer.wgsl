@@ -0,0 +1,59 @@
+// Geometry pass of the deferred renderer, storing the surface attributes of
+// every visible pixel in the G-buffer for the lighting passes
+
+[[block]]
+struct Camera {
+    view_proj: mat4x4<f32>;
+    position: vec4<f32>;
+    ambient: vec4<f32>;
+};
+
+[[block]]
+struct Object {
+    model: mat4x4<f32>;
+    albedo: vec4<f32>;
+    // x = roughness, y = metallic
+    material: vec4<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> camera: Camera;
+[[group(1), binding(0)]] var<uniform> object_data: Object;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+struct GBufferOutput {
+    [[location(0)]] albedo: vec4<f32>;
+    [[location(1)]] normal: vec4<f32>;
+    // w is 1 wherever geometry was drawn
+    [[location(2)]] position: vec4<f32>;
+    [[location(3)]] material: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+) -> VertexOutput {
+    let world = object_data.model * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.position = camera.view_proj * world;
+    out.world_position = world.xyz;
+    // Only correct for uniformly scaled models
+    out.normal = (object_data.model * vec4<f32>(normal, 0.0)).xyz;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> GBufferOutput {
+    var out: GBufferOutput;
+    out.albedo = object_data.albedo;
+    out.normal = vec4<f32>(normalize(in.normal), 0.0);
+    out.position = vec4<f32>(in.world_position, 1.0);
+    out.material = object_data.material;
+    return out;
+}
@@ -0,0 +1,50 @@
+// Meshes tessellated by the GUI, blended in gamma space with premultiplied
+// alpha the way it expects
+
+[[block]]
+struct Screen {
+    // In points
+    size: vec2<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> screen: Screen;
+[[group(1), binding(0)]] var gui_texture: texture_2d<f32>;
+[[group(1), binding(1)]] var gui_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    // In points from the top left corner of the screen
+    [[location(0)]] position: vec2<f32>,
+    [[location(1)]] uv: vec2<f32>,
+    // sRGB encoded, premultiplied
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    let ndc = position / screen.size * vec2<f32>(2.0, -2.0)
+        + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Textures are sampled as linear values, the vertex colors aren't
+    let texel = textureSample(gui_texture, gui_sampler, in.uv);
+    let color = in.color * vec4<f32>(linear_to_srgb(texel.rgb), texel.a);
+
+    if (screen.encode_srgb == 1u) {
+        return color;
+    }
+    return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+}
@@ -0,0 +1,59 @@
+// Helpers shared by the compute passes turning an equirectangular
+// environment into the maps of image-based lighting
+
+let PI: f32 = 3.14159265359;
+
+// Direction through the middle of texel `id` of a cube face of `size`
+// texels, faces ordered +X, -X, +Y, -Y, +Z, -Z
+fn cube_direction(id: vec3<u32>, size: u32) -> vec3<f32> {
+    let uv = (vec2<f32>(id.xy) + vec2<f32>(0.5)) / f32(size) * 2.0
+        - vec2<f32>(1.0);
+    var direction: vec3<f32>;
+    switch (i32(id.z)) {
+        case 0: { direction = vec3<f32>(1.0, -uv.y, -uv.x); }
+        case 1: { direction = vec3<f32>(-1.0, -uv.y, uv.x); }
+        case 2: { direction = vec3<f32>(uv.x, 1.0, uv.y); }
+        case 3: { direction = vec3<f32>(uv.x, -1.0, -uv.y); }
+        case 4: { direction = vec3<f32>(uv.x, -uv.y, 1.0); }
+        default: { direction = vec3<f32>(-uv.x, -uv.y, -1.0); }
+    }
+    return normalize(direction);
+}
+
+// Builds a basis around `normal` and moves `v` from tangent space into it
+fn to_world(v: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(normal.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(up, normal));
+    let forward = cross(normal, right);
+    return right * v.x + forward * v.y + normal * v.z;
+}
+
+// Low-discrepancy point `i` of `count`
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    var bits = i;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 1431655765u) << 1u) | ((bits & 2863311530u) >> 1u);
+    bits = ((bits & 858993459u) << 2u) | ((bits & 3435973836u) >> 2u);
+    bits = ((bits & 252645135u) << 4u) | ((bits & 4042322160u) >> 4u);
+    bits = ((bits & 16711935u) << 8u) | ((bits & 4278255360u) >> 8u);
+    return vec2<f32>(f32(i) / f32(count), f32(bits) * 2.3283064365386963e-10);
+}
+
+// Halfway vector around `normal` distributed like GGX microfacets
+fn importance_sample_ggx(
+    xi: vec2<f32>,
+    normal: vec3<f32>,
+    roughness: f32,
+) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return to_world(
+        vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta),
+        normal
+    );
+}
@@ -0,0 +1,44 @@
+// Convolves an environment cubemap with a cosine lobe, giving the diffuse
+// light reaching a surface facing each direction
+
+let SAMPLE_DELTA: f32 = 0.05;
+
+[[group(0), binding(0)]] var environment: texture_cube<f32>;
+[[group(0), binding(1)]] var environment_sampler: sampler;
+[[group(0), binding(2)]] var output: texture_storage_2d_array<rgba16float, write>;
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (i32(id.x) >= size.x || i32(id.y) >= size.y) {
+        return;
+    }
+
+    let normal = cube_direction(id, u32(size.x));
+    var irradiance = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi = phi + SAMPLE_DELTA) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta = theta + SAMPLE_DELTA) {
+            let tangent = vec3<f32>(
+                sin(theta) * cos(phi),
+                sin(theta) * sin(phi),
+                cos(theta)
+            );
+            let direction = to_world(tangent, normal);
+            irradiance = irradiance + textureSampleLevel(
+                environment,
+                environment_sampler,
+                direction,
+                0.0
+            ).rgb * cos(theta) * sin(theta);
+            count = count + 1.0;
+        }
+    }
+
+    textureStore(
+        output,
+        vec2<i32>(id.xy),
+        i32(id.z),
+        vec4<f32>(PI * irradiance / count, 1.0)
+    );
+}
@@ -0,0 +1,57 @@
+// Forward pass drawing the mesh nodes of a scene with Blinn-Phong shading
+// under the directional, point and spot lights of the frame. Each draw
+// binds its own slice of the object buffer and the group of its material.
+// Lights with a shadow map layer look it up with PCF filtering, the
+// directional one picking its cascade from the distance to the camera.
+// Appended to scene.wgsl
+
+[[block]]
+struct Material {
+    ambient: vec4<f32>;
+    // a = opacity
+    diffuse: vec4<f32>;
+    // w = shininess
+    specular: vec4<f32>;
+    emissive: vec4<f32>;
+};
+
+[[group(2), binding(0)]] var<uniform> material: Material;
+[[group(2), binding(1)]] var diffuse_map: texture_2d<f32>;
+[[group(2), binding(2)]] var specular_map: texture_2d<f32>;
+[[group(2), binding(3)]] var material_sampler: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.normal);
+    let view = normalize(camera.position - in.world_position);
+    let diffuse = material.diffuse
+        * textureSample(diffuse_map, material_sampler, in.uv);
+    let specular_color = material.specular.rgb
+        * textureSample(specular_map, material_sampler, in.uv).rgb;
+    let shininess = material.specular.w;
+
+    var color = material.emissive.rgb
+        + material.ambient.rgb * diffuse.rgb * lights.ambient;
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i = i + 1u) {
+        let light = lights.lights[i];
+
+        let incoming = sample_light(light, in.world_position);
+        let n_dot_l = dot(normal, incoming.direction);
+        if (n_dot_l > 0.0 && any(incoming.radiance > vec3<f32>(0.0))) {
+            let halfway = normalize(incoming.direction + view);
+            let specular = pow(max(dot(normal, halfway), 0.0), shininess);
+            var radiance = incoming.radiance;
+            let layer = shadow_layer(light, in.world_position);
+            if (layer >= 0) {
+                radiance = radiance * shadow_factor(layer, in.world_position);
+            }
+            color = color + radiance * (diffuse.rgb * n_dot_l
+                + specular_color * specular);
+        }
+    }
+
+    if (camera.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, diffuse.a);
+}
@@ -0,0 +1,68 @@
+// Blends the morph targets of a mesh into a vertex buffer of its own: each
+// vertex moves by the deltas of every target scaled by its weight. Vertices
+// are read and written as `MeshVertex`, eight floats each
+
+let VERTEX_FLOATS: u32 = 8u;
+
+[[block]]
+struct Params {
+    vertex_count: u32;
+    target_count: u32;
+};
+
+[[block]]
+struct Floats {
+    values: array<f32>;
+};
+
+// Position then normal delta of each vertex, target after target
+[[block]]
+struct Deltas {
+    values: array<vec4<f32>>;
+};
+
+[[group(0), binding(0)]] var<uniform> params: Params;
+[[group(0), binding(1)]] var<storage, read> base: Floats;
+[[group(0), binding(2)]] var<storage, read> deltas: Deltas;
+[[group(0), binding(3)]] var<storage, read> weights: Floats;
+[[group(0), binding(4)]] var<storage, read_write> output: Floats;
+
+[[stage(compute), workgroup_size(64, 1, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let vertex = id.x;
+    if (vertex >= params.vertex_count) {
+        return;
+    }
+
+    let first = vertex * VERTEX_FLOATS;
+    var position = vec3<f32>(
+        base.values[first],
+        base.values[first + 1u],
+        base.values[first + 2u],
+    );
+    var normal = vec3<f32>(
+        base.values[first + 3u],
+        base.values[first + 4u],
+        base.values[first + 5u],
+    );
+    for (var i = 0u; i < params.target_count; i = i + 1u) {
+        let weight = weights.values[i];
+        if (weight != 0.0) {
+            let delta = 2u * (i * params.vertex_count + vertex);
+            position = position + deltas.values[delta].xyz * weight;
+            normal = normal + deltas.values[delta + 1u].xyz * weight;
+        }
+    }
+    if (any(normal != vec3<f32>(0.0))) {
+        normal = normalize(normal);
+    }
+
+    output.values[first] = position.x;
+    output.values[first + 1u] = position.y;
+    output.values[first + 2u] = position.z;
+    output.values[first + 3u] = normal.x;
+    output.values[first + 4u] = normal.y;
+    output.values[first + 5u] = normal.z;
+    output.values[first + 6u] = base.values[first + 6u];
+    output.values[first + 7u] = base.values[first + 7u];
+}
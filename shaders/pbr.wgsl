@@ -0,0 +1,177 @@
+// Forward pass drawing the mesh nodes of a scene with metallic-roughness
+// materials: a Cook-Torrance BRDF with the GGX distribution, Smith
+// geometry and Schlick fresnel for the lights of the frame, and
+// image-based lighting from the irradiance and prefiltered maps of the
+// environment combined with the split-sum BRDF lookup table. Appended to
+// scene.wgsl
+
+let PI: f32 = 3.14159265359;
+
+[[block]]
+struct Material {
+    // a = opacity
+    base_color: vec4<f32>;
+    emissive: vec3<f32>;
+    // 0 without a normal map
+    normal_scale: f32;
+    metallic: f32;
+    roughness: f32;
+    occlusion_strength: f32;
+};
+
+[[group(2), binding(0)]] var<uniform> material: Material;
+[[group(2), binding(1)]] var base_color_map: texture_2d<f32>;
+// Roughness in green, metalness in blue
+[[group(2), binding(2)]] var metallic_roughness_map: texture_2d<f32>;
+[[group(2), binding(3)]] var normal_map: texture_2d<f32>;
+[[group(2), binding(4)]] var occlusion_map: texture_2d<f32>;
+[[group(2), binding(5)]] var emissive_map: texture_2d<f32>;
+[[group(2), binding(6)]] var material_sampler: sampler;
+[[group(3), binding(0)]] var irradiance_map: texture_cube<f32>;
+[[group(3), binding(1)]] var prefiltered_map: texture_cube<f32>;
+[[group(3), binding(2)]] var brdf_lut: texture_2d<f32>;
+[[group(3), binding(3)]] var environment_sampler: sampler;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let view = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let light = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return view * light;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Rough surfaces reflect less of the environment at grazing angles
+fn fresnel_schlick_roughness(
+    cos_theta: f32,
+    f0: vec3<f32>,
+    roughness: f32
+) -> vec3<f32> {
+    let f90 = max(vec3<f32>(1.0 - roughness), f0);
+    return f0 + (f90 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Bends `normal` by a tangent-space normal map sample, building the
+// tangent frame from screen-space derivatives of the position and uv
+fn perturb_normal(
+    normal: vec3<f32>,
+    position: vec3<f32>,
+    uv: vec2<f32>,
+    mapped: vec3<f32>
+) -> vec3<f32> {
+    let dp1 = dpdx(position);
+    let dp2 = dpdy(position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+
+    let dp2_perp = cross(dp2, normal);
+    let dp1_perp = cross(normal, dp1);
+    let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+    let scale = inverseSqrt(
+        max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12)
+    );
+    let frame = mat3x3<f32>(tangent * scale, bitangent * scale, normal);
+    return normalize(frame * mapped);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let base_color = material.base_color
+        * textureSample(base_color_map, material_sampler, in.uv);
+    let metallic_roughness =
+        textureSample(metallic_roughness_map, material_sampler, in.uv);
+    let metallic = clamp(material.metallic * metallic_roughness.b, 0.0, 1.0);
+    let roughness =
+        clamp(material.roughness * metallic_roughness.g, 0.04, 1.0);
+    let occlusion = mix(
+        1.0,
+        textureSample(occlusion_map, material_sampler, in.uv).r,
+        material.occlusion_strength
+    );
+    let emissive = material.emissive
+        * textureSample(emissive_map, material_sampler, in.uv).rgb;
+    let normal_sample = textureSample(normal_map, material_sampler, in.uv).xyz
+        * 2.0 - 1.0;
+
+    let geometric_normal = normalize(in.normal);
+    let mapped = perturb_normal(
+        geometric_normal,
+        in.world_position,
+        in.uv,
+        normalize(vec3<f32>(
+            normal_sample.xy * material.normal_scale,
+            normal_sample.z
+        ))
+    );
+    let normal = select(
+        geometric_normal,
+        mapped,
+        material.normal_scale > 0.0
+    );
+    let view = normalize(camera.position - in.world_position);
+    let n_dot_v = max(dot(normal, view), 1e-4);
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+
+    var color = emissive;
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i = i + 1u) {
+        let light = lights.lights[i];
+
+        let incoming = sample_light(light, in.world_position);
+        let n_dot_l = dot(normal, incoming.direction);
+        if (n_dot_l > 0.0 && any(incoming.radiance > vec3<f32>(0.0))) {
+            let halfway = normalize(incoming.direction + view);
+            let n_dot_h = max(dot(normal, halfway), 0.0);
+            let fresnel = fresnel_schlick(max(dot(halfway, view), 0.0), f0);
+            let specular = fresnel
+                * distribution_ggx(n_dot_h, roughness)
+                * geometry_smith(n_dot_v, n_dot_l, roughness)
+                / (4.0 * n_dot_v * n_dot_l + 1e-4);
+            let diffuse = (1.0 - fresnel) * (1.0 - metallic)
+                * base_color.rgb / PI;
+
+            var radiance = incoming.radiance;
+            let layer = shadow_layer(light, in.world_position);
+            if (layer >= 0) {
+                radiance = radiance * shadow_factor(layer, in.world_position);
+            }
+            color = color + (diffuse + specular) * radiance * n_dot_l;
+        }
+    }
+
+    // Split-sum approximation of the environment lighting
+    let fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let irradiance =
+        textureSample(irradiance_map, environment_sampler, normal).rgb;
+    let diffuse = (1.0 - fresnel) * (1.0 - metallic) * irradiance
+        * base_color.rgb;
+    let max_lod = f32(textureNumLevels(prefiltered_map) - 1);
+    let prefiltered = textureSampleLevel(
+        prefiltered_map,
+        environment_sampler,
+        reflect(-view, normal),
+        roughness * max_lod
+    ).rgb;
+    let brdf = textureSample(
+        brdf_lut,
+        environment_sampler,
+        vec2<f32>(n_dot_v, roughness)
+    ).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+    color = color + (diffuse + specular) * occlusion;
+
+    if (camera.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, base_color.a);
+}
@@ -0,0 +1,52 @@
+// Blurs an environment cubemap by the GGX lobe of one roughness, written
+// into one mip level of the prefiltered specular map. The view direction
+// is assumed to match the normal
+
+let SAMPLE_COUNT: u32 = 256u;
+
+[[block]]
+struct Params {
+    roughness: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> params: Params;
+[[group(0), binding(1)]] var environment: texture_cube<f32>;
+[[group(0), binding(2)]] var environment_sampler: sampler;
+[[group(0), binding(3)]] var output: texture_storage_2d_array<rgba16float, write>;
+
+[[stage(compute), workgroup_size(8, 8, 1)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (i32(id.x) >= size.x || i32(id.y) >= size.y) {
+        return;
+    }
+
+    let normal = cube_direction(id, u32(size.x));
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let halfway = importance_sample_ggx(
+            hammersley(i, SAMPLE_COUNT),
+            normal,
+            params.roughness
+        );
+        let light = normalize(2.0 * dot(normal, halfway) * halfway - normal);
+        let n_dot_l = dot(normal, light);
+        if (n_dot_l > 0.0) {
+            color = color + textureSampleLevel(
+                environment,
+                environment_sampler,
+                light,
+                0.0
+            ).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+    }
+
+    textureStore(
+        output,
+        vec2<i32>(id.xy),
+        i32(id.z),
+        vec4<f32>(color / max(weight, 0.0001), 1.0)
+    );
+}
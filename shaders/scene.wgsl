@@ -0,0 +1,220 @@
+// Declarations shared by the forward shaders of the scene renderer: the
+// camera, lights and shadow maps of group 0, the object transform and
+// joint matrices of group 1, the vertex stages for rigid and skinned
+// meshes and the light and shadow lookups. The shading
+// model brings its own material in group 2 and fragment stage
+
+let MAX_LIGHTS: u32 = 16u;
+let MAX_CASCADES: u32 = 4u;
+
+let DIRECTIONAL: u32 = 0u;
+let POINT: u32 = 1u;
+let SPOT: u32 = 2u;
+
+[[block]]
+struct Camera {
+    view_proj: mat4x4<f32>;
+    position: vec3<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+struct Light {
+    position: vec3<f32>;
+    kind: u32;
+    // Where the light travels to
+    direction: vec3<f32>;
+    range: f32;
+    color: vec3<f32>;
+    intensity: f32;
+    // Cosines of the inner and outer angles of spot lights
+    cone: vec2<f32>;
+    // First shadow map layer, -1 without shadows
+    shadow: i32;
+};
+
+[[block]]
+struct Lights {
+    ambient: vec3<f32>;
+    count: u32;
+    lights: array<Light, 16>;
+};
+
+[[block]]
+struct Shadows {
+    view_projs: array<mat4x4<f32>, 8>;
+    // Far distance of each cascade
+    splits: vec4<f32>;
+    // Where the camera looks at
+    forward: vec3<f32>;
+    cascades: u32;
+    texel_size: f32;
+    pcf_radius: i32;
+};
+
+[[block]]
+struct Object {
+    model: mat4x4<f32>;
+    // Inverse transpose of the model matrix
+    normal: mat4x4<f32>;
+    // First matrix of the object's palette in `joints`
+    joint_offset: u32;
+};
+
+[[block]]
+struct Joints {
+    matrices: array<mat4x4<f32>>;
+};
+
+[[group(0), binding(0)]] var<uniform> camera: Camera;
+[[group(0), binding(1)]] var<uniform> lights: Lights;
+[[group(0), binding(2)]] var<uniform> shadows: Shadows;
+[[group(0), binding(3)]] var shadow_maps: texture_depth_2d_array;
+[[group(0), binding(4)]] var shadow_sampler: sampler_comparison;
+[[group(1), binding(0)]] var<uniform> object_data: Object;
+[[group(1), binding(1)]] var<storage, read> joints: Joints;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+fn vertex_output(
+    position: vec4<f32>,
+    normal: vec4<f32>,
+    uv: vec2<f32>,
+) -> VertexOutput {
+    let world = object_data.model * position;
+
+    var out: VertexOutput;
+    out.position = camera.view_proj * world;
+    out.world_position = world.xyz;
+    out.normal = (object_data.normal * normal).xyz;
+    out.uv = uv;
+    return out;
+}
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+    [[location(2)]] uv: vec2<f32>,
+) -> VertexOutput {
+    return vertex_output(
+        vec4<f32>(position, 1.0),
+        vec4<f32>(normal, 0.0),
+        uv,
+    );
+}
+
+// Blend of the joint matrices deforming a vertex, weights adding up to 1
+fn skin_matrix(indices: vec4<u32>, weights: vec4<f32>) -> mat4x4<f32> {
+    let base = object_data.joint_offset;
+    return joints.matrices[base + indices.x] * weights.x
+        + joints.matrices[base + indices.y] * weights.y
+        + joints.matrices[base + indices.z] * weights.z
+        + joints.matrices[base + indices.w] * weights.w;
+}
+
+[[stage(vertex)]]
+fn vs_skinned(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+    [[location(2)]] uv: vec2<f32>,
+    [[location(3)]] joint_indices: vec4<u32>,
+    [[location(4)]] weights: vec4<f32>,
+) -> VertexOutput {
+    // Joints are assumed to scale uniformly, so normals skin like
+    // positions
+    let skin = skin_matrix(joint_indices, weights);
+    return vertex_output(
+        skin * vec4<f32>(position, 1.0),
+        skin * vec4<f32>(normal, 0.0),
+        uv,
+    );
+}
+
+// Inverse square falloff, windowed to reach zero at the range
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+// Direction towards `light` from `position` and the radiance reaching it,
+// before shadows
+struct LightSample {
+    direction: vec3<f32>;
+    radiance: vec3<f32>;
+};
+
+fn sample_light(light: Light, position: vec3<f32>) -> LightSample {
+    var incoming: LightSample;
+    var strength = light.intensity;
+    if (light.kind == DIRECTIONAL) {
+        incoming.direction = -light.direction;
+    } else {
+        let offset = light.position - position;
+        let distance = length(offset);
+        incoming.direction = offset / distance;
+        strength = strength * attenuation(distance, light.range);
+
+        if (light.kind == SPOT) {
+            let angle = dot(-incoming.direction, light.direction);
+            strength = strength * clamp(
+                (angle - light.cone.y) / (light.cone.x - light.cone.y),
+                0.0,
+                1.0
+            );
+        }
+    }
+
+    incoming.radiance = light.color * strength;
+    return incoming;
+}
+
+// Fraction of the light reaching `position` according to the shadow map
+// `layer`, averaged over the surrounding texels
+fn shadow_factor(layer: i32, position: vec3<f32>) -> f32 {
+    let clip = shadows.view_projs[layer] * vec4<f32>(position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if (ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    let radius = shadows.pcf_radius;
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y = y + 1) {
+        for (var x = -radius; x <= radius; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.texel_size;
+            lit = lit + textureSampleCompareLevel(
+                shadow_maps,
+                shadow_sampler,
+                uv + offset,
+                layer,
+                ndc.z
+            );
+        }
+    }
+
+    let side = f32(2 * radius + 1);
+    return lit / (side * side);
+}
+
+// Shadow map layer of `light` covering `position`, -1 if there is none
+fn shadow_layer(light: Light, position: vec3<f32>) -> i32 {
+    if (light.shadow < 0 || light.kind != DIRECTIONAL) {
+        return light.shadow;
+    }
+
+    let depth = dot(position - camera.position, shadows.forward);
+    for (var i = 0u; i < min(shadows.cascades, MAX_CASCADES); i = i + 1u) {
+        if (depth < shadows.splits[i]) {
+            return light.shadow + i32(i);
+        }
+    }
+    return -1;
+}
@@ -0,0 +1,48 @@
+// Depth-only pass drawing the mesh nodes of a scene from one shadow casting
+// light into one layer of the shadow map array. Binds the same object
+// buffer and joint matrices as the lit shader
+
+[[block]]
+struct Layer {
+    view_proj: mat4x4<f32>;
+};
+
+[[block]]
+struct Object {
+    model: mat4x4<f32>;
+    // Inverse transpose of the model matrix
+    normal: mat4x4<f32>;
+    // First matrix of the object's palette in `joints`
+    joint_offset: u32;
+};
+
+[[block]]
+struct Joints {
+    matrices: array<mat4x4<f32>>;
+};
+
+[[group(0), binding(0)]] var<uniform> layer: Layer;
+[[group(1), binding(0)]] var<uniform> object_data: Object;
+[[group(1), binding(1)]] var<storage, read> joints: Joints;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    return layer.view_proj * object_data.model * vec4<f32>(position, 1.0);
+}
+
+[[stage(vertex)]]
+fn vs_skinned(
+    [[location(0)]] position: vec3<f32>,
+    [[location(3)]] joint_indices: vec4<u32>,
+    [[location(4)]] weights: vec4<f32>,
+) -> [[builtin(position)]] vec4<f32> {
+    let base = object_data.joint_offset;
+    let skin = joints.matrices[base + joint_indices.x] * weights.x
+        + joints.matrices[base + joint_indices.y] * weights.y
+        + joints.matrices[base + joint_indices.z] * weights.z
+        + joints.matrices[base + joint_indices.w] * weights.w;
+    return layer.view_proj * object_data.model * skin
+        * vec4<f32>(position, 1.0);
+}
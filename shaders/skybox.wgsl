@@ -0,0 +1,44 @@
+// Draws the surroundings stored in a cubemap behind everything else, with a
+// full-screen triangle at the far plane looking up the direction of each
+// pixel
+
+[[block]]
+struct Sky {
+    // Inverse of the projection times the rotation of the view
+    inverse_view_proj: mat4x4<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> sky: Sky;
+[[group(0), binding(1)]] var cubemap: texture_cube<f32>;
+[[group(0), binding(2)]] var cubemap_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    // At the far plane, where the depth buffer is cleared to
+    out.position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let far = sky.inverse_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = far.xyz / far.w;
+
+    var color = textureSample(cubemap, cubemap_sampler, direction).rgb;
+    if (sky.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
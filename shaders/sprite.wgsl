@@ -0,0 +1,43 @@
+// Textured and tinted quads of a sprite batch, placed on the CPU
+
+[[block]]
+struct View {
+    view_proj: mat4x4<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> view: View;
+[[group(1), binding(0)]] var sprite_texture: texture_2d<f32>;
+[[group(1), binding(1)]] var sprite_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    // In world units of the 2D camera
+    [[location(0)]] position: vec2<f32>,
+    [[location(1)]] uv: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = view.view_proj * vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = textureSample(sprite_texture, sprite_sampler, in.uv);
+
+    var color = texel.rgb * in.color.rgb;
+    if (view.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, texel.a * in.color.a);
+}
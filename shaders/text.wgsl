@@ -0,0 +1,49 @@
+// Screen space quads of glyphs, covered by the coverage the font
+// rasterized into the glyph atlas
+
+[[block]]
+struct Screen {
+    // In pixels
+    size: vec2<f32>;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> screen: Screen;
+[[group(0), binding(1)]] var atlas: texture_2d<f32>;
+[[group(0), binding(2)]] var atlas_sampler: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    // In pixels from the top left corner of the screen
+    [[location(0)]] position: vec2<f32>,
+    // In pixels of the atlas, which grows as glyphs get added
+    [[location(1)]] uv: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    let ndc = position / screen.size * vec2<f32>(2.0, -2.0)
+        + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = uv / vec2<f32>(textureDimensions(atlas));
+    out.color = color;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+
+    var color = in.color.rgb;
+    if (screen.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+    return vec4<f32>(color, in.color.a * coverage);
+}
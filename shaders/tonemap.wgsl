@@ -0,0 +1,54 @@
+// Maps the HDR scene to displayable [0, 1] values. Expects fullscreen.wgsl
+// to be prepended
+
+[[block]]
+struct Tonemap {
+    exposure: f32;
+    // 0 = Reinhard, 1 = ACES, anything else = clamp
+    operator: u32;
+    // 1 if the target doesn't encode sRGB by itself
+    encode_srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: Tonemap;
+[[group(0), binding(1)]] var hdr_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var hdr_sampler: sampler;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (color + vec3<f32>(1.0));
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp(
+        (color * (a * color + b)) / (color * (c * color + d) + e),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0),
+    );
+}
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let hdr = textureSample(hdr_texture, hdr_sampler, in.uv);
+    let exposed = hdr.rgb * settings.exposure;
+
+    var color: vec3<f32>;
+    if (settings.operator == 0u) {
+        color = reinhard(exposed);
+    } elseif (settings.operator == 1u) {
+        color = aces(exposed);
+    } else {
+        color = clamp(exposed, vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+
+    if (settings.encode_srgb == 1u) {
+        color = linear_to_srgb(color);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,29 @@
+// Darkens the corners of the image. Expects fullscreen.wgsl to be prepended
+
+[[block]]
+struct Vignette {
+    // How dark the corners get, 0 disables the effect
+    strength: f32;
+    // Distance from the center where darkening starts
+    radius: f32;
+    // Width of the transition between untouched and darkened
+    softness: f32;
+    aspect: f32;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: Vignette;
+[[group(0), binding(1)]] var source_texture: texture_2d<f32>;
+[[group(0), binding(2)]] var source_sampler: sampler;
+
+[[stage(fragment)]]
+fn fs_main(in: FullscreenOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let offset = (in.uv - vec2<f32>(0.5)) * vec2<f32>(settings.aspect, 1.0);
+    let t = clamp(
+        (length(offset) - settings.radius) / max(settings.softness, 0.0001),
+        0.0,
+        1.0,
+    );
+    let falloff = t * t * (3.0 - 2.0 * t);
+    return vec4<f32>(color.rgb * (1.0 - falloff * settings.strength), color.a);
+}
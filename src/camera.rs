@@ -0,0 +1,409 @@
+use cgmath::{
+    ortho, perspective, vec2, Deg, InnerSpace, Matrix4, Point3, Rad, Vector2,
+    Vector3,
+};
+
+use crate::{
+    render::Frustum,
+    window::{
+        input::{Key, MouseButton},
+        Window,
+    },
+};
+
+/// cgmath builds OpenGL matrices with depth in [-1, 1], wgpu expects [0, 1]
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// Keeps the camera from flipping over when looking straight up or down
+const MAX_PITCH: Rad<f32> = Rad(std::f32::consts::FRAC_PI_2 * 0.99);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fovy: Deg<f32>,
+        near: f32,
+        far: f32,
+    },
+    /// `height` world units fit vertically in the view
+    Orthographic {
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let projection = match *self {
+            Projection::Perspective { fovy, near, far } => {
+                perspective(fovy, aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (x, y) = (height * aspect / 2.0, height / 2.0);
+                ortho(-x, x, -y, y, near, far)
+            }
+        };
+
+        OPENGL_TO_WGPU * projection
+    }
+}
+
+/// Viewpoint looking down -Z when `yaw` and `pitch` are zero. Positive yaw
+/// turns right, positive pitch looks up
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub projection: Projection,
+    aspect: f32,
+}
+
+impl Camera {
+    pub fn new(
+        position: Point3<f32>,
+        projection: Projection,
+        aspect: f32,
+    ) -> Camera {
+        Camera {
+            position,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+            projection,
+            aspect,
+        }
+    }
+
+    /// Follows the window size so the image never stretches. Call once per
+    /// frame after `Window::update`
+    pub fn update(&mut self, window: &Window) {
+        if window.did_resize && window.height > 0 {
+            self.aspect = window.width as f32 / window.height as f32;
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+
+        Vector3::new(sin_yaw * cos_pitch, sin_pitch, -cos_yaw * cos_pitch)
+    }
+
+    /// Horizontal direction to the right of the view
+    pub fn right(&self) -> Vector3<f32> {
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+
+        Vector3::new(cos_yaw, 0.0, sin_yaw)
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.right().cross(self.forward())
+    }
+
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = (target - self.position).normalize();
+
+        self.yaw = Rad(direction.x.atan2(-direction.z));
+        self.pitch = Rad(direction.y.asin());
+    }
+
+    /// Turns by the given angles, keeping the pitch away from the poles
+    pub fn rotate(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw += yaw;
+        self.pitch =
+            Rad((self.pitch + pitch).0.clamp(-MAX_PITCH.0, MAX_PITCH.0));
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection.matrix(self.aspect)
+    }
+
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view()
+    }
+
+    /// Volume the camera sees, to skip what's outside of it
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_proj())
+    }
+
+    /// World space corners of the slice of the view between the distances
+    /// `near` and `far`, the four near ones first
+    pub fn frustum_corners(&self, near: f32, far: f32) -> [Point3<f32>; 8] {
+        let half_height = |distance: f32| match self.projection {
+            Projection::Perspective { fovy, .. } => {
+                distance * (Rad::from(fovy) / 2.0).0.tan()
+            }
+            Projection::Orthographic { height, .. } => height / 2.0,
+        };
+        let (forward, right, up) = (self.forward(), self.right(), self.up());
+
+        let mut corners = [self.position; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let distance = if i < 4 { near } else { far };
+            let y = half_height(distance);
+            let x = y * self.aspect;
+            let (sx, sy) = match i % 4 {
+                0 => (-1.0, -1.0),
+                1 => (1.0, -1.0),
+                2 => (1.0, 1.0),
+                _ => (-1.0, 1.0),
+            };
+
+            *corner += forward * distance + right * (x * sx) + up * (y * sy);
+        }
+
+        corners
+    }
+}
+
+/// Moves a camera from user input
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, window: &Window, dt: f32);
+}
+
+/// WASD to move, Q and E to go down and up, shift to go faster. Holding the
+/// right mouse button looks around and the wheel changes the speed
+pub struct FlyController {
+    /// Units per second
+    pub speed: f32,
+    /// Radians per pixel of mouse movement
+    pub sensitivity: f32,
+}
+
+impl FlyController {
+    pub fn new(speed: f32) -> FlyController {
+        FlyController {
+            speed,
+            sensitivity: 0.004,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, window: &Window, dt: f32) {
+        if window.getbutton(MouseButton::Right) {
+            let (dx, dy) = window.mouse_delta;
+            camera.rotate(
+                Rad(dx as f32 * self.sensitivity),
+                Rad(-dy as f32 * self.sensitivity),
+            );
+        }
+        self.speed *= 1.25_f32.powi(window.scroll_delta);
+
+        let forward = camera.forward();
+        let right = camera.right();
+        let mut direction = Vector3::new(0.0, 0.0, 0.0);
+        for (key, axis) in [
+            (Key::W, forward),
+            (Key::S, -forward),
+            (Key::D, right),
+            (Key::A, -right),
+            (Key::E, Vector3::unit_y()),
+            (Key::Q, -Vector3::unit_y()),
+        ] {
+            if window.getkey(key) {
+                direction += axis;
+            }
+        }
+
+        if direction.magnitude2() > 0.0 {
+            let boost = if window.getkey(Key::LeftShift) {
+                4.0
+            } else {
+                1.0
+            };
+            camera.position += direction.normalize() * self.speed * boost * dt;
+        }
+    }
+}
+
+/// Circles around `target`. Dragging with the left mouse button rotates,
+/// with the middle one pans and the wheel zooms
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Radians per pixel of mouse movement
+    pub sensitivity: f32,
+}
+
+impl OrbitController {
+    /// Orbits around `target` starting from where `camera` currently is
+    pub fn new(camera: &mut Camera, target: Point3<f32>) -> OrbitController {
+        camera.look_at(target);
+
+        OrbitController {
+            target,
+            distance: (camera.position - target).magnitude(),
+            sensitivity: 0.006,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, window: &Window, _dt: f32) {
+        let (dx, dy) = window.mouse_delta;
+
+        if window.getbutton(MouseButton::Left) {
+            camera.rotate(
+                Rad(dx as f32 * self.sensitivity),
+                Rad(-dy as f32 * self.sensitivity),
+            );
+        }
+        if window.getbutton(MouseButton::Middle) {
+            // Pans at the speed the target moves on screen
+            let scale = self.distance * self.sensitivity * 0.25;
+            self.target +=
+                (camera.up() * dy as f32 - camera.right() * dx as f32) * scale;
+        }
+        self.distance =
+            (self.distance * 0.9_f32.powi(window.scroll_delta)).max(0.1);
+
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+/// Orthographic view of the XY plane for 2D drawing, e.g. with
+/// `SpriteBatch`. Looks at `position` with Y going up, or down when
+/// `height` is negative
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2d {
+    pub position: Vector2<f32>,
+    /// World units fitting vertically in the view
+    pub height: f32,
+    /// Counterclockwise turn of the view
+    pub rotation: Rad<f32>,
+}
+
+impl Camera2d {
+    pub fn new(position: Vector2<f32>, height: f32) -> Camera2d {
+        Camera2d {
+            position,
+            height,
+            rotation: Rad(0.0),
+        }
+    }
+
+    /// One unit per pixel of a `width` by `height` target, from its top
+    /// left corner with Y going down, for HUDs
+    pub fn screen(width: u32, height: u32) -> Camera2d {
+        Camera2d::new(
+            vec2(width as f32 / 2.0, height as f32 / 2.0),
+            -(height as f32),
+        )
+    }
+
+    pub fn view_proj(&self, aspect: f32) -> Matrix4<f32> {
+        let (x, y) = (self.height.abs() * aspect / 2.0, self.height / 2.0);
+        let view = Matrix4::from_angle_z(-self.rotation)
+            * Matrix4::from_translation(-self.position.extend(0.0));
+
+        OPENGL_TO_WGPU * ortho(-x, x, -y, y, -1.0, 1.0) * view
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, point3, vec3, Transform};
+
+    fn camera() -> Camera {
+        Camera::new(
+            point3(0.0, 0.0, 0.0),
+            Projection::Perspective {
+                fovy: Deg(60.0),
+                near: 0.1,
+                far: 100.0,
+            },
+            1.0,
+        )
+    }
+
+    #[test]
+    fn default_orientation() {
+        let camera = camera();
+
+        assert_abs_diff_eq!(camera.forward(), vec3(0.0, 0.0, -1.0));
+        assert_abs_diff_eq!(camera.right(), vec3(1.0, 0.0, 0.0));
+        assert_abs_diff_eq!(camera.up(), vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn look_at_target() {
+        let mut camera = camera();
+        camera.position = point3(1.0, 2.0, 3.0);
+        camera.look_at(point3(4.0, -2.0, 3.0));
+
+        assert_abs_diff_eq!(
+            camera.forward(),
+            vec3(3.0, -4.0, 0.0).normalize(),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = camera();
+        camera.rotate(Rad(0.0), Rad(10.0));
+
+        assert!(camera.pitch < Rad(std::f32::consts::FRAC_PI_2));
+        assert!(camera.forward().y > 0.99);
+    }
+
+    #[test]
+    fn frustum_corners_project_to_the_edges() {
+        let camera = camera();
+        let corners = camera.frustum_corners(1.0, 10.0);
+
+        for (i, corner) in corners.iter().enumerate() {
+            let ndc = camera.view_proj().transform_point(*corner);
+            assert_abs_diff_eq!(ndc.x.abs(), 1.0, epsilon = 1e-4);
+            assert_abs_diff_eq!(ndc.y.abs(), 1.0, epsilon = 1e-4);
+            let distance = if i < 4 { 1.0 } else { 10.0 };
+            assert_abs_diff_eq!(
+                -camera.view().transform_point(*corner).z,
+                distance,
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    #[test]
+    fn depth_range() {
+        let camera = camera();
+        let project =
+            |z: f32| camera.view_proj().transform_point(point3(0.0, 0.0, z)).z;
+
+        assert_abs_diff_eq!(project(-0.1), 0.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(project(-100.0), 1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn camera_2d_screen_and_world() {
+        let screen = Camera2d::screen(640, 480).view_proj(640.0 / 480.0);
+        let project = |matrix: Matrix4<f32>, x: f32, y: f32| {
+            let ndc = matrix.transform_point(point3(x, y, 0.0));
+            (ndc.x, ndc.y)
+        };
+        assert_abs_diff_eq!(project(screen, 0.0, 0.0).0, -1.0);
+        assert_abs_diff_eq!(project(screen, 0.0, 0.0).1, 1.0);
+        assert_abs_diff_eq!(project(screen, 640.0, 480.0).0, 1.0);
+        assert_abs_diff_eq!(project(screen, 640.0, 480.0).1, -1.0);
+
+        let mut camera = Camera2d::new(vec2(10.0, 0.0), 4.0);
+        camera.rotation = Rad(std::f32::consts::FRAC_PI_2);
+        // Turned a quarter counterclockwise, +Y points to the right
+        let (x, y) = project(camera.view_proj(2.0), 10.0, 2.0);
+        assert_abs_diff_eq!(x, 0.5, epsilon = 1e-5);
+        assert_abs_diff_eq!(y, 0.0, epsilon = 1e-5);
+    }
+}
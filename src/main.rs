@@ -1,186 +1,1133 @@
+mod camera;
 // mod math;
 mod render;
+mod scene;
 mod util;
 mod window;
 
-use bytemuck::{cast_slice, Pod, Zeroable};
-use cgmath::{perspective, point3, vec3, Matrix, Matrix4, Vector3, Vector4};
+use camera::{
+    Camera, Camera2d, CameraController, FlyController, OrbitController,
+    Projection,
+};
+use cgmath::{
+    point3, vec3, Deg, EuclideanSpace, Matrix4, Point3, Quaternion, Rad,
+    Rotation3, SquareMatrix,
+};
 use render::{
-    Buffer, PresentMode, Render, Shader, ShaderAttribute, ShaderAttributeType,
+    AnimationClip, Atlas, AtlasBuilder, Backend, Color, ColorSpace,
+    CommandBuffer, DebugDraw, DeferredObject, DeferredRenderer, Features,
+    FilterMode, Font, Framebuffer, Gui, Light, Lights, Material, Mesh, Model,
+    MorphAnimation, PerfOverlay, PointLight, PostProcessChain,
+    PostProcessConfig, PowerPreference, Render, RenderConfig, ShadowConfig,
+    Sprite, SpriteBatch, SpriteTexture, SurfaceFormat, TextAlign, TextRenderer,
+    TextStyle, Texture, TextureFormat, TonemapOperator, Tonemapper,
 };
+use scene::{NodeId, Scene, SceneRenderer, Transform};
 use std::{
-    fs::File,
-    io::Read,
+    env,
     time::{Instant, SystemTime},
 };
-use util::srand;
+use util::{randf, srand};
 use window::{input::Key, Window};
 
-use crate::util::IntoArray;
+fn moon_colors() -> [Color; 2] {
+    [
+        Color::from_srgb(0.4, 0.6, 1.0, 1.0),
+        Color::from_srgb(1.0, 0.9, 0.4, 1.0),
+    ]
+}
+
+/// Glowing materials of the moons, in the order of `moon_colors`
+fn moon_materials() -> Vec<Material> {
+    moon_colors()
+        .into_iter()
+        .map(|color| Material {
+            emissive: color,
+            ..Material::from_color(color)
+        })
+        .collect()
+}
+
+/// Every node of the loaded model, plus copies of its first mesh circling
+/// above it to show off the hierarchy. The moons use the materials right
+/// after the ones of the model. Returns the scene, the node to spin every
+/// frame and the nodes of the model
+fn create_scene(model: &Model) -> (Scene, NodeId, Vec<NodeId>) {
+    let mut scene = Scene::new();
+    let world = scene.add_node("world", None, Transform::default());
+    let ids = scene.add_model(&model.nodes, Some(world));
+    let moon_material = model.materials.len();
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Debug)]
-struct Vertex {
-    pos: [f32; 3],
-    color: [f32; 3],
+    let spinner = scene.add_node(
+        "spinner",
+        Some(world),
+        Transform::from_translation(vec3(2.0, 2.5, 1.5)),
+    );
+    for i in 0..moon_colors().len() {
+        let offset = if i == 0 { 1.5 } else { -1.5 };
+        let moon = scene.add_node(
+            &format!("moon{}", i),
+            Some(spinner),
+            Transform {
+                translation: vec3(offset, 0.0, 0.0),
+                scale: vec3(0.1, 0.1, 0.1),
+                ..Default::default()
+            },
+        );
+        scene.node_mut(moon).mesh = Some(0);
+        scene.node_mut(moon).material = Some(moon_material + i);
+    }
+
+    (scene, spinner, ids)
+}
+
+/// Loops the first animation of the model: the joint matrices of every
+/// node skinned by the skeletons it moves and the morph target weights of
+/// the nodes it changes. `ids` are the scene nodes of `Model::nodes`
+fn animate(scene: &mut Scene, model: &Model, ids: &[NodeId], time: f32) {
+    let name = match model
+        .animations
+        .iter()
+        .map(|clip| &clip.name)
+        .chain(model.morph_animations.iter().map(|morph| &morph.name))
+        .next()
+    {
+        Some(name) => name,
+        None => return,
+    };
+    let clips = model.animations.iter().filter(|clip| &clip.name == name);
+    let morphs = model
+        .morph_animations
+        .iter()
+        .filter(|morph| &morph.name == name);
+    let duration = clips
+        .clone()
+        .map(AnimationClip::duration)
+        .chain(morphs.clone().map(MorphAnimation::duration))
+        .fold(0.0, f32::max);
+    let time = time % duration.max(f32::EPSILON);
+
+    for clip in clips {
+        let skeleton = &model.skeletons[clip.skeleton];
+        let mut pose = skeleton.rest_pose();
+        clip.sample(time, &mut pose);
+        let joints = skeleton.joint_matrices(&pose);
+
+        let skinned = scene
+            .nodes()
+            .filter(|(_, node)| node.skin == Some(clip.skeleton))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in skinned {
+            scene.node_mut(id).joints = joints.clone();
+        }
+    }
+
+    for morph in morphs {
+        let weights = morph.sample(time);
+        let node = &model.nodes[morph.node];
+        let id = ids[morph.node];
+        // The meshes of the node past the first are drawn by children
+        let extras = (1..node.meshes.len())
+            .filter_map(|i| {
+                let name = format!("{}.{}", node.name, i);
+                scene
+                    .node(id)
+                    .children()
+                    .iter()
+                    .copied()
+                    .find(|&child| scene.node(child).name == name)
+            })
+            .collect::<Vec<_>>();
+        for id in std::iter::once(id).chain(extras) {
+            scene.node_mut(id).weights = weights.clone();
+        }
+    }
+}
+
+/// A dim sun, a spot light shining down on the objects, both casting
+/// shadows, and a point light on each moon, rebuilt every frame as the
+/// moons move
+fn set_scene_lights(lights: &mut Lights, scene: &mut Scene) {
+    scene.update();
+    lights.clear();
+    lights.add(Light::Directional {
+        direction: vec3(-0.4, -1.0, -0.6),
+        color: Color::WHITE,
+        intensity: 0.3,
+        shadows: true,
+    });
+    lights.add(Light::Spot {
+        position: point3(0.0, 6.0, 0.0),
+        direction: vec3(0.0, -1.0, 0.0),
+        range: 15.0,
+        inner: Deg(20.0),
+        outer: Deg(30.0),
+        color: Color::from_srgb(1.0, 0.95, 0.8, 1.0),
+        intensity: 20.0,
+        shadows: true,
+    });
+
+    for (i, color) in moon_colors().into_iter().enumerate() {
+        let moon = match scene.find(&format!("moon{}", i)) {
+            Some(moon) => scene.node(moon),
+            None => continue,
+        };
+        lights.add(Light::Point {
+            position: Point3::from_homogeneous(moon.world().w),
+            range: 6.0,
+            color,
+            intensity: 8.0,
+        });
+    }
 }
 
-impl Vertex {
-    fn new(pos: [f32; 3], color: [f32; 3]) -> Vertex {
-        Vertex { pos, color }
+/// Axes of the model nodes, drawn over everything, then the lights of the
+/// scene and the bounds of its meshes, hidden behind it: oriented along
+/// their node in green, as culled against the view in yellow
+fn draw_debug(
+    debug_draw: &mut DebugDraw,
+    scene: &Scene,
+    model_nodes: &[NodeId],
+    meshes: &[Mesh],
+    lights: &Lights,
+) {
+    debug_draw.set_depth_test(false);
+    for id in model_nodes {
+        debug_draw.axes(scene.node(*id).world(), 0.5);
+    }
+    debug_draw.set_depth_test(true);
+    for (_, node) in scene.nodes() {
+        let bounds = match node.mesh.and_then(|mesh| meshes.get(mesh)) {
+            Some(mesh) => mesh.bounds(),
+            None => continue,
+        };
+        let world = node.world();
+        debug_draw.oriented_box(
+            world * Matrix4::from_translation(bounds.center().to_vec()),
+            bounds.extents(),
+            Color::new(0.2, 0.9, 0.3, 1.0),
+        );
+        let culled = bounds.transform(&world);
+        debug_draw.aabb(culled.min, culled.max, Color::new(0.9, 0.8, 0.1, 1.0));
+    }
+    for light in lights.iter() {
+        debug_draw.light(light);
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug)]
-struct Uniforms {
-    color: Vector4<f32>,
-    mvp: Matrix4<f32>,
+/// Options taken from the command line. `--list-adapters` prints the
+/// adapters that `--adapter <index>` can select and exits
+struct Args {
+    render_config: RenderConfig,
+    list_adapters: bool,
+    hdr: bool,
+    tonemap: TonemapOperator,
+    postfx: Option<PostProcessConfig>,
+    deferred: bool,
+    lights: usize,
+    shadows: ShadowConfig,
+    /// OBJ or glTF file drawn by the forward path
+    model: String,
+    /// Equirectangular HDR image lighting the forward path, which then
+    /// shades with PBR
+    environment: Option<String>,
+    /// Equirectangular panorama or six cubemap faces drawn behind the
+    /// forward path. Defaults to the environment when there is one
+    skybox: Option<Vec<String>>,
+    /// Draws the axes of the model nodes and the lights over the forward
+    /// path
+    debug: bool,
+    /// TTF or OTF file the performance overlay is drawn with
+    font: String,
+    /// Shows an inspector window to change rendering settings with the mouse
+    gui: bool,
+    /// Scale of the inspector, instead of the one of the display
+    ui_scale: Option<f32>,
+    /// Comma separated images packed into an atlas and drawn as a row of 2D
+    /// sprites at the bottom of the screen
+    sprite: Option<String>,
+    /// File the sprite atlas is loaded from when it holds every image,
+    /// written after packing them otherwise
+    atlas_cache: Option<String>,
 }
 
-fn create_shader(render: &Render) -> Shader {
-    let mut vshader_file =
-        File::open("./shaders/vert.spv").expect("Failed to open vert.spv");
-    let mut vsrc = vec![];
-    vshader_file
-        .read_to_end(&mut vsrc)
-        .expect("Failed to read vert.spv");
+/// Depth attachment of the forward scene, offscreen or on the swapchain
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// Depth buffer the scene tests against when drawn straight into the
+/// swapchain, or into the input of the post-processing effects
+fn create_swapchain_depth(render: &Render) -> Texture {
+    let (width, height) = render.size();
 
-    let mut fshader_file =
-        File::open("./shaders/frag.spv").expect("Failed to open frag.spv");
-    let mut fsrc = vec![];
-    fshader_file
-        .read_to_end(&mut fsrc)
-        .expect("Failed to read frag.spv");
+    render.create_depth_texture(width, height, DEPTH_FORMAT)
+}
 
-    render.create_shader(&vsrc, Some(&fsrc))
+/// Offscreen target the scene is drawn into when running with `--hdr`,
+/// tonemapped into the swapchain at the end of the frame
+struct HdrTarget {
+    framebuffer: Framebuffer,
+    tonemapper: Tonemapper,
+    depth: bool,
 }
 
-fn create_vertex_data(render: &Render) -> (Buffer, Buffer, usize) {
-    let (models, _) =
-        tobj::load_obj("./assets/untitled.obj", &tobj::LoadOptions::default())
-            .expect("Couldn't open obj file");
+impl HdrTarget {
+    const FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+    fn create_framebuffer(render: &Render, depth: bool) -> Framebuffer {
+        let (width, height) = render.size();
+
+        render.create_framebuffer(
+            width,
+            height,
+            &[Self::FORMAT],
+            if depth { Some(DEPTH_FORMAT) } else { None },
+        )
+    }
+
+    /// The deferred renderer brings its own depth buffer, so `depth` is only
+    /// needed by the forward path
+    fn new(
+        render: &Render,
+        operator: TonemapOperator,
+        depth: bool,
+    ) -> HdrTarget {
+        let framebuffer = Self::create_framebuffer(render, depth);
+        let mut tonemapper = render.create_tonemapper(render.surface_format());
+        tonemapper.set_input(render, &framebuffer);
+        tonemapper.set_operator(render, operator);
+
+        HdrTarget {
+            framebuffer,
+            tonemapper,
+            depth,
+        }
+    }
+
+    fn resize(&mut self, render: &Render) {
+        self.framebuffer = Self::create_framebuffer(render, self.depth);
+        self.tonemapper.set_input(render, &self.framebuffer);
+    }
+}
+
+/// Every object of the sample OBJ file lit by `--lights` randomly placed
+/// point lights, drawn with `--deferred`
+struct DeferredScene {
+    renderer: DeferredRenderer,
+    meshes: Vec<Mesh>,
+    objects: Vec<DeferredObject>,
+}
+
+impl DeferredScene {
+    fn new(render: &Render, light_count: usize) -> DeferredScene {
+        let mut renderer = render.create_deferred_renderer(HdrTarget::FORMAT);
+        let meshes = render
+            .load_model("./assets/untitled.obj")
+            .unwrap_or_else(|err| panic!("{}", err))
+            .meshes;
+        let objects = meshes
+            .iter()
+            .map(|_| {
+                let albedo = Color::from_srgb(
+                    0.5 + randf() * 0.5,
+                    0.5 + randf() * 0.5,
+                    0.5 + randf() * 0.5,
+                    1.0,
+                );
+                renderer.create_object(
+                    render,
+                    Matrix4::identity(),
+                    albedo,
+                    0.2 + randf() * 0.6,
+                    0.0,
+                )
+            })
+            .collect();
+
+        // Spread over the floor of the sample scene
+        let lights = (0..light_count)
+            .map(|_| PointLight {
+                position: [
+                    -1.0 + randf() * 6.0,
+                    0.1 + randf() * 1.5,
+                    -1.0 + randf() * 5.0,
+                ],
+                radius: 0.5 + randf() * 1.5,
+                color: Color::new(randf(), randf(), randf(), 1.0),
+                intensity: 1.0 + randf() * 3.0,
+            })
+            .collect::<Vec<_>>();
+        renderer.set_lights(render, &lights);
+
+        DeferredScene {
+            renderer,
+            meshes,
+            objects,
+        }
+    }
 
-    let mesh = &models[0].mesh;
-    let mut verts = Vec::new();
-    for x in 0..mesh.positions.len() / 3 {
-        let ps = mesh
-            .positions
+    fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let objects = self
+            .meshes
             .iter()
-            .skip(3 * x)
-            .take(3)
-            .collect::<IntoArray<&f32, 3>>()
-            .array;
-        let ps = ps.map(|x| *x);
-        let colors = if mesh.vertex_color.len() > 0 {
-            mesh.vertex_color
-                .iter()
-                .skip(3 * x)
-                .take(3)
-                .collect::<IntoArray<&f32, 3>>()
-                .array
-                .map(|x| *x)
+            .zip(self.objects.iter())
+            .collect::<Vec<_>>();
+
+        self.renderer.draw(commands, &objects, output)
+    }
+}
+
+fn print_usage() {
+    println!(
+        "usage: conc [--backend <vulkan|gl|metal|dx12|primary|all>[,...]] \
+         [--power <low|high>] [--fallback] [--adapter <index>] \
+         [--surface-format <srgb|linear>] [--hdr] \
+         [--tonemap <aces|reinhard|clamp>] [--postfx <config.json>] \
+         [--deferred] [--lights <count>] [--shadow-splits <distance>[,...]] \
+         [--model <file.obj|file.gltf|file.glb>] [--pbr] \
+         [--environment <file.hdr>] \
+         [--skybox <panorama|+x,-x,+y,-y,+z,-z>] [--debug] \
+         [--font <file.ttf>] [--gui] [--ui-scale <scale>] \
+         [--sprite <image>[,<image>...]] [--atlas-cache <file>] \
+         [--list-adapters]"
+    );
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        render_config: RenderConfig {
+            // Per pass GPU times in the performance overlay, when supported
+            optional_features: Features::TIMESTAMP_QUERY,
+            ..RenderConfig::default()
+        },
+        list_adapters: false,
+        hdr: false,
+        tonemap: TonemapOperator::Aces,
+        postfx: None,
+        deferred: false,
+        lights: 200,
+        shadows: ShadowConfig::default(),
+        model: "./assets/untitled.obj".to_string(),
+        environment: None,
+        skybox: None,
+        debug: false,
+        font: "./assets/DejaVuSansMono.ttf".to_string(),
+        gui: false,
+        ui_scale: None,
+        sprite: None,
+        atlas_cache: None,
+    };
+    let mut argv = env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        let mut value = |name: &str| {
+            argv.next().ok_or(format!("Missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "--backend" => {
+                args.render_config.backends = value("--backend")?
+                    .split(',')
+                    .map(|x| x.parse::<Backend>())
+                    .collect::<Result<_, _>>()?;
+            }
+            "--power" => {
+                args.render_config.power_preference =
+                    value("--power")?.parse::<PowerPreference>()?;
+            }
+            "--adapter" => {
+                let index = value("--adapter")?;
+                args.render_config.adapter =
+                    Some(index.parse().map_err(|_| {
+                        format!("Invalid adapter index '{}'", index)
+                    })?);
+            }
+            "--surface-format" => {
+                args.render_config.surface_format =
+                    value("--surface-format")?.parse::<SurfaceFormat>()?;
+            }
+            "--fallback" => args.render_config.force_fallback_adapter = true,
+            "--list-adapters" => args.list_adapters = true,
+            "--hdr" => args.hdr = true,
+            "--tonemap" => {
+                args.hdr = true;
+                args.tonemap =
+                    value("--tonemap")?.parse::<TonemapOperator>()?;
+            }
+            "--postfx" => {
+                args.postfx =
+                    Some(PostProcessConfig::load(value("--postfx")?)?);
+            }
+            "--deferred" => {
+                args.deferred = true;
+                args.hdr = true;
+            }
+            "--lights" => {
+                let count = value("--lights")?;
+                args.lights = count
+                    .parse()
+                    .map_err(|_| format!("Invalid light count '{}'", count))?;
+            }
+            "--model" => args.model = value("--model")?,
+            "--pbr" => {
+                args.environment
+                    .get_or_insert("./assets/sky.hdr".to_string());
+            }
+            "--environment" => {
+                args.environment = Some(value("--environment")?);
+            }
+            "--skybox" => {
+                let images = value("--skybox")?
+                    .split(',')
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>();
+                if images.len() != 1 && images.len() != 6 {
+                    return Err(
+                        "Expected a panorama or six cubemap faces".to_string()
+                    );
+                }
+                args.skybox = Some(images);
+            }
+            "--debug" => args.debug = true,
+            "--font" => args.font = value("--font")?,
+            "--gui" => args.gui = true,
+            "--sprite" => args.sprite = Some(value("--sprite")?),
+            "--atlas-cache" => args.atlas_cache = Some(value("--atlas-cache")?),
+            "--ui-scale" => {
+                let scale = value("--ui-scale")?;
+                args.ui_scale =
+                    Some(scale.parse().map_err(|_| {
+                        format!("Invalid UI scale '{}'", scale)
+                    })?);
+            }
+            "--shadow-splits" => {
+                args.shadows.cascade_splits = value("--shadow-splits")?
+                    .split(',')
+                    .map(|x| {
+                        x.parse().map_err(|_| {
+                            format!("Invalid cascade split '{}'", x)
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+
+    Ok(args)
+}
+
+const PERSPECTIVE: Projection = Projection::Perspective {
+    fovy: Deg(67.5),
+    near: 0.1,
+    far: 400.0,
+};
+const ORTHOGRAPHIC: Projection = Projection::Orthographic {
+    height: 8.0,
+    near: 0.1,
+    far: 400.0,
+};
+
+/// C switches between flying and orbiting around the middle of the scene,
+/// P between perspective and orthographic projections
+fn process_camera_keys(
+    window: &Window,
+    camera: &mut Camera,
+    controller: &mut Box<dyn CameraController>,
+    orbiting: &mut bool,
+) {
+    if window.keypressed(Key::C) {
+        *orbiting = !*orbiting;
+        *controller = if *orbiting {
+            Box::new(OrbitController::new(camera, point3(2.0, 0.0, 1.5)))
         } else {
-            [1.0, 1.0, 1.0]
+            Box::new(FlyController::new(4.0))
         };
-        let vertex = Vertex::new(ps, colors);
-        verts.push(vertex);
+        println!("camera: {}", if *orbiting { "orbit" } else { "fly" });
+    }
+
+    if window.keypressed(Key::P) {
+        camera.projection = if camera.projection == PERSPECTIVE {
+            ORTHOGRAPHIC
+        } else {
+            PERSPECTIVE
+        };
+    }
+}
+
+/// Packs the `--sprite` images, or loads them already packed from `cache`
+fn load_sprite_atlas(
+    paths: &[&str],
+    cache: Option<&str>,
+) -> Result<Atlas, String> {
+    let cached = cache
+        .and_then(|cache| Atlas::load(cache).ok())
+        .filter(|atlas| paths.iter().all(|path| atlas.entry(path).is_some()));
+    if let Some(atlas) = cached {
+        return Ok(atlas);
+    }
+
+    // Sprites are scaled down, two pixels keep their neighbours out of the
+    // texels filtering reads
+    let mut builder = AtlasBuilder::new(1024).padding(2).extrusion(1);
+    for path in paths {
+        builder.add_file(path)?;
+    }
+    let atlas = builder.build()?;
+    if let Some(cache) = cache {
+        atlas.save(cache)?;
+    }
+
+    Ok(atlas)
+}
+
+/// Row of sprites along the bottom of the screen for `--sprite`, cycling
+/// through the atlas `frames`, the one in the middle spinning in front of its
+/// neighbours
+fn draw_hud(
+    sprites: &mut SpriteBatch,
+    frames: &[(SpriteTexture, [[f32; 2]; 2])],
+    spin: f32,
+    screen_height: u32,
+) {
+    const SIZE: f32 = 48.0;
+    const MARGIN: f32 = 16.0;
+
+    for i in 0..5 {
+        let position = [
+            MARGIN + SIZE / 2.0 + i as f32 * SIZE * 0.8,
+            screen_height as f32 - MARGIN - SIZE / 2.0,
+        ];
+        let (texture, uv) = frames[i % frames.len()];
+        let mut sprite = Sprite::new(texture, position, [SIZE, SIZE]);
+        sprite.uv = uv;
+        sprite.color = Color::new(1.0, 1.0 - i as f32 * 0.2, 1.0, 0.9);
+        if i == 2 {
+            sprite.rotation = spin;
+            sprite.depth = -1.0;
+        }
+        sprites.sprite(&sprite);
     }
+}
+
+/// Name of the model centered at the top of the screen, and how the camera
+/// moves and projects in the top right corner
+fn draw_status(
+    text: &mut TextRenderer,
+    model: &str,
+    camera: &Camera,
+    orbiting: bool,
+    (width, _): (u32, u32),
+) {
+    const MARGIN: f32 = 8.0;
 
-    let indices = mesh.indices.iter().map(|x| *x as u16).collect::<Vec<u16>>();
+    let style = |align| TextStyle {
+        align,
+        ..TextStyle::default()
+    };
+    text.text(
+        model,
+        [width as f32 / 2.0, MARGIN],
+        &style(TextAlign::Center),
+    );
 
-    (
-        render.create_vertex_buffer(cast_slice(&verts)),
-        render.create_index_buffer(cast_slice(&indices)),
-        indices.len(),
-    )
+    let camera = format!(
+        "{} camera, {}",
+        if orbiting { "orbit" } else { "fly" },
+        if camera.projection == PERSPECTIVE {
+            "perspective"
+        } else {
+            "orthographic"
+        }
+    );
+    text.text(
+        &camera,
+        [width as f32 - MARGIN, MARGIN],
+        &style(TextAlign::Right),
+    );
 }
 
-fn process_keys(window: &mut Window) {
+/// Window of the `--gui` flag showing the frame rate and changing the
+/// settings otherwise bound to keys
+fn inspector(
+    ctx: &egui::Context,
+    render: &mut Render,
+    hdr: Option<&mut HdrTarget>,
+    spin_speed: &mut f32,
+    culling: &mut bool,
+    atlas: Option<egui::TextureId>,
+    fps: f32,
+) {
+    egui::Window::new("Inspector").show(ctx, |ui| {
+        ui.label(format!("{:.1} fps", fps));
+
+        let mut vsync = render.present_mode() == render::PresentMode::Fifo;
+        if ui.checkbox(&mut vsync, "vsync").changed() {
+            render.toggle_vsync();
+        }
+        ui.add(egui::Slider::new(spin_speed, 0.0..=4.0).text("spin speed"));
+        ui.checkbox(culling, "frustum culling");
+        if let Some(atlas) = atlas {
+            ui.collapsing("sprite atlas", |ui| {
+                ui.image((atlas, egui::vec2(256.0, 256.0)))
+            });
+        }
+
+        if let Some(hdr) = hdr {
+            let tonemapper = &mut hdr.tonemapper;
+            let mut exposure = tonemapper.exposure();
+            let slider = egui::Slider::new(&mut exposure, 0.05..=8.0)
+                .logarithmic(true)
+                .text("exposure");
+            if ui.add(slider).changed() {
+                tonemapper.set_exposure(render, exposure);
+            }
+            let operator = format!("tonemap: {:?}", tonemapper.operator());
+            if ui.button(operator).clicked() {
+                tonemapper.set_operator(render, tonemapper.operator().next());
+            }
+        }
+    });
+}
+
+fn process_keys(
+    window: &mut Window,
+    render: &mut Render,
+    hdr: Option<&mut HdrTarget>,
+    postfx: Option<&mut PostProcessChain>,
+) {
     if window.getkey(Key::Escape) {
         window.close();
     }
+
+    if window.keypressed(Key::V) {
+        let vsync = render.toggle_vsync();
+        println!(
+            "vsync {} ({:?} requested)",
+            if vsync { "on" } else { "off" },
+            render.present_mode()
+        );
+    }
+
+    if let Some(hdr) = hdr {
+        let tonemapper = &mut hdr.tonemapper;
+
+        if window.keypressed(Key::T) {
+            tonemapper.set_operator(render, tonemapper.operator().next());
+            println!("tonemap operator: {:?}", tonemapper.operator());
+        }
+        if window.keypressed(Key::Equal) {
+            tonemapper.set_exposure(render, tonemapper.exposure() * 1.25);
+            println!("exposure: {:.2}", tonemapper.exposure());
+        }
+        if window.keypressed(Key::Minus) {
+            tonemapper.set_exposure(render, tonemapper.exposure() / 1.25);
+            println!("exposure: {:.2}", tonemapper.exposure());
+        }
+    }
+
+    if let Some(postfx) = postfx {
+        let keys = [
+            Key::Number1,
+            Key::Number2,
+            Key::Number3,
+            Key::Number4,
+            Key::Number5,
+            Key::Number6,
+            Key::Number7,
+            Key::Number8,
+            Key::Number9,
+        ];
+
+        for (i, key) in keys.into_iter().enumerate() {
+            if !window.keypressed(key) {
+                continue;
+            }
+            if let Some((name, enabled)) = postfx.toggle(i) {
+                println!("{} {}", name, if enabled { "on" } else { "off" });
+            }
+        }
+    }
 }
 
-async fn run(mut window: Window) {
-    let mut render = Render::new(&window, PresentMode::Fifo).await;
+async fn run(mut window: Window, args: Args) {
+    let mut render = Render::new(&window, &args.render_config).await;
+    println!(
+        "Using adapter: {} ({:?})",
+        render.adapter_info().name,
+        render.surface_format()
+    );
     let mut dt_time = Instant::now();
 
-    let (vertices, indices, n_indices) = create_vertex_data(&render);
-    println!("n_indices: {}", n_indices);
-    let shader_layout = render.create_shader_layout([
-        ShaderAttribute::new(ShaderAttributeType::Vec3, 0),
-        ShaderAttribute::new(ShaderAttributeType::Vec3, 1),
-    ]);
-    let shader = create_shader(&render);
-    let bind_layout = render.create_bind_group_layout::<Uniforms>();
-    let pipeline =
-        render.create_pipeline(&shader_layout, &shader, &bind_layout);
-
-    let projection =
-        perspective(cgmath::Deg(67.5_f32), 640.0 / 480.0, 1.0, 400.0);
-    let orientation = Matrix4::look_at_rh(
-        point3(0.0_f32, 1.0, 10.0),
-        point3(0.0_f32, 0.0, 0.0),
-        Vector3::unit_y(),
+    let model = render
+        .load_model(&args.model)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (mut scene, spinner, model_nodes) = create_scene(&model);
+    let mut hdr = if args.hdr {
+        Some(HdrTarget::new(&render, args.tonemap, !args.deferred))
+    } else {
+        None
+    };
+    let mut postfx = args.postfx.map(|config| {
+        render
+            .create_post_process_chain(&config, render.surface_format())
+            .unwrap_or_else(|err| panic!("Failed to create effects: {}", err))
+    });
+    let format = if hdr.is_some() {
+        HdrTarget::FORMAT
+    } else {
+        render.surface_format()
+    };
+    let depth = Some(DEPTH_FORMAT);
+    let mut swapchain_depth =
+        hdr.is_none().then(|| create_swapchain_depth(&render));
+    let mut scene_renderer = match args.environment {
+        Some(ref path) => {
+            let environment = render
+                .load_environment(path)
+                .unwrap_or_else(|err| panic!("{}", err));
+            SceneRenderer::with_environment(&render, format, depth, environment)
+        }
+        None => SceneRenderer::new(&render, format, depth),
+    };
+    match args.skybox.as_deref() {
+        Some([panorama]) => {
+            let cubemap = render
+                .load_panorama(panorama, 1024)
+                .unwrap_or_else(|err| panic!("{}", err));
+            scene_renderer.set_skybox(&render, Some(&cubemap));
+        }
+        Some(faces) => {
+            let faces: &[String; 6] = faces.try_into().unwrap();
+            let cubemap = render
+                .load_cubemap(faces)
+                .unwrap_or_else(|err| panic!("{}", err));
+            scene_renderer.set_skybox(&render, Some(&cubemap));
+        }
+        None => scene_renderer.set_environment_skybox(&render),
+    }
+    let mut debug_draw = if args.debug {
+        Some(DebugDraw::new(&render, format, depth))
+    } else {
+        None
+    };
+    if args.shadows != *scene_renderer.shadow_config() {
+        scene_renderer.set_shadow_config(&render, args.shadows);
+    }
+    let model_materials = if args.environment.is_some() {
+        model
+            .pbr_materials
+            .iter()
+            .map(|material| {
+                scene_renderer.create_pbr_material(&render, material)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        model
+            .materials
+            .iter()
+            .map(|material| scene_renderer.create_material(&render, material))
+            .collect()
+    };
+    let materials =
+        model_materials
+            .into_iter()
+            .chain(moon_materials().iter().map(|material| {
+                scene_renderer.create_material(&render, material)
+            }))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut camera = Camera::new(
+        point3(0.0, 1.0, 10.0),
+        PERSPECTIVE,
+        window.width as f32 / window.height as f32,
     );
-    let location = Matrix4::from_translation(vec3::<f32>(0.0, -1.0, -10.0));
-    let mvp = projection * location;
-    let uniform_data = Uniforms {
-        color: Vector4::new(1.0_f32, 0.5, 0.5, 1.0),
-        mvp,
+    let mut controller: Box<dyn CameraController> =
+        Box::new(FlyController::new(4.0));
+    let mut orbiting = false;
+
+    let mut deferred = if args.deferred {
+        Some(DeferredScene::new(&render, args.lights))
+    } else {
+        None
+    };
+    let font =
+        Font::load(&args.font, 16.0).unwrap_or_else(|err| panic!("{}", err));
+    let mut text = TextRenderer::new(&render, font, render.surface_format());
+    let mut overlay = PerfOverlay::new();
+    let mut gui = if args.gui {
+        let mut gui = Gui::new(&render, render.surface_format());
+        gui.set_scale(args.ui_scale);
+        Some(gui)
+    } else {
+        None
+    };
+    let mut spin_speed = 1.0;
+    // The batch, the page and rect of every image and the atlas pages, kept
+    // alive along with the batch drawing them
+    let mut sprites = args.sprite.as_ref().map(|paths| {
+        let paths = paths.split(',').collect::<Vec<_>>();
+        let atlas = load_sprite_atlas(&paths, args.atlas_cache.as_deref())
+            .unwrap_or_else(|err| panic!("{}", err));
+        println!(
+            "sprite atlas: {} images on {} pages of {}x{}",
+            atlas.entries().count(),
+            atlas.page_count(),
+            atlas.page_size(),
+            atlas.page_size()
+        );
+
+        let mut batch = SpriteBatch::new(&render, render.surface_format());
+        let pages = atlas.create_textures(&render, ColorSpace::Srgb);
+        let textures = pages
+            .iter()
+            .map(|page| batch.add_texture(&render, page, FilterMode::Linear))
+            .collect::<Vec<_>>();
+        let frames = paths
+            .iter()
+            .map(|path| {
+                let entry = atlas.entry(path).unwrap();
+                (textures[entry.page], entry.uv)
+            })
+            .collect::<Vec<_>>();
+        (batch, frames, pages)
+    });
+    // First page of the sprite atlas, shown by the inspector
+    let atlas_preview = match (&mut gui, &sprites) {
+        (Some(gui), Some((_, _, pages))) => Some(gui.register_texture(
+            &render,
+            &pages[0],
+            egui::TextureOptions::LINEAR,
+        )),
+        _ => None,
     };
-    let uniforms = render.create_uniforms(&uniform_data);
-    let bind_group = render.create_bind_group(&bind_layout, &uniforms);
+    let mut spin = 0.0;
+    let mut profiler = render.create_gpu_profiler();
+    if profiler.is_none() {
+        println!("Timestamp queries unsupported, GPU timings unavailable");
+    }
 
-    let mut timer = Instant::now();
-    let mut dt_avg = 0.0_f64;
-    let mut n_times = 0;
+    let start = Instant::now();
 
     loop {
         let dt = dt_time.elapsed().as_nanos() as f64 / 1_000_000_000_f64;
         dt_time = Instant::now();
-
-        if cfg!(debug_assertions) {
-            n_times += 1;
-            let n_times_f = n_times as f64;
-            dt_avg =
-                (dt_avg * (n_times_f - 1.0_f64) / n_times_f) + dt / n_times_f;
-            if timer.elapsed().as_millis() >= 500 {
-                timer = Instant::now();
-                println!("{:.2} average fps", 1.0 / dt_avg);
-                n_times = 0;
-                dt_avg = 0.0;
-            }
-        }
+        overlay.begin_frame(dt as f32);
 
         if window.should_close {
             break;
         }
+        let stage = Instant::now();
         window.update();
 
-        process_keys(&mut window);
+        // The keyboard and mouse go to the GUI first
+        let (gui_pointer, gui_keyboard) = match gui {
+            Some(ref mut gui) => {
+                let fps = overlay.fps();
+                let ran = gui.run(&mut window, |ctx| {
+                    inspector(
+                        ctx,
+                        &mut render,
+                        hdr.as_mut(),
+                        &mut spin_speed,
+                        &mut scene_renderer.culling,
+                        atlas_preview,
+                        fps,
+                    )
+                });
+                // Not worth stopping for, the copy can be tried again
+                if let Err(err) = ran {
+                    eprintln!("{}", err);
+                }
+                (gui.wants_pointer(), gui.wants_keyboard())
+            }
+            None => (false, false),
+        };
+
+        if !gui_keyboard {
+            // F3 shows and hides the performance overlay
+            if window.keypressed(Key::F3) {
+                overlay.toggle();
+            }
+            process_keys(
+                &mut window,
+                &mut render,
+                hdr.as_mut(),
+                postfx.as_mut(),
+            );
+            process_camera_keys(
+                &window,
+                &mut camera,
+                &mut controller,
+                &mut orbiting,
+            );
+        }
+        camera.update(&window);
+        if !gui_pointer && !gui_keyboard {
+            controller.update(&mut camera, &window, dt as f32);
+        }
 
         if window.did_resize {
-            render.reconfigure(window.width, window.height, PresentMode::Fifo);
+            render.reconfigure(window.width, window.height);
+            if let Some(ref mut hdr) = hdr {
+                hdr.resize(&render);
+            }
+            if let Some(ref mut depth) = swapchain_depth {
+                *depth = create_swapchain_depth(&render);
+            }
+            if let Some(ref mut postfx) = postfx {
+                postfx.resize(&render);
+            }
+            if let Some(ref mut deferred) = deferred {
+                deferred.renderer.resize(&render);
+            }
         }
 
+        overlay.cpu_stage("input", stage.elapsed());
+
+        let stage = Instant::now();
+        spin += spin_speed * dt as f32;
+        animate(
+            &mut scene,
+            &model,
+            &model_nodes,
+            start.elapsed().as_secs_f32(),
+        );
+        let transform = *scene.node(spinner).transform();
+        scene.node_mut(spinner).set_transform(Transform {
+            rotation: Quaternion::from_angle_y(Rad(spin)),
+            ..transform
+        });
+        overlay.cpu_stage("update", stage.elapsed());
+
+        let stage = Instant::now();
+        if let Some(ref deferred) = deferred {
+            deferred.renderer.set_camera(
+                &render,
+                camera.view_proj(),
+                camera.position,
+                Color::new(0.02, 0.02, 0.02, 1.0),
+            );
+        } else {
+            set_scene_lights(&mut scene_renderer.lights, &mut scene);
+            scene_renderer.prepare(&render, &mut scene, &model.meshes, &camera);
+            overlay.set_cull_stats(scene_renderer.cull_stats());
+            if let Some(ref mut debug_draw) = debug_draw {
+                // F leaves the current view drawn for a while to look at
+                // it from elsewhere
+                if window.keypressed(Key::F) {
+                    debug_draw.set_duration(10.0);
+                    debug_draw.frustum(camera.view_proj(), Color::WHITE);
+                    debug_draw.set_duration(0.0);
+                }
+                draw_debug(
+                    debug_draw,
+                    &scene,
+                    &model_nodes,
+                    &model.meshes,
+                    &scene_renderer.lights,
+                );
+                debug_draw.prepare(&render, &camera, dt as f32);
+            }
+        }
+
+        overlay.cpu_stage("prepare", stage.elapsed());
+
+        // scene -> [hdr tonemapping] -> [post-processing] -> swapchain
+        let stage = Instant::now();
         let framebuffer = render.get_presentation_framebuffer();
+        let display_target = postfx
+            .as_ref()
+            .map_or(&framebuffer, |postfx| postfx.input());
+        let depth_target = swapchain_depth
+            .as_ref()
+            .map(|depth| render.attach_depth(display_target, depth));
+        let scene_target = hdr
+            .as_ref()
+            .map(|hdr| &hdr.framebuffer)
+            .or(depth_target.as_ref())
+            .unwrap_or(display_target);
 
-        render
-            .start_commands()
-            .configure_draw(&pipeline, &framebuffer)
-            .set_vertices(&vertices)
-            .set_indices(&indices)
-            .bind_resources(&bind_group)
-            .draw(0..n_indices as u32)
-            .submit(&render.queue);
+        let commands = match profiler {
+            Some(ref profiler) => render.start_commands().profile(profiler),
+            None => render.start_commands(),
+        };
+        let mut commands = match deferred {
+            Some(ref deferred) => deferred.draw(commands, scene_target),
+            None => {
+                let commands = scene_renderer.draw(
+                    commands,
+                    &model.meshes,
+                    &materials,
+                    scene_target,
+                );
+                match debug_draw {
+                    Some(ref debug_draw) => debug_draw.draw(commands),
+                    None => commands,
+                }
+            }
+        };
+        if let Some(ref hdr) = hdr {
+            commands = hdr.tonemapper.draw(commands, display_target);
+        }
+        if let Some(ref postfx) = postfx {
+            commands = postfx.draw(commands, &framebuffer);
+        }
+        if let Some((ref mut batch, ref frames, _)) = sprites {
+            let (width, height) = render.size();
+            draw_hud(batch, frames, spin, height);
+            batch.prepare(&render, &Camera2d::screen(width, height));
+        }
+        if let Some((ref batch, _, _)) = sprites {
+            commands = batch.draw(commands, &framebuffer);
+        }
+        overlay.set_memory_usage(render.memory_usage());
+        overlay.draw(&mut text, [8.0, 8.0]);
+        draw_status(&mut text, &args.model, &camera, orbiting, render.size());
+        text.prepare(&render);
+        commands = text.draw(commands, &framebuffer);
+        if let Some(ref mut gui) = gui {
+            gui.prepare(&render);
+        }
+        if let Some(ref gui) = gui {
+            commands = gui.draw(commands, &framebuffer);
+        }
+        // Shown next frame, as the overlay itself is drawn by now
+        overlay.set_draw_stats(commands.stats());
+        overlay.cpu_stage("record", stage.elapsed());
 
+        let stage = Instant::now();
+        commands.submit(&render.queue);
         render.present();
+        overlay.cpu_stage("present", stage.elapsed());
+
+        if let Some(ref mut profiler) = profiler {
+            profiler.collect(&render);
+            overlay.set_gpu_timings(profiler.timings());
+        }
     }
 }
 
 fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        print_usage();
+        std::process::exit(1);
+    });
+
+    if args.list_adapters {
+        for adapter in Render::enumerate_adapters(&args.render_config) {
+            println!("{}", adapter);
+            println!("    features: {:?}", adapter.features);
+        }
+        return;
+    }
+
     srand(
         SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -190,5 +1137,5 @@ fn main() {
     let sdl = sdl2::init().expect("Failed to initialize SDL");
     let window = Window::new(&sdl, "Conc", 640, 480);
 
-    pollster::block_on(run(window));
+    pollster::block_on(run(window, args));
 }
@@ -1,37 +1,96 @@
+mod animation;
+mod atlas;
+mod binding;
+mod bounds;
 mod buffer;
+mod color;
 mod command_buffer;
+mod config;
+mod debug_draw;
+mod deferred;
+mod environment;
 mod framebuffer;
+mod gltf;
+mod gui;
+mod light;
+mod material;
+mod mesh;
+mod morph;
+mod perf_overlay;
 mod pipeline;
+mod post_process;
+mod profiler;
 mod shader;
 mod shader_layout;
+mod shadow;
+mod skybox;
+mod sprite;
+mod stats;
+mod text;
+mod texture;
+mod tonemap;
 
+pub use animation::{
+    AnimationChannel, AnimationClip, Interpolation, Joint, JointPose,
+    Keyframes, Skeleton,
+};
+pub use atlas::{Atlas, AtlasBuilder};
+pub use binding::{Binding, BindingLayout};
+pub use bounds::{Aabb, BoundingSphere, Frustum};
 pub use buffer::Buffer;
 use bytemuck::cast_slice;
+pub use color::Color;
 pub use command_buffer::CommandBuffer;
+pub use config::{
+    AdapterDescription, Backend, PowerPreference, RenderConfig, SurfaceFormat,
+};
+pub use debug_draw::DebugDraw;
+pub use deferred::{DeferredObject, DeferredRenderer, PointLight};
+pub use environment::Environment;
 pub use framebuffer::Framebuffer;
-pub use pipeline::Pipeline;
+pub use gui::Gui;
+pub use light::{Light, Lights};
+pub use material::{Material, PbrMaterial, TextureMap};
+pub use mesh::{Mesh, MeshInstance, Model, ModelNode, SkinVertex};
+pub use morph::{MorphAnimation, MorphInstance, MorphTarget, Morpher};
+pub use perf_overlay::PerfOverlay;
+pub use pipeline::{ComputePipeline, Pipeline, PipelineBuilder};
+pub use post_process::{PostProcessChain, PostProcessConfig};
+pub use profiler::GpuProfiler;
 pub use shader::Shader;
 pub use shader_layout::{ShaderAttribute, ShaderAttributeType, ShaderLayout};
+pub use shadow::{ShadowConfig, ShadowMaps};
+pub use skybox::Skybox;
+pub use sprite::{Sprite, SpriteBatch, SpriteTexture};
+pub use stats::{CullStats, DrawStats, MemoryUsage};
+pub use text::{Font, TextAlign, TextRenderer, TextStyle};
+pub use texture::{ColorSpace, Sampler, Texture};
+pub use tonemap::{TonemapOperator, Tonemapper};
+pub use wgpu::{Features, FilterMode, TextureFormat};
 
 use crate::window::Window;
+use stats::{Allocation, MemoryCounter};
 use std::{
     borrow::Cow,
     mem::{replace, size_of},
+    num::NonZeroU32,
+    path::Path,
 };
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Adapter, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
-    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-    BindingType, BufferBindingType, ColorTargetState, Device, DeviceDescriptor,
-    Features, FragmentState, Instance, Limits, MultisampleState,
-    PipelineLayoutDescriptor, PowerPreference, PresentMode as WgpuPresentMode,
-    PrimitiveState, Queue, RenderPipelineDescriptor, RequestAdapterOptions,
-    ShaderModuleDescriptor, ShaderSource, ShaderStages, Surface,
-    SurfaceConfiguration, SurfaceTexture, TextureFormat, TextureUsages,
-    TextureViewDescriptor, VertexBufferLayout, VertexState,
+    Adapter, AdapterInfo, AddressMode, BindGroup, BindGroupDescriptor,
+    BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BufferBindingType, CompareFunction,
+    Device, DeviceDescriptor, Extent3d, ImageCopyTexture, ImageDataLayout,
+    Instance, Origin3d, PresentMode as WgpuPresentMode, Queue,
+    RequestAdapterOptions, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, Surface, SurfaceConfiguration, SurfaceTexture,
+    Texture as WgpuTexture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureUsages, TextureViewDescriptor, TextureViewDimension,
 };
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PresentMode {
     Immediate,
     Fifo,
@@ -39,7 +98,7 @@ pub enum PresentMode {
 }
 
 impl PresentMode {
-    fn to_wgpu(&self) -> WgpuPresentMode {
+    fn to_wgpu(self) -> WgpuPresentMode {
         match self {
             PresentMode::Immediate => WgpuPresentMode::Immediate,
             PresentMode::Fifo => WgpuPresentMode::Fifo,
@@ -50,64 +109,116 @@ impl PresentMode {
 
 pub struct Render {
     _instance: Instance,
-    _adapter: Adapter,
+    adapter: Adapter,
     pub surface: Surface,
     swapchain_format: TextureFormat,
+    surface_config: SurfaceConfiguration,
+    present_mode: PresentMode,
     device: Device,
     pub queue: Queue,
     active_frame: Option<SurfaceTexture>,
+    buffer_memory: MemoryCounter,
+    texture_memory: MemoryCounter,
 }
 
 impl Render {
-    pub async fn new(window: &Window, present_mode: PresentMode) -> Render {
-        #[cfg(target_os = "linux")]
-        let instance = Instance::new(Backends::VULKAN);
+    pub async fn new(window: &Window, config: &RenderConfig) -> Render {
+        let instance = Instance::new(config.wgpu_backends());
 
         let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to create WebGPU adapter");
+        let adapter = match config.adapter {
+            Some(index) => instance
+                .enumerate_adapters(config.wgpu_backends())
+                .nth(index)
+                .filter(|adapter| adapter.is_surface_supported(&surface))
+                .unwrap_or_else(|| {
+                    panic!("Adapter {} cannot present to this window", index)
+                }),
+            None => instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: config.power_preference.to_wgpu(),
+                    force_fallback_adapter: config.force_fallback_adapter,
+                    compatible_surface: Some(&surface),
+                })
+                .await
+                .expect("Failed to create WebGPU adapter"),
+        };
+
+        let missing_features = config.features - adapter.features();
+        if !missing_features.is_empty() {
+            panic!(
+                "Adapter {} does not support features {:?}",
+                adapter.get_info().name,
+                missing_features
+            );
+        }
 
+        let features =
+            config.features | (config.optional_features & adapter.features());
         let (device, queue): (Device, Queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: None,
-                    features: Features::default(),
-                    limits: Limits::downlevel_webgl2_defaults(),
+                    features,
+                    limits: config.limits.clone(),
                 },
                 None,
             )
             .await
             .expect("Failed to create device");
 
-        let swapchain_format = surface
-            .get_preferred_format(&adapter)
-            .expect("Failed to get swapchain format");
+        let swapchain_format = config.surface_format.apply(
+            surface
+                .get_preferred_format(&adapter)
+                .expect("Failed to get swapchain format"),
+        );
 
-        let config = SurfaceConfiguration {
+        let surface_config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
             format: swapchain_format,
             width: window.width as u32,
             height: window.height as u32,
-            present_mode: present_mode.to_wgpu(),
+            present_mode: config.present_mode.to_wgpu(),
         };
 
-        surface.configure(&device, &config);
-
-        Render {
+        let mut render = Render {
             _instance: instance,
-            _adapter: adapter,
+            adapter,
             surface,
             swapchain_format,
+            surface_config,
+            present_mode: config.present_mode,
             device,
             queue,
             active_frame: None,
-        }
+            buffer_memory: MemoryCounter::default(),
+            texture_memory: MemoryCounter::default(),
+        };
+        render.set_present_mode(config.present_mode);
+
+        render
+    }
+
+    /// Lists the adapters available for `config.backends`, in the order
+    /// used by `RenderConfig::adapter`
+    pub fn enumerate_adapters(
+        config: &RenderConfig,
+    ) -> Vec<AdapterDescription> {
+        let instance = Instance::new(config.wgpu_backends());
+
+        instance
+            .enumerate_adapters(config.wgpu_backends())
+            .enumerate()
+            .map(|(index, adapter)| AdapterDescription {
+                index,
+                info: adapter.get_info(),
+                features: adapter.features(),
+            })
+            .collect()
+    }
+
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.adapter.get_info()
     }
 
     pub fn create_shader_layout<const T: usize>(
@@ -117,6 +228,7 @@ impl Render {
         ShaderLayout::new(&self, attrs)
     }
 
+    #[allow(dead_code)]
     pub fn create_shader(
         &self,
         vertex_shader: &[u8],
@@ -142,74 +254,126 @@ impl Render {
         Shader::new(vertex_module, fragment_module)
     }
 
+    /// Creates a shader from WGSL source holding both stages, with the
+    /// entry points `vs_main` and `fs_main`
+    pub fn create_wgsl_shader(&self, source: &str) -> Shader {
+        self.create_wgsl_shader_with_entries(source, "vs_main", Some("fs_main"))
+    }
+
+    pub fn create_wgsl_shader_with_entries(
+        &self,
+        source: &str,
+        vert_entry: &'static str,
+        frag_entry: Option<&'static str>,
+    ) -> Shader {
+        let module =
+            self.device.create_shader_module(&ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(Cow::Borrowed(source)),
+            });
+
+        Shader::from_module(module, vert_entry, frag_entry)
+    }
+
+    #[allow(dead_code)]
     pub fn create_pipeline<const T: usize>(
         &self,
         layout: &ShaderLayout<T>,
         shader: &Shader,
         bind_group_layout: &BindGroupLayout,
     ) -> Pipeline {
-        let pipeline_layout =
-            self.device
-                .create_pipeline_layout(&PipelineLayoutDescriptor {
-                    label: None,
-                    bind_group_layouts: &[&bind_group_layout],
-                    push_constant_ranges: &[],
-                });
-
-        let frag_state: &[ColorTargetState] = &[self.swapchain_format.into()];
-        let frag_info = if let Some(ref f) = shader.frag {
-            Some(FragmentState {
-                module: f,
-                entry_point: "main",
-                targets: frag_state,
-            })
-        } else {
-            None
-        };
+        self.build_pipeline(shader)
+            .vertex_layout(layout)
+            .bind_group_layout(bind_group_layout)
+            .build()
+    }
 
-        let (stride, attrs) = layout.wgpu_attributes();
-        let pipeline =
-            self.device
-                .create_render_pipeline(&RenderPipelineDescriptor {
-                    label: None,
-                    layout: Some(&pipeline_layout),
-                    vertex: VertexState {
-                        module: &shader.vert,
-                        entry_point: "main",
-                        buffers: &[VertexBufferLayout {
-                            array_stride: stride as u64,
-                            step_mode: wgpu::VertexStepMode::Vertex,
-                            attributes: &attrs,
-                        }],
-                    },
-                    fragment: frag_info,
-                    primitive: PrimitiveState::default(),
-                    depth_stencil: None,
-                    multisample: MultisampleState::default(),
-                });
+    /// Compute pipeline running `entry_point` of a WGSL `shader`
+    pub fn create_compute_pipeline(
+        &self,
+        shader: &Shader,
+        entry_point: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> ComputePipeline {
+        ComputePipeline::new(self, shader, entry_point, bind_group_layouts)
+    }
 
-        Pipeline::new(pipeline)
+    pub fn build_pipeline<'a>(
+        &'a self,
+        shader: &'a Shader,
+    ) -> PipelineBuilder<'a> {
+        PipelineBuilder::new(self, shader)
     }
 
     pub fn start_commands(&self) -> CommandBuffer {
         CommandBuffer::begin(&self.device)
     }
 
-    pub fn reconfigure(
-        &mut self,
-        width: i32,
-        height: i32,
-        present_mode: PresentMode,
-    ) {
-        let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: self.swapchain_format,
-            width: width as u32,
-            height: height as u32,
-            present_mode: present_mode.to_wgpu(),
-        };
+    pub fn reconfigure(&mut self, width: i32, height: i32) {
+        self.surface_config.width = width as u32;
+        self.surface_config.height = height as u32;
+
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    pub fn surface_format(&self) -> TextureFormat {
+        self.swapchain_format
+    }
+
+    /// Whether the swapchain converts linear shader output to sRGB by itself
+    pub fn is_srgb_output(&self) -> bool {
+        self.swapchain_format.describe().srgb
+    }
+
+    /// Value a shader has to write to the swapchain to display `color`.
+    /// Linear surfaces don't encode on output, so the encoding is done here
+    #[allow(dead_code)]
+    pub fn output_color(&self, color: Color) -> [f32; 4] {
+        if self.is_srgb_output() {
+            color.to_array()
+        } else {
+            color.to_srgb()
+        }
+    }
+
+    /// Whether shaders drawing to `format` must encode their output to sRGB
+    /// themselves. 8-bit targets without sRGB encoding are only ever the
+    /// swapchain, anything else stays linear
+    pub fn encodes_srgb(&self, format: TextureFormat) -> bool {
+        format == self.surface_format() && !self.is_srgb_output()
+    }
+
+    /// Size of the swapchain, which offscreen targets usually follow
+    pub fn size(&self) -> (u32, u32) {
+        (self.surface_config.width, self.surface_config.height)
+    }
+
+    /// Mode last requested with `set_present_mode`. wgpu doesn't expose
+    /// the surface capabilities yet and silently falls back to `Fifo` when
+    /// the driver refuses a mode, so this is not necessarily the one in use
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
 
-        self.surface.configure(&self.device, &config);
+    /// Reconfigures the swapchain requesting `present_mode`. Only `Fifo` is
+    /// guaranteed, see `present_mode`
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+        self.surface_config.present_mode = present_mode.to_wgpu();
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    /// Requests `Fifo`, or `Mailbox` to render uncapped without tearing
+    /// where available. Returns whether vsync is now requested
+    pub fn toggle_vsync(&mut self) -> bool {
+        let vsync = self.present_mode != PresentMode::Fifo;
+        self.set_present_mode(if vsync {
+            PresentMode::Fifo
+        } else {
+            PresentMode::Mailbox
+        });
+
+        vsync
     }
 
     pub fn get_presentation_framebuffer(&mut self) -> Framebuffer {
@@ -231,6 +395,22 @@ impl Render {
         }
     }
 
+    /// GPU memory taken by the buffers and textures created so far and
+    /// still alive, swapchain images excluded
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            buffers: self.buffer_memory.bytes(),
+            textures: self.texture_memory.bytes(),
+        }
+    }
+
+    /// Profiler of the GPU time spent in each pass, `None` when the device
+    /// lacks `Features::TIMESTAMP_QUERY`, see
+    /// `RenderConfig::optional_features`
+    pub fn create_gpu_profiler(&self) -> Option<GpuProfiler> {
+        GpuProfiler::new(self)
+    }
+
     fn create_buffer(&self, data: &[u8], usage: wgpu::BufferUsages) -> Buffer {
         let buf = self.device.create_buffer_init(&BufferInitDescriptor {
             label: None,
@@ -238,17 +418,43 @@ impl Render {
             usage,
         });
 
-        Buffer::new(buf, data)
+        Buffer::new(buf, data, self.buffer_memory.allocate(data.len() as u64))
     }
 
     pub fn create_vertex_buffer(&self, data: &[u8]) -> Buffer {
         self.create_buffer(data, wgpu::BufferUsages::VERTEX)
     }
 
+    /// Vertex buffer compute shaders can also read and write, e.g. to blend
+    /// morph targets
+    pub fn create_vertex_storage_buffer(&self, data: &[u8]) -> Buffer {
+        self.create_buffer(
+            data,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+        )
+    }
+
+    /// Vertex buffer of `size` bytes rewritten with `write_buffer`, e.g.
+    /// every frame
+    pub fn create_dynamic_vertex_buffer(&self, size: u64) -> Buffer {
+        self.create_buffer(
+            &vec![0; size as usize],
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
     pub fn create_index_buffer(&self, data: &[u8]) -> Buffer {
         self.create_buffer(data, wgpu::BufferUsages::INDEX)
     }
 
+    /// Index buffer of `size` bytes rewritten with `write_buffer`
+    pub fn create_dynamic_index_buffer(&self, size: u64) -> Buffer {
+        self.create_buffer(
+            &vec![0; size as usize],
+            wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
     pub fn create_uniforms<T>(&self, data: &T) -> Buffer {
         let data_bytes = unsafe {
             std::slice::from_raw_parts(
@@ -262,9 +468,45 @@ impl Render {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        Buffer::new(buf, data_bytes)
+        Buffer::new(
+            buf,
+            data_bytes,
+            self.buffer_memory.allocate(data_bytes.len() as u64),
+        )
+    }
+
+    /// Zeroed uniform buffer of `size` bytes, to be filled with
+    /// `write_buffer`
+    pub fn create_uniform_buffer(&self, size: u64) -> Buffer {
+        self.create_buffer(
+            &vec![0; size as usize],
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    /// Zeroed storage buffer of `size` bytes, to be filled with
+    /// `write_buffer`
+    pub fn create_storage_buffer(&self, size: u64) -> Buffer {
+        self.create_buffer(
+            &vec![0; size as usize],
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    /// Storage buffer holding `data`
+    pub fn create_storage_buffer_with(&self, data: &[u8]) -> Buffer {
+        self.create_buffer(
+            data,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        )
+    }
+
+    /// Dynamic offsets into uniform buffers have to be multiples of this
+    pub fn uniform_alignment(&self) -> u32 {
+        self.device.limits().min_uniform_buffer_offset_alignment
     }
 
+    #[allow(dead_code)]
     pub fn create_bind_group_layout<T>(&self) -> BindGroupLayout {
         self.device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -284,6 +526,7 @@ impl Render {
             })
     }
 
+    #[allow(dead_code)]
     pub fn create_bind_group(
         &self,
         layout: &BindGroupLayout,
@@ -291,7 +534,7 @@ impl Render {
     ) -> BindGroup {
         self.device.create_bind_group(&BindGroupDescriptor {
             label: None,
-            layout: layout,
+            layout,
             entries: &[BindGroupEntry {
                 binding: 0,
                 resource: buffer.binding_resource(),
@@ -299,7 +542,600 @@ impl Render {
         })
     }
 
+    /// Creates a bind group layout with one entry per element of `entries`,
+    /// numbered from 0
+    pub fn create_bind_group_layout_with(
+        &self,
+        entries: &[BindingLayout],
+        visibility: ShaderStages,
+    ) -> BindGroupLayout {
+        let entries = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| BindGroupLayoutEntry {
+                binding: i as u32,
+                visibility,
+                ty: entry.to_wgpu(),
+                count: None,
+            })
+            .collect::<Vec<_>>();
+
+        self.device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &entries,
+            })
+    }
+
+    pub fn create_bind_group_with(
+        &self,
+        layout: &BindGroupLayout,
+        resources: &[Binding],
+    ) -> BindGroup {
+        let entries = resources
+            .iter()
+            .enumerate()
+            .map(|(i, resource)| BindGroupEntry {
+                binding: i as u32,
+                resource: resource.resource(),
+            })
+            .collect::<Vec<_>>();
+
+        self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// Creates an RGBA8 texture from tightly packed `data`. `color_space`
+    /// decides whether sampling it decodes sRGB into linear values
+    pub fn create_texture(
+        &self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        color_space: ColorSpace,
+    ) -> Texture {
+        self.create_texture_with_format(
+            width,
+            height,
+            data,
+            color_space.rgba8_format(),
+        )
+    }
+
+    /// Creates a texture counted by `memory_usage` until it is dropped
+    fn create_counted_texture(
+        &self,
+        descriptor: &TextureDescriptor,
+    ) -> (WgpuTexture, Allocation) {
+        let allocation = self
+            .texture_memory
+            .allocate(stats::texture_bytes(descriptor));
+
+        (self.device.create_texture(descriptor), allocation)
+    }
+
+    pub fn create_texture_with_format(
+        &self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        format: TextureFormat,
+    ) -> Texture {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let (texture, allocation) =
+            self.create_counted_texture(&TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            });
+
+        let block_size = format.describe().block_size as u32;
+        self.queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width * block_size),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Texture::new(texture, view, format, width, height, allocation)
+    }
+
+    /// Loads an image file into an RGBA8 texture
+    pub fn load_texture<P: AsRef<Path>>(
+        &self,
+        path: P,
+        color_space: ColorSpace,
+    ) -> Result<Texture, String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|err| {
+                format!("Failed to load {}: {}", path.display(), err)
+            })?
+            .to_rgba8();
+
+        Ok(self.create_texture(
+            image.width(),
+            image.height(),
+            &image,
+            color_space,
+        ))
+    }
+
+    /// Loads an equirectangular HDR image and generates the maps of
+    /// image-based lighting from it
+    pub fn load_environment<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Environment, String> {
+        Environment::load(self, path)
+    }
+
+    /// Loads the meshes, materials and nodes of an OBJ or glTF file,
+    /// picking the format from the extension
+    pub fn load_model<P: AsRef<Path>>(&self, path: P) -> Result<Model, String> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("gltf") | Some("glb") => gltf::load_gltf(self, path),
+            _ => mesh::load_obj(self, path),
+        }
+    }
+
+    /// Creates a framebuffer of the given size rendering into a texture of
+    /// `format` that can be bound with `Binding::Texture` afterwards
+    pub fn create_offscreen_framebuffer(
+        &self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Framebuffer {
+        self.create_framebuffer(width, height, &[format], None)
+    }
+
+    /// Offscreen framebuffer with a color attachment per entry of `formats`
+    /// and an optional depth attachment, all of which can be sampled once
+    /// rendered
+    pub fn create_framebuffer(
+        &self,
+        width: u32,
+        height: u32,
+        formats: &[TextureFormat],
+        depth: Option<TextureFormat>,
+    ) -> Framebuffer {
+        let colors = formats
+            .iter()
+            .map(|format| self.create_render_target(width, height, *format))
+            .collect();
+        let depth = depth
+            .map(|format| self.create_render_target(width, height, format));
+
+        Framebuffer::offscreen(colors, depth)
+    }
+
+    /// Depth texture of `format` to test against with `attach_depth`, e.g.
+    /// while drawing into the swapchain
+    pub fn create_depth_texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Texture {
+        self.create_render_target(width, height, format)
+    }
+
+    /// Framebuffer drawing into the color attachments of `target`, the
+    /// presentation framebuffer of this frame or an offscreen one, and
+    /// testing against `depth`, which must be of the same size
+    pub fn attach_depth(
+        &self,
+        target: &Framebuffer,
+        depth: &Texture,
+    ) -> Framebuffer {
+        let colors = match target.get_textures() {
+            [] => {
+                let frame = self
+                    .active_frame
+                    .as_ref()
+                    .expect("No swapchain image acquired this frame");
+                vec![frame
+                    .texture
+                    .create_view(&TextureViewDescriptor::default())]
+            }
+            textures => textures
+                .iter()
+                .map(|color| {
+                    color
+                        .get_texture()
+                        .create_view(&TextureViewDescriptor::default())
+                })
+                .collect(),
+        };
+
+        Framebuffer::with_views(
+            colors,
+            depth
+                .get_texture()
+                .create_view(&TextureViewDescriptor::default()),
+        )
+    }
+
+    fn create_render_target(
+        &self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Texture {
+        let (texture, allocation) =
+            self.create_counted_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::TEXTURE_BINDING,
+            });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Texture::new(texture, view, format, width, height, allocation)
+    }
+
+    /// Depth texture with `layers` layers, sampled as a whole through the
+    /// returned texture and rendered one layer at a time through the
+    /// returned framebuffers
+    pub fn create_layered_depth(
+        &self,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+    ) -> (Texture, Vec<Framebuffer>) {
+        let (texture, allocation) =
+            self.create_counted_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: layers,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::TEXTURE_BINDING,
+            });
+        let framebuffers = (0..layers)
+            .map(|layer| {
+                Framebuffer::depth_only(texture.create_view(
+                    &TextureViewDescriptor {
+                        dimension: Some(TextureViewDimension::D2),
+                        base_array_layer: layer,
+                        array_layer_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    },
+                ))
+            })
+            .collect();
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        (
+            Texture::new(texture, view, format, width, height, allocation),
+            framebuffers,
+        )
+    }
+
+    /// Cube texture of six `size` x `size` faces with `mip_levels` levels,
+    /// filled by compute shaders through views of single mip levels and
+    /// sampled as a whole through the returned texture
+    pub fn create_cubemap(
+        &self,
+        size: u32,
+        mip_levels: u32,
+        format: TextureFormat,
+    ) -> Texture {
+        self.create_cube_texture(
+            size,
+            mip_levels,
+            format,
+            TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+        )
+    }
+
+    fn create_cube_texture(
+        &self,
+        size: u32,
+        mip_levels: u32,
+        format: TextureFormat,
+        usage: TextureUsages,
+    ) -> Texture {
+        let (texture, allocation) =
+            self.create_counted_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: mip_levels,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+            });
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        Texture::new(texture, view, format, size, size, allocation)
+    }
+
+    /// Cubemap of six `size` x `size` RGBA8 faces, given in the order +X,
+    /// -X, +Y, -Y, +Z, -Z
+    pub fn create_cubemap_from_faces(
+        &self,
+        size: u32,
+        faces: [&[u8]; 6],
+        color_space: ColorSpace,
+    ) -> Texture {
+        let format = color_space.rgba8_format();
+        let cubemap = self.create_cube_texture(
+            size,
+            1,
+            format,
+            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        );
+
+        for (layer, face) in faces.into_iter().enumerate() {
+            self.queue.write_texture(
+                ImageCopyTexture {
+                    texture: cubemap.get_texture(),
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                face,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(size * 4),
+                    rows_per_image: NonZeroU32::new(size),
+                },
+                Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        cubemap
+    }
+
+    /// Loads six square images of the same size into a cubemap, see
+    /// `create_cubemap_from_faces`
+    pub fn load_cubemap<P: AsRef<Path>>(
+        &self,
+        faces: &[P; 6],
+    ) -> Result<Texture, String> {
+        let images = faces
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let image = image::open(path)
+                    .map_err(|err| {
+                        format!("Failed to load {}: {}", path.display(), err)
+                    })?
+                    .to_rgba8();
+                if image.width() != image.height() {
+                    return Err(format!(
+                        "The cubemap face {} isn't square",
+                        path.display()
+                    ));
+                }
+
+                Ok(image)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let size = images[0].width();
+        if images.iter().any(|image| image.width() != size) {
+            return Err("The cubemap faces differ in size".to_string());
+        }
+
+        Ok(self.create_cubemap_from_faces(
+            size,
+            [
+                &images[0], &images[1], &images[2], &images[3], &images[4],
+                &images[5],
+            ],
+            ColorSpace::Srgb,
+        ))
+    }
+
+    /// Projects an equirectangular panorama of `width` x `height` linear
+    /// RGBA texels onto a float cubemap with faces of `size`, on the GPU
+    pub fn create_cubemap_from_equirect(
+        &self,
+        width: u32,
+        height: u32,
+        texels: &[f32],
+        size: u32,
+    ) -> Texture {
+        environment::equirect_to_cubemap(self, width, height, texels, size)
+    }
+
+    /// Loads an equirectangular panorama into a cubemap with faces of
+    /// `size`. Radiance HDR (`.hdr`) images keep their range, other formats
+    /// are decoded from sRGB
+    pub fn load_panorama<P: AsRef<Path>>(
+        &self,
+        path: P,
+        size: u32,
+    ) -> Result<Texture, String> {
+        let (width, height, texels) =
+            environment::read_equirect(path.as_ref())?;
+
+        Ok(self.create_cubemap_from_equirect(width, height, &texels, size))
+    }
+
+    /// Texture written by compute shaders and sampled afterwards
+    pub fn create_storage_texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Texture {
+        let (texture, allocation) =
+            self.create_counted_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::STORAGE_BINDING,
+            });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        Texture::new(texture, view, format, width, height, allocation)
+    }
+
+    pub fn create_tonemapper(
+        &self,
+        target_format: TextureFormat,
+    ) -> Tonemapper {
+        Tonemapper::new(self, target_format)
+    }
+
+    /// Deferred renderer lighting into framebuffers of `output_format`
+    pub fn create_deferred_renderer(
+        &self,
+        output_format: TextureFormat,
+    ) -> DeferredRenderer {
+        DeferredRenderer::new(self, output_format)
+    }
+
+    /// Builds the effects of `config`, fails if a lookup table can't be loaded
+    pub fn create_post_process_chain(
+        &self,
+        config: &PostProcessConfig,
+        format: TextureFormat,
+    ) -> Result<PostProcessChain, String> {
+        PostProcessChain::new(self, config, format)
+    }
+
+    pub fn create_sampler(
+        &self,
+        filter: FilterMode,
+        address_mode: AddressMode,
+    ) -> Sampler {
+        Sampler::new(self.device.create_sampler(&SamplerDescriptor {
+            label: None,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        }))
+    }
+
+    /// Sampler for `BindingLayout::ComparisonSampler`, returning how much of
+    /// the filtered texels pass a less-or-equal test
+    pub fn create_comparison_sampler(&self) -> Sampler {
+        Sampler::new(self.device.create_sampler(&SamplerDescriptor {
+            label: None,
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        }))
+    }
+
     pub fn write_buffer(&self, buffer: &Buffer, data: &[u8]) {
         self.queue.write_buffer(buffer.get_buf(), 0, data)
     }
+
+    /// Replaces the `width` by `height` texels of `texture` starting at
+    /// `origin` with tightly packed `data`
+    pub fn write_texture(
+        &self,
+        texture: &Texture,
+        origin: [u32; 2],
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) {
+        let block_size = texture.format().describe().block_size as u32;
+        self.queue.write_texture(
+            ImageCopyTexture {
+                texture: texture.get_texture(),
+                mip_level: 0,
+                origin: Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(width * block_size),
+                rows_per_image: NonZeroU32::new(height),
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
 }
@@ -0,0 +1,443 @@
+use cgmath::{
+    InnerSpace, Matrix4, One, Quaternion, SquareMatrix, Vector3, VectorSpace,
+};
+
+/// Local transform of a joint relative to its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointPose {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl JointPose {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(
+                self.scale.x,
+                self.scale.y,
+                self.scale.z,
+            )
+    }
+}
+
+impl Default for JointPose {
+    fn default() -> Self {
+        JointPose {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index into `Skeleton::joints`
+    pub parent: Option<usize>,
+    /// Moves vertices from the space of the skinned mesh into the space of
+    /// the joint, as it was when the mesh was bound to it
+    pub inverse_bind: Matrix4<f32>,
+    /// Pose of the joint when no animation moves it
+    pub rest: JointPose,
+}
+
+/// Hierarchy of joints deforming a skinned mesh
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    /// Places the root joints in the space of the skinned mesh, e.g. the
+    /// transform of nodes above the skeleton
+    pub root: Matrix4<f32>,
+}
+
+impl Skeleton {
+    /// Pose of every joint at rest, to be changed by `AnimationClip::sample`
+    pub fn rest_pose(&self) -> Vec<JointPose> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// Matrix palette skinning the mesh in `pose`: for each joint, the
+    /// transform from the bind pose to the posed joint, in the space of
+    /// the mesh
+    pub fn joint_matrices(&self, pose: &[JointPose]) -> Vec<Matrix4<f32>> {
+        let mut globals: Vec<Option<Matrix4<f32>>> =
+            vec![None; self.joints.len()];
+        for index in 0..self.joints.len() {
+            self.global(index, pose, &mut globals);
+        }
+
+        globals
+            .iter()
+            .zip(self.joints.iter())
+            .map(|(global, joint)| {
+                global.unwrap_or(self.root) * joint.inverse_bind
+            })
+            .collect()
+    }
+
+    /// Transform of the joint at `index` in the space of the mesh, computing
+    /// its ancestors first. Joints past the end of `pose` stay at rest
+    fn global(
+        &self,
+        index: usize,
+        pose: &[JointPose],
+        globals: &mut [Option<Matrix4<f32>>],
+    ) -> Matrix4<f32> {
+        if let Some(global) = globals[index] {
+            return global;
+        }
+
+        let joint = &self.joints[index];
+        // A parent out of range or equal to the joint itself is a root
+        let parent = match joint.parent {
+            Some(parent) if parent < self.joints.len() && parent != index => {
+                self.global(parent, pose, globals)
+            }
+            _ => self.root,
+        };
+        let local = pose.get(index).unwrap_or(&joint.rest);
+        let global = parent * local.matrix();
+        globals[index] = Some(global);
+        global
+    }
+}
+
+impl Default for Skeleton {
+    fn default() -> Self {
+        Skeleton {
+            joints: Vec::new(),
+            root: Matrix4::identity(),
+        }
+    }
+}
+
+/// How values between two keyframes are computed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Holds the value of the previous keyframe
+    Step,
+    /// Lerps vectors and slerps rotations
+    Linear,
+    /// Cubic Hermite spline through the values, each stored between its
+    /// in-tangent and out-tangent
+    CubicSpline,
+}
+
+/// Keyframed values of one property of a joint
+#[derive(Clone, Debug, PartialEq)]
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationChannel {
+    /// Index into `Skeleton::joints`
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    /// Increasing times of the keyframes, in seconds
+    pub times: Vec<f32>,
+    /// One value per time, or three with `Interpolation::CubicSpline`
+    pub keyframes: Keyframes,
+}
+
+/// Keyframed animation of the joints of a skeleton
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Index into `Model::skeletons` of the skeleton the channels animate
+    pub skeleton: usize,
+    pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    /// Time of the last keyframe, in seconds
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|channel| channel.times.last())
+            .fold(0.0, |duration, &time| duration.max(time))
+    }
+
+    /// Sets the joints animated by the clip in `pose` to their values at
+    /// `time`. Times outside the keyframes hold the first or last value;
+    /// looping is up to the caller, e.g. `time % clip.duration()`
+    pub fn sample(&self, time: f32, pose: &mut [JointPose]) {
+        for channel in self.channels.iter() {
+            let joint = match pose.get_mut(channel.joint) {
+                Some(joint) => joint,
+                None => continue,
+            };
+            let keyframe = match keyframe(&channel.times, time) {
+                Some(keyframe) => keyframe,
+                None => continue,
+            };
+            let (from, to, _) = keyframe;
+            let span = channel.times[to] - channel.times[from];
+            let interpolation = channel.interpolation;
+
+            match channel.keyframes {
+                Keyframes::Translation(ref values) => {
+                    joint.translation = interpolate(
+                        interpolation,
+                        values,
+                        keyframe,
+                        span,
+                        VectorSpace::lerp,
+                    );
+                }
+                Keyframes::Rotation(ref values) => {
+                    joint.rotation = interpolate(
+                        interpolation,
+                        values,
+                        keyframe,
+                        span,
+                        slerp,
+                    )
+                    .normalize();
+                }
+                Keyframes::Scale(ref values) => {
+                    joint.scale = interpolate(
+                        interpolation,
+                        values,
+                        keyframe,
+                        span,
+                        VectorSpace::lerp,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Keyframes surrounding `time` and how far between them it is, `None`
+/// without keyframes
+pub(super) fn keyframe(
+    times: &[f32],
+    time: f32,
+) -> Option<(usize, usize, f32)> {
+    let last = times.len().checked_sub(1)?;
+    let next = times.partition_point(|&t| t <= time);
+
+    Some(match next {
+        0 => (0, 0, 0.0),
+        next if next > last => (last, last, 0.0),
+        next => {
+            let (start, end) = (times[next - 1], times[next]);
+            (next - 1, next, (time - start) / (end - start))
+        }
+    })
+}
+
+/// Weights of the start value, start out-tangent, end value and end
+/// in-tangent of a cubic Hermite spline `t` of the way along it
+pub(super) fn hermite(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        2.0 * t3 - 3.0 * t2 + 1.0,
+        t3 - 2.0 * t2 + t,
+        -2.0 * t3 + 3.0 * t2,
+        t3 - t2,
+    ]
+}
+
+/// Value between the `keyframe` returned by `keyframe`, whose times are
+/// `span` seconds apart. `lerp` blends two values for `Linear`
+fn interpolate<V: VectorSpace<Scalar = f32>>(
+    interpolation: Interpolation,
+    values: &[V],
+    (from, to, t): (usize, usize, f32),
+    span: f32,
+    lerp: fn(V, V, f32) -> V,
+) -> V {
+    match interpolation {
+        Interpolation::Step => values[from],
+        Interpolation::Linear => lerp(values[from], values[to], t),
+        Interpolation::CubicSpline => {
+            // Tangents are per second, the spline runs over `span`
+            let [start, out, end, into] = hermite(t);
+            values[3 * from + 1] * start
+                + values[3 * from + 2] * (out * span)
+                + values[3 * to + 1] * end
+                + values[3 * to] * (into * span)
+        }
+    }
+}
+
+/// Spherical interpolation along the shortest arc
+fn slerp(
+    from: Quaternion<f32>,
+    to: Quaternion<f32>,
+    t: f32,
+) -> Quaternion<f32> {
+    let to = if from.dot(to) < 0.0 { -to } else { to };
+    let cos = from.dot(to).min(1.0);
+
+    // Close rotations divide by almost zero, a normalized lerp is as good
+    if cos > 0.9995 {
+        return from.nlerp(to, t);
+    }
+
+    let angle = cos.acos();
+    let sin = angle.sin();
+    (from * ((1.0 - t) * angle).sin() + to * (t * angle).sin()) / sin
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{vec3, Deg, Rotation3, Transform};
+
+    use super::*;
+
+    fn approx(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).magnitude() < 1e-4
+    }
+
+    fn two_bones() -> Skeleton {
+        let bone = |name: &str, parent, height: f32| Joint {
+            name: name.to_string(),
+            parent,
+            inverse_bind: Matrix4::from_translation(vec3(0.0, -height, 0.0)),
+            rest: JointPose {
+                translation: vec3(
+                    0.0,
+                    if parent.is_some() { 1.0 } else { 0.0 },
+                    0.0,
+                ),
+                ..Default::default()
+            },
+        };
+
+        Skeleton {
+            joints: vec![bone("hip", None, 0.0), bone("knee", Some(0), 1.0)],
+            root: Matrix4::identity(),
+        }
+    }
+
+    #[test]
+    fn rest_pose_is_identity() {
+        let skeleton = two_bones();
+        let matrices = skeleton.joint_matrices(&skeleton.rest_pose());
+
+        for matrix in matrices {
+            assert_eq!(matrix, Matrix4::identity());
+        }
+    }
+
+    #[test]
+    fn children_follow_parents() {
+        let skeleton = two_bones();
+        let mut pose = skeleton.rest_pose();
+        pose[0].rotation = Quaternion::from_angle_z(Deg(90.0));
+        let matrices = skeleton.joint_matrices(&pose);
+
+        // A vertex at the tip of the second bone swings over to -X
+        let tip = matrices[1].transform_point(cgmath::point3(0.0, 2.0, 0.0));
+        assert!(approx(vec3(tip.x, tip.y, tip.z), vec3(-2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn sample_keyframes() {
+        let clip = AnimationClip {
+            name: "wave".to_string(),
+            skeleton: 0,
+            channels: vec![
+                AnimationChannel {
+                    joint: 0,
+                    interpolation: Interpolation::Linear,
+                    times: vec![1.0, 2.0],
+                    keyframes: Keyframes::Translation(vec![
+                        vec3(0.0, 0.0, 0.0),
+                        vec3(2.0, 4.0, 0.0),
+                    ]),
+                },
+                AnimationChannel {
+                    joint: 1,
+                    interpolation: Interpolation::Linear,
+                    times: vec![0.0, 2.0],
+                    keyframes: Keyframes::Rotation(vec![
+                        Quaternion::one(),
+                        Quaternion::from_angle_y(Deg(90.0)),
+                    ]),
+                },
+                AnimationChannel {
+                    joint: 1,
+                    interpolation: Interpolation::Step,
+                    times: vec![0.0, 1.0],
+                    keyframes: Keyframes::Scale(vec![
+                        vec3(1.0, 1.0, 1.0),
+                        vec3(3.0, 3.0, 3.0),
+                    ]),
+                },
+            ],
+        };
+        assert_eq!(clip.duration(), 2.0);
+
+        let mut pose = two_bones().rest_pose();
+        clip.sample(1.5, &mut pose);
+        assert!(approx(pose[0].translation, vec3(1.0, 2.0, 0.0)));
+        // Slerp keeps a constant angular speed
+        let expected = Quaternion::from_angle_y(Deg(67.5));
+        assert!(pose[1].rotation.dot(expected).abs() > 1.0 - 1e-5);
+        assert_eq!(pose[1].scale, vec3(3.0, 3.0, 3.0));
+
+        // Before the first keyframe and after the last one
+        clip.sample(0.5, &mut pose);
+        assert_eq!(pose[0].translation, vec3(0.0, 0.0, 0.0));
+        assert_eq!(pose[1].scale, vec3(1.0, 1.0, 1.0));
+        clip.sample(5.0, &mut pose);
+        assert_eq!(pose[0].translation, vec3(2.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn sample_cubic_spline() {
+        let channel = |tangent| AnimationChannel {
+            joint: 0,
+            interpolation: Interpolation::CubicSpline,
+            times: vec![0.0, 2.0],
+            keyframes: Keyframes::Translation(vec![
+                tangent,
+                vec3(0.0, 0.0, 0.0),
+                tangent,
+                tangent,
+                vec3(2.0, 0.0, 0.0),
+                tangent,
+            ]),
+        };
+        let sample = |tangent, time| {
+            let clip = AnimationClip {
+                name: "slide".to_string(),
+                skeleton: 0,
+                channels: vec![channel(tangent)],
+            };
+            let mut pose = two_bones().rest_pose();
+            clip.sample(time, &mut pose);
+            pose[0].translation
+        };
+
+        // Tangents matching the slope give a straight line
+        let slope = vec3(1.0, 0.0, 0.0);
+        assert!(approx(sample(slope, 0.5), vec3(0.5, 0.0, 0.0)));
+        assert!(approx(sample(slope, 1.0), vec3(1.0, 0.0, 0.0)));
+        // Flat ones ease in and out
+        let flat = vec3(0.0, 0.0, 0.0);
+        assert!(approx(sample(flat, 0.5), vec3(0.3125, 0.0, 0.0)));
+        assert!(approx(sample(flat, 1.0), vec3(1.0, 0.0, 0.0)));
+        assert!(approx(sample(flat, 3.0), vec3(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn slerp_takes_shortest_arc() {
+        let from = Quaternion::from_angle_x(Deg(10.0));
+        let to = -Quaternion::from_angle_x(Deg(30.0));
+        let halfway = slerp(from, to, 0.5);
+
+        let expected = Quaternion::from_angle_x(Deg(20.0));
+        assert!(halfway.dot(expected).abs() > 1.0 - 1e-5);
+        assert!((halfway.magnitude() - 1.0).abs() < 1e-5);
+    }
+}
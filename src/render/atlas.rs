@@ -0,0 +1,466 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ColorSpace, Render, Texture};
+
+/// Where an image went in an `Atlas`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    pub page: usize,
+    /// Position of the top left corner of the image in its page, in pixels,
+    /// not counting the extruded border
+    pub position: [u32; 2],
+    pub size: [u32; 2],
+    /// Top left and bottom right corners of the image, from 0 to 1 across
+    /// the page, as taken by `Sprite::uv`
+    pub uv: [[f32; 2]; 2],
+}
+
+/// Images packed into RGBA8 pages by an `AtlasBuilder`. Can be saved and
+/// loaded back without packing again
+#[derive(Serialize, Deserialize)]
+pub struct Atlas {
+    page_size: u32,
+    /// Tightly packed RGBA8 texels of every page
+    pages: Vec<Vec<u8>>,
+    entries: HashMap<String, AtlasEntry>,
+}
+
+impl Atlas {
+    /// Width and height of every page
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn page_pixels(&self, page: usize) -> &[u8] {
+        &self.pages[page]
+    }
+
+    /// Where the image added as `name` went
+    pub fn entry(&self, name: &str) -> Option<&AtlasEntry> {
+        self.entries.get(name)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &AtlasEntry)> {
+        self.entries
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    /// One texture per page, in order
+    pub fn create_textures(
+        &self,
+        render: &Render,
+        color_space: ColorSpace,
+    ) -> Vec<Texture> {
+        self.pages
+            .iter()
+            .map(|pixels| {
+                render.create_texture(
+                    self.page_size,
+                    self.page_size,
+                    pixels,
+                    color_space,
+                )
+            })
+            .collect()
+    }
+
+    /// Writes the layout and pages with bincode, for `load`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let save_error =
+            |err: String| format!("Failed to save {}: {}", path.display(), err);
+        let bytes = bincode::serialize(self)
+            .map_err(|err| save_error(err.to_string()))?;
+
+        fs::write(path, bytes).map_err(|err| save_error(err.to_string()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Atlas, String> {
+        let path = path.as_ref();
+        let load_error =
+            |err: String| format!("Failed to load {}: {}", path.display(), err);
+        let bytes =
+            fs::read(path).map_err(|err| load_error(err.to_string()))?;
+
+        bincode::deserialize(&bytes).map_err(|err| load_error(err.to_string()))
+    }
+}
+
+struct AtlasImage {
+    name: String,
+    size: [u32; 2],
+    pixels: Vec<u8>,
+}
+
+/// Packs images into square atlas pages, opening new pages when the
+/// current ones are full. Images are kept `padding` pixels apart and from
+/// the edges, and can have their border pixels repeated `extrusion` times
+/// around them so filtering near their edges doesn't pick up neighbours
+pub struct AtlasBuilder {
+    page_size: u32,
+    padding: u32,
+    extrusion: u32,
+    images: Vec<AtlasImage>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_size: u32) -> AtlasBuilder {
+        AtlasBuilder {
+            page_size,
+            padding: 1,
+            extrusion: 0,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn padding(mut self, pixels: u32) -> Self {
+        self.padding = pixels;
+
+        self
+    }
+
+    pub fn extrusion(mut self, pixels: u32) -> Self {
+        self.extrusion = pixels;
+
+        self
+    }
+
+    /// Adds an image of tightly packed RGBA8 `pixels`, replacing any added
+    /// before as `name`. Fails on empty images and on pixels not matching
+    /// the size
+    pub fn add_rgba(
+        &mut self,
+        name: &str,
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    ) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Err(format!("Image {} is empty", name));
+        }
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|count| count.checked_mul(4));
+        if expected != Some(pixels.len()) {
+            return Err(format!(
+                "Image {} of {}x{} has {} bytes of pixels",
+                name,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        self.images.retain(|image| image.name != name);
+        self.images.push(AtlasImage {
+            name: name.to_string(),
+            size: [width, height],
+            pixels,
+        });
+
+        Ok(())
+    }
+
+    /// Adds an image file, named after `path` as given
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|err| {
+                format!("Failed to load {}: {}", path.display(), err)
+            })?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        self.add_rgba(&path.to_string_lossy(), width, height, image.into_raw())
+            .map_err(|err| {
+                format!("Failed to load {}: {}", path.display(), err)
+            })
+    }
+
+    /// Packs the images added so far, tallest first
+    pub fn build(&self) -> Result<Atlas, String> {
+        let border = self.extrusion * 2 + self.padding;
+        // Padding on the left and top of the page, the cells bring theirs
+        // on the right and bottom
+        let space = self.page_size.saturating_sub(self.padding);
+
+        let mut order = (0..self.images.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            let [width, height] = self.images[i].size;
+            Reverse((height, width))
+        });
+
+        let mut packers: Vec<Skyline> = Vec::new();
+        let mut pages: Vec<Vec<u8>> = Vec::new();
+        let mut entries = HashMap::new();
+        for i in order {
+            let image = &self.images[i];
+            let [width, height] = image.size;
+            let cell = [width + border, height + border];
+
+            let placed =
+                packers.iter_mut().enumerate().find_map(|(page, packer)| {
+                    packer.insert(cell[0], cell[1]).map(|corner| (page, corner))
+                });
+            let (page, corner) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut packer = Skyline::new(space, space);
+                    let corner =
+                        packer.insert(cell[0], cell[1]).ok_or_else(|| {
+                            format!(
+                                "Image {} of {}x{} doesn't fit in atlas pages \
+                                 of {}x{}",
+                                image.name,
+                                width,
+                                height,
+                                self.page_size,
+                                self.page_size
+                            )
+                        })?;
+                    packers.push(packer);
+                    pages.push(vec![0; (self.page_size.pow(2) * 4) as usize]);
+                    (packers.len() - 1, corner)
+                }
+            };
+
+            let offset = self.padding + self.extrusion;
+            let position = [corner[0] + offset, corner[1] + offset];
+            blit_extruded(
+                &mut pages[page],
+                self.page_size,
+                image,
+                position,
+                self.extrusion,
+            );
+
+            let size = self.page_size as f32;
+            entries.insert(
+                image.name.clone(),
+                AtlasEntry {
+                    page,
+                    position,
+                    size: image.size,
+                    uv: [
+                        [position[0] as f32 / size, position[1] as f32 / size],
+                        [
+                            (position[0] + width) as f32 / size,
+                            (position[1] + height) as f32 / size,
+                        ],
+                    ],
+                },
+            );
+        }
+
+        Ok(Atlas {
+            page_size: self.page_size,
+            pages,
+            entries,
+        })
+    }
+}
+
+/// Copies `image` into `page` at `position`, repeating its edge texels
+/// `extrusion` times around it
+fn blit_extruded(
+    page: &mut [u8],
+    page_size: u32,
+    image: &AtlasImage,
+    position: [u32; 2],
+    extrusion: u32,
+) {
+    let [width, height] = image.size;
+    // No edge to repeat, `add_rgba` turns these away anyway
+    if width == 0 || height == 0 {
+        return;
+    }
+    let extrusion = extrusion as i64;
+
+    for y in -extrusion..height as i64 + extrusion {
+        let source_y = y.clamp(0, height as i64 - 1) as u32;
+        let page_y = (position[1] as i64 + y) as u32;
+        for x in -extrusion..width as i64 + extrusion {
+            let source_x = x.clamp(0, width as i64 - 1) as u32;
+            let page_x = (position[0] as i64 + x) as u32;
+
+            let source = ((source_y * width + source_x) * 4) as usize;
+            let target = ((page_y * page_size + page_x) * 4) as usize;
+            page[target..target + 4]
+                .copy_from_slice(&image.pixels[source..source + 4]);
+        }
+    }
+}
+
+/// Bottom-left skyline packer: rectangles rest on the lowest spot of the
+/// outline left by the ones already placed
+struct Skyline {
+    width: u32,
+    height: u32,
+    /// Horizontal segments of the outline from left to right, as their left
+    /// end, height and width
+    segments: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Skyline {
+        Skyline {
+            width,
+            height,
+            segments: vec![(0, 0, width)],
+        }
+    }
+
+    /// Top left corner given to a rectangle, `None` if it doesn't fit
+    fn insert(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        // Lowest top, then leftmost
+        let (index, y) = (0..self.segments.len())
+            .filter_map(|index| Some((index, self.fit(index, width, height)?)))
+            .min_by_key(|&(index, y)| (y, self.segments[index].0))?;
+        let x = self.segments[index].0;
+
+        // The rectangle's top replaces the segments it covers
+        self.segments.insert(index, (x, y + height, width));
+        let right = x + width;
+        let next = index + 1;
+        while next < self.segments.len() {
+            let (start, top, length) = self.segments[next];
+            if start >= right {
+                break;
+            }
+            let end = start + length;
+            if end <= right {
+                self.segments.remove(next);
+            } else {
+                self.segments[next] = (right, top, end - right);
+                break;
+            }
+        }
+        // Neighbours at the same height merge
+        self.segments.dedup_by(|right, left| {
+            if left.1 == right.1 {
+                left.2 += right.2;
+                true
+            } else {
+                false
+            }
+        });
+
+        Some([x, y])
+    }
+
+    /// Height a rectangle starting at segment `index` rests at
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.segments[index].0;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut covered = 0;
+        for &(_, top, length) in &self.segments[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(top);
+            covered += length;
+        }
+
+        (y + height <= self.height).then_some(y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; (width * height * 4) as usize]
+    }
+
+    #[test]
+    fn skyline_packs_without_overlap() {
+        let mut skyline = Skyline::new(10, 10);
+        assert_eq!(skyline.insert(4, 6), Some([0, 0]));
+        assert_eq!(skyline.insert(6, 3), Some([4, 0]));
+        // Lowest spot is over the second rectangle
+        assert_eq!(skyline.insert(6, 3), Some([4, 3]));
+        assert_eq!(skyline.insert(10, 4), Some([0, 6]));
+        assert_eq!(skyline.insert(1, 1), None);
+    }
+
+    #[test]
+    fn pages_padding_and_extrusion() {
+        let mut builder = AtlasBuilder::new(16).padding(1).extrusion(1);
+        builder.add_rgba("a", 4, 4, solid(4, 4, 10)).unwrap();
+        builder.add_rgba("b", 2, 2, solid(2, 2, 20)).unwrap();
+        builder.add_rgba("big", 12, 12, solid(12, 12, 30)).unwrap();
+        let atlas = builder.build().unwrap();
+
+        let big = atlas.entry("big").unwrap();
+        assert_eq!((big.page, big.position), (0, [2, 2]));
+        // Nothing else fits next to the tallest image
+        let a = atlas.entry("a").unwrap();
+        assert_eq!((a.page, a.position), (1, [2, 2]));
+        let b = atlas.entry("b").unwrap();
+        assert_eq!((b.page, b.position), (1, [9, 2]));
+        assert_eq!(a.uv, [[2.0 / 16.0, 2.0 / 16.0], [6.0 / 16.0, 6.0 / 16.0]]);
+        assert_eq!(atlas.page_count(), 2);
+
+        let texel = |page: usize, x: u32, y: u32| {
+            atlas.page_pixels(page)[((y * 16 + x) * 4) as usize]
+        };
+        // Extruded border, then padding
+        assert_eq!(texel(1, 1, 1), 10);
+        assert_eq!(texel(1, 6, 3), 10);
+        assert_eq!(texel(1, 7, 3), 0);
+        assert_eq!(texel(1, 8, 2), 20);
+        assert_eq!(texel(0, 0, 0), 0);
+
+        let too_big = {
+            let mut builder = AtlasBuilder::new(16);
+            builder.add_rgba("huge", 16, 1, solid(16, 1, 0)).unwrap();
+            builder.build()
+        };
+        assert!(too_big.is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_images() {
+        let mut builder = AtlasBuilder::new(16).extrusion(1);
+        assert!(builder.add_rgba("empty", 0, 4, Vec::new()).is_err());
+        assert!(builder.add_rgba("short", 2, 2, solid(2, 1, 0)).is_err());
+        assert!(builder
+            .add_rgba("overflow", u32::MAX, u32::MAX, Vec::new())
+            .is_err());
+
+        assert_eq!(builder.build().unwrap().page_count(), 0);
+        assert!(builder.build().unwrap().page_count() == 0);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut builder = AtlasBuilder::new(8);
+        builder.add_rgba("a", 2, 3, solid(2, 3, 255)).unwrap();
+        let atlas = builder.build().unwrap();
+
+        let path = std::env::temp_dir().join("conc_atlas_test.bin");
+        atlas.save(&path).unwrap();
+        let loaded = Atlas::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.page_size(), 8);
+        assert_eq!(loaded.entry("a"), atlas.entry("a"));
+        assert_eq!(loaded.page_pixels(0), atlas.page_pixels(0));
+        assert!(Atlas::load(&path).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::num::NonZeroU64;
+
+use wgpu::{
+    BindingResource, BindingType, BufferBinding, BufferBindingType,
+    StorageTextureAccess, TextureFormat, TextureSampleType, TextureView,
+    TextureViewDimension,
+};
+
+use super::{Buffer, Sampler, Texture};
+
+/// Shape of one entry of a bind group layout. Entries are numbered in the
+/// order they are given to `Render::create_bind_group_layout_with`
+#[derive(Clone, Copy, Debug)]
+pub enum BindingLayout {
+    Uniform,
+    /// Uniform buffer whose offset is given when binding the group, so one
+    /// buffer can hold the uniforms of many draws
+    DynamicUniform,
+    Storage {
+        read_only: bool,
+    },
+    Texture,
+    /// Float texture that can only be read with `textureLoad`, e.g.
+    /// `Rgba32Float`
+    UnfilterableTexture,
+    /// Six square faces sampled with a direction
+    Cubemap,
+    /// Array of depth textures, read through a `ComparisonSampler`
+    DepthTextureArray,
+    /// Texture written by compute shaders, with `D2` or `D2Array` views
+    StorageTexture {
+        format: TextureFormat,
+        dimension: TextureViewDimension,
+    },
+    Sampler,
+    /// Sampler comparing depth textures against a reference value
+    ComparisonSampler,
+}
+
+impl BindingLayout {
+    pub fn to_wgpu(self) -> BindingType {
+        match self {
+            BindingLayout::Uniform => BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            BindingLayout::DynamicUniform => BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            },
+            BindingLayout::Storage { read_only } => BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            BindingLayout::Texture => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            BindingLayout::UnfilterableTexture => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            BindingLayout::Cubemap => BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::Cube,
+                multisampled: false,
+            },
+            BindingLayout::DepthTextureArray => BindingType::Texture {
+                sample_type: TextureSampleType::Depth,
+                view_dimension: TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            BindingLayout::StorageTexture { format, dimension } => {
+                BindingType::StorageTexture {
+                    access: StorageTextureAccess::WriteOnly,
+                    format,
+                    view_dimension: dimension,
+                }
+            }
+            BindingLayout::Sampler => BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            BindingLayout::ComparisonSampler => BindingType::Sampler {
+                filtering: true,
+                comparison: true,
+            },
+        }
+    }
+}
+
+/// Resource bound to the entry with the same index in the layout
+pub enum Binding<'a> {
+    Buffer(&'a Buffer),
+    /// First `size` bytes of a buffer, for `BindingLayout::DynamicUniform`
+    BufferRange(&'a Buffer, u64),
+    Texture(&'a Texture),
+    /// View of part of a texture, e.g. one mip level to write into
+    TextureView(&'a TextureView),
+    Sampler(&'a Sampler),
+}
+
+impl<'a> Binding<'a> {
+    pub fn resource(&self) -> BindingResource<'a> {
+        match self {
+            Binding::Buffer(buffer) => buffer.binding_resource(),
+            Binding::BufferRange(buffer, size) => {
+                BindingResource::Buffer(BufferBinding {
+                    buffer: buffer.get_buf(),
+                    offset: 0,
+                    size: NonZeroU64::new(*size),
+                })
+            }
+            Binding::Texture(texture) => {
+                BindingResource::TextureView(texture.get_view())
+            }
+            Binding::TextureView(view) => BindingResource::TextureView(view),
+            Binding::Sampler(sampler) => {
+                BindingResource::Sampler(sampler.get_sampler())
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Transform, Vector3,
+    Vector4,
+};
+
+/// Axis-aligned box between two corners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+/// Sphere enclosing a mesh, cheaper to test than its box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+/// Planes enclosing the volume a view-projection matrix sees, their normals
+/// pointing inwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, as the normal in xyz and the
+    /// distance to the origin in w
+    planes: [Vector4<f32>; 6],
+}
+
+impl Aabb {
+    /// Smallest box holding every point, a single point at the origin
+    /// without any
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Aabb {
+        let mut points = points.into_iter();
+        let first = points.next().unwrap_or_else(Point3::origin);
+
+        points.fold(Aabb::new(first, first), |aabb, point| Aabb {
+            min: Point3::new(
+                aabb.min.x.min(point.x),
+                aabb.min.y.min(point.y),
+                aabb.min.z.min(point.z),
+            ),
+            max: Point3::new(
+                aabb.max.x.max(point.x),
+                aabb.max.y.max(point.y),
+                aabb.max.z.max(point.z),
+            ),
+        })
+    }
+
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Half the size along each axis
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    /// Box holding this one once transformed by `matrix`, larger than the
+    /// transformed box itself when rotated
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        let center = matrix.transform_point(self.center());
+        let extents = self.extents();
+        // Each axis of the new box reaches as far as the transformed axes
+        // of the old one combined
+        let extents = Vector3::new(
+            matrix.row(0).truncate().map(f32::abs).dot(extents),
+            matrix.row(1).truncate().map(f32::abs).dot(extents),
+            matrix.row(2).truncate().map(f32::abs).dot(extents),
+        );
+
+        Aabb::new(center - extents, center + extents)
+    }
+}
+
+impl BoundingSphere {
+    /// Sphere through the corners of `aabb`
+    pub fn from_aabb(aabb: &Aabb) -> BoundingSphere {
+        BoundingSphere {
+            center: aabb.center(),
+            radius: aabb.extents().magnitude(),
+        }
+    }
+
+    /// Sphere holding this one once transformed by `matrix`, scaled by its
+    /// largest scale factor
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        let scale = [matrix.x, matrix.y, matrix.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0, f32::max);
+
+        BoundingSphere {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl Frustum {
+    /// Extracts the planes from the rows of `view_proj`, which maps depth to
+    /// [0, 1] as wgpu expects
+    pub fn from_matrix(view_proj: Matrix4<f32>) -> Frustum {
+        let row = |i| view_proj.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.truncate().magnitude());
+
+        Frustum { planes }
+    }
+
+    /// Signed distance from `plane` to `point`, positive inside
+    fn distance(plane: Vector4<f32>, point: Point3<f32>) -> f32 {
+        plane.truncate().dot(point.to_vec()) + plane.w
+    }
+
+    /// Whether any part of the sphere may be visible
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|&plane| {
+            Frustum::distance(plane, sphere.center) >= -sphere.radius
+        })
+    }
+
+    /// Whether any part of the box may be visible. Boxes near the corners of
+    /// the frustum can pass without being in view
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|&plane| {
+            // Corner furthest along the normal
+            let corner = Point3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+
+            Frustum::distance(plane, corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_abs_diff_eq, point3, vec3, Deg, Matrix3};
+
+    use super::*;
+    use crate::camera::{Camera, Projection};
+
+    fn camera() -> Camera {
+        // At the origin, looking down -Z
+        Camera::new(
+            point3(0.0, 0.0, 0.0),
+            Projection::Perspective {
+                fovy: Deg(60.0),
+                near: 0.1,
+                far: 100.0,
+            },
+            16.0 / 9.0,
+        )
+    }
+
+    #[test]
+    fn transformed_bounds() {
+        let aabb = Aabb::from_points([
+            point3(-1.0, 0.0, 0.0),
+            point3(1.0, 2.0, 0.5),
+            point3(0.0, 1.0, -0.5),
+        ]);
+        assert_eq!(
+            aabb,
+            Aabb::new(point3(-1.0, 0.0, -0.5), point3(1.0, 2.0, 0.5))
+        );
+
+        // Quarter turn around Z, twice as large, moved along X
+        let matrix = Matrix4::from_translation(vec3(10.0, 0.0, 0.0))
+            * Matrix4::from(Matrix3::from_angle_z(Deg(90.0)))
+            * Matrix4::from_scale(2.0);
+        let moved = aabb.transform(&matrix);
+        assert_abs_diff_eq!(moved.min, point3(6.0, -2.0, -1.0), epsilon = 1e-5);
+        assert_abs_diff_eq!(moved.max, point3(10.0, 2.0, 1.0), epsilon = 1e-5);
+
+        let sphere = BoundingSphere::from_aabb(&aabb).transform(&matrix);
+        assert_abs_diff_eq!(
+            sphere.center,
+            point3(8.0, 0.0, 0.0),
+            epsilon = 1e-5
+        );
+        assert_abs_diff_eq!(sphere.radius, 2.0 * 1.5, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn frustum_culling() {
+        let frustum = camera().frustum();
+        let sphere = |center, radius| BoundingSphere { center, radius };
+
+        assert!(
+            frustum.intersects_sphere(&sphere(point3(0.0, 0.0, -10.0), 1.0))
+        );
+        // Behind the camera and past the far plane
+        assert!(!frustum.intersects_sphere(&sphere(point3(0.0, 0.0, 5.0), 1.0)));
+        assert!(
+            !frustum.intersects_sphere(&sphere(point3(0.0, 0.0, -102.0), 1.0))
+        );
+        // Left of the view, then large enough to reach into it
+        assert!(
+            !frustum.intersects_sphere(&sphere(point3(-30.0, 0.0, -10.0), 1.0))
+        );
+        assert!(
+            frustum.intersects_sphere(&sphere(point3(-30.0, 0.0, -10.0), 25.0))
+        );
+
+        let cube = |center: Point3<f32>| {
+            Aabb::new(
+                center - vec3(1.0, 1.0, 1.0),
+                center + vec3(1.0, 1.0, 1.0),
+            )
+        };
+        assert!(frustum.intersects_aabb(&cube(point3(0.0, 0.0, -10.0))));
+        assert!(frustum.intersects_aabb(&cube(point3(0.0, 0.0, 0.5))));
+        assert!(!frustum.intersects_aabb(&cube(point3(0.0, 0.0, 5.0))));
+        assert!(!frustum.intersects_aabb(&cube(point3(0.0, 30.0, -10.0))));
+    }
+}
@@ -1,17 +1,25 @@
 use wgpu::{BindingResource, Buffer as WgpuBuffer};
 
+use super::stats::Allocation;
+
 pub struct Buffer {
     buf: WgpuBuffer,
     size: usize,
     offset: usize,
+    _allocation: Allocation,
 }
 
 impl<'a> Buffer {
-    pub fn new(buf: WgpuBuffer, data: &[u8]) -> Buffer {
+    pub(super) fn new(
+        buf: WgpuBuffer,
+        data: &[u8],
+        allocation: Allocation,
+    ) -> Buffer {
         Buffer {
             buf,
             size: data.len(),
             offset: 0,
+            _allocation: allocation,
         }
     }
 
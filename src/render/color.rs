@@ -0,0 +1,100 @@
+/// Converts one sRGB-encoded channel to linear
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts one linear channel to sRGB encoding
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Color in linear space, which is what every shader works in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Builds a linear color from sRGB values, e.g. ones picked in an image
+    /// editor. Alpha is always linear
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color {
+            r: srgb_to_linear(r),
+            g: srgb_to_linear(g),
+            b: srgb_to_linear(b),
+            a,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn from_srgb8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::from_srgb(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    #[allow(dead_code)]
+    pub fn to_wgpu(self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.r as f64,
+            g: self.g as f64,
+            b: self.b as f64,
+            a: self.a as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=255 {
+            let value = i as f32 / 255.0;
+            let result = linear_to_srgb(srgb_to_linear(value));
+            assert!((result - value).abs() < 1e-5, "{} != {}", result, value);
+        }
+    }
+
+    #[test]
+    fn srgb_midpoint() {
+        // sRGB 0.5 is roughly 21.4% linear intensity
+        assert!((srgb_to_linear(0.5) - 0.2140).abs() < 1e-3);
+        assert_eq!(Color::from_srgb8(255, 255, 255, 255), Color::WHITE);
+    }
+}
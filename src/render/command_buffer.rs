@@ -1,70 +1,193 @@
-use std::{ops::Range, ptr::null};
+use std::ops::Range;
 
 use wgpu::{
-    BindGroup, CommandEncoder, CommandEncoderDescriptor, Device, LoadOp,
-    Operations, Queue, RenderPass, RenderPassColorAttachment,
-    RenderPassDescriptor,
+    BindGroup, CommandEncoder, CommandEncoderDescriptor, ComputePassDescriptor,
+    Device, LoadOp, Operations, Queue, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor,
 };
 
-use super::{Buffer, Framebuffer, Pipeline};
+use super::{
+    Buffer, ComputePipeline, DrawStats, Framebuffer, GpuProfiler, Pipeline,
+};
 
 pub struct CommandBuffer<'a> {
     encoder: Box<CommandEncoder>,
     render_pass: *mut RenderPass<'a>,
+    stats: DrawStats,
+    /// Name of the passes begun from now on
+    label: Option<&'a str>,
+    /// Profiler timing the passes, with the slot it gave this buffer
+    profiler: Option<(&'a GpuProfiler, usize)>,
+    /// Query of the timestamp ending the pass being recorded
+    pass_end: Option<u32>,
 }
 
 impl<'a> CommandBuffer<'a> {
-    fn new(
-        encoder: Box<CommandEncoder>,
-        render_pass: *mut RenderPass<'a>,
-    ) -> CommandBuffer {
+    pub fn begin(device: &Device) -> CommandBuffer<'a> {
+        let encoder = device
+            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+
         CommandBuffer {
-            encoder,
-            render_pass,
+            encoder: Box::new(encoder),
+            render_pass: std::ptr::null_mut(),
+            stats: DrawStats::default(),
+            label: None,
+            profiler: None,
+            pass_end: None,
         }
     }
 
-    pub fn begin(device: &Device) -> CommandBuffer {
-        let encoder = device
-            .create_command_encoder(&CommandEncoderDescriptor { label: None });
+    /// Names the passes begun from now on, for graphics debuggers and
+    /// `GpuProfiler::timings`
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
 
-        CommandBuffer::new(Box::new(encoder), std::ptr::null_mut())
+    /// Times every pass recorded from now on with `profiler`. Does nothing
+    /// while the profiler is still waiting on the results of earlier frames
+    pub fn profile(mut self, profiler: &'a GpuProfiler) -> Self {
+        self.profiler = profiler.begin().map(|slot| (profiler, slot));
+        self
+    }
+
+    /// Draws and dispatches recorded so far
+    pub fn stats(&self) -> DrawStats {
+        self.stats
+    }
+
+    /// Ends the render pass being recorded, if any. wgpu records a pass
+    /// into its encoder when the pass is dropped
+    fn end_pass(&mut self) {
+        if !self.render_pass.is_null() {
+            let render_pass = unsafe { Box::from_raw(self.render_pass) };
+            drop(render_pass);
+            self.render_pass = std::ptr::null_mut();
+            self.time_pass_end();
+        }
+    }
+
+    /// Writes the timestamp starting the pass about to begin, if profiling
+    fn time_pass_start(&mut self) {
+        if let Some((profiler, slot)) = self.profiler {
+            let name = self.label.unwrap_or("unlabeled");
+            if let Some(query) = profiler.begin_pass(slot, name) {
+                self.encoder.write_timestamp(profiler.query_set(), query);
+                self.pass_end = Some(query + 1);
+            }
+        }
+    }
+
+    /// Writes the timestamp ending the pass that just ended, if it was timed
+    fn time_pass_end(&mut self) {
+        if let (Some((profiler, _)), Some(query)) =
+            (self.profiler, self.pass_end.take())
+        {
+            self.encoder.write_timestamp(profiler.query_set(), query);
+        }
     }
 
+    /// Starts a new render pass into `framebuffer`, ending the previous one
     pub fn configure_draw(
         self,
         pipeline: &'a Pipeline,
         framebuffer: &'a Framebuffer,
     ) -> Self {
+        self.begin_pass(pipeline, framebuffer, true)
+    }
+
+    /// Starts a new render pass into `framebuffer` like `configure_draw`,
+    /// but drawing over what it already holds instead of clearing it
+    pub fn configure_draw_over(
+        self,
+        pipeline: &'a Pipeline,
+        framebuffer: &'a Framebuffer,
+    ) -> Self {
+        self.begin_pass(pipeline, framebuffer, false)
+    }
+
+    fn begin_pass(
+        mut self,
+        pipeline: &'a Pipeline,
+        framebuffer: &'a Framebuffer,
+        clear: bool,
+    ) -> Self {
+        self.end_pass();
+        self.time_pass_start();
+
+        let color_attachments = framebuffer
+            .get_targets()
+            .into_iter()
+            .map(|view| RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: if clear {
+                        LoadOp::Clear(wgpu::Color::BLACK)
+                    } else {
+                        LoadOp::Load
+                    },
+                    store: true,
+                },
+            })
+            .collect::<Vec<_>>();
+        let depth_stencil_attachment =
+            framebuffer.get_depth_view().map(|view| {
+                RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(Operations {
+                        load: if clear {
+                            LoadOp::Clear(1.0)
+                        } else {
+                            LoadOp::Load
+                        },
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }
+            });
+
         let encoder = Box::into_raw(self.encoder);
         let mut render_pass = unsafe {
             (*encoder).begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[RenderPassColorAttachment {
-                    view: framebuffer.get_target(),
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: None,
+                label: self.label,
+                color_attachments: &color_attachments,
+                depth_stencil_attachment,
             })
         };
 
         render_pass.set_pipeline(pipeline.pipeline());
 
-        CommandBuffer::new(
-            unsafe { Box::from_raw(encoder) },
-            Box::into_raw(Box::new(render_pass)),
-        )
+        CommandBuffer {
+            encoder: unsafe { Box::from_raw(encoder) },
+            render_pass: Box::into_raw(Box::new(render_pass)),
+            stats: self.stats,
+            label: self.label,
+            profiler: self.profiler,
+            pass_end: self.pass_end,
+        }
+    }
+
+    /// Switches pipeline without ending the pass, so the following draws
+    /// keep adding to what was drawn so far
+    pub fn set_pipeline(self, pipeline: &'a Pipeline) -> Self {
+        let pass = unsafe { &mut *self.render_pass };
+        pass.set_pipeline(pipeline.pipeline());
+
+        self
     }
 
     pub fn set_vertices(self, buffer: &'a Buffer) -> Self {
+        self.set_vertices_at(0, buffer)
+    }
+
+    /// Binds `buffer` to the vertex buffer slot `slot`, in the order the
+    /// pipeline was given its vertex and instance layouts
+    pub fn set_vertices_at(self, slot: u32, buffer: &'a Buffer) -> Self {
         let pass = unsafe { &mut *self.render_pass };
-        pass.set_vertex_buffer(0, buffer.get_buf().slice(..));
+        pass.set_vertex_buffer(slot, buffer.get_buf().slice(..));
 
-        CommandBuffer::new(self.encoder, self.render_pass)
+        self
     }
 
     pub fn set_indices(self, buffer: &'a Buffer) -> Self {
@@ -74,27 +197,116 @@ impl<'a> CommandBuffer<'a> {
             wgpu::IndexFormat::Uint16,
         );
 
-        CommandBuffer::new(self.encoder, self.render_pass)
+        self
     }
 
     pub fn bind_resources(self, bind_info: &'a BindGroup) -> Self {
+        self.bind_resources_at(0, bind_info)
+    }
+
+    pub fn bind_resources_at(
+        self,
+        index: u32,
+        bind_info: &'a BindGroup,
+    ) -> Self {
+        self.bind_resources_with_offsets(index, bind_info, &[])
+    }
+
+    /// Binds a group with one offset per `BindingLayout::DynamicUniform`
+    /// entry, in order
+    pub fn bind_resources_with_offsets(
+        self,
+        index: u32,
+        bind_info: &'a BindGroup,
+        offsets: &[u32],
+    ) -> Self {
         let pass = unsafe { &mut *self.render_pass };
-        pass.set_bind_group(0, bind_info, &[]);
+        pass.set_bind_group(index, bind_info, offsets);
 
-        CommandBuffer::new(self.encoder, self.render_pass)
+        self
     }
 
     pub fn draw(self, range: Range<u32>) -> Self {
+        self.draw_from(range, 0)
+    }
+
+    /// Draws with `base_vertex` added to every index, for meshes sharing
+    /// the same vertex and index buffers
+    pub fn draw_from(mut self, range: Range<u32>, base_vertex: i32) -> Self {
         let pass = unsafe { &mut *self.render_pass };
-        pass.draw_indexed(range, 0, 0..1);
+        self.stats.add_draw(range.len() as u32, 1);
+        pass.draw_indexed(range, base_vertex, 0..1);
 
-        CommandBuffer::new(self.encoder, self.render_pass)
+        self
     }
 
-    pub fn submit(self, queue: &Queue) {
-        {
-            let _render_pass = unsafe { Box::from_raw(self.render_pass) };
+    /// Limits the following draws to a rectangle of the target, in pixels
+    /// from its top left corner
+    pub fn set_scissor(self, position: [u32; 2], size: [u32; 2]) -> Self {
+        let pass = unsafe { &mut *self.render_pass };
+        pass.set_scissor_rect(position[0], position[1], size[0], size[1]);
+
+        self
+    }
+
+    /// Draws without an index buffer, e.g. vertices generated in the shader
+    pub fn draw_vertices(mut self, range: Range<u32>) -> Self {
+        let pass = unsafe { &mut *self.render_pass };
+        self.stats.add_draw(range.len() as u32, 1);
+        pass.draw(range, 0..1);
+
+        self
+    }
+
+    pub fn draw_instanced(
+        mut self,
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    ) -> Self {
+        let pass = unsafe { &mut *self.render_pass };
+        self.stats
+            .add_draw(vertices.len() as u32, instances.len() as u32);
+        pass.draw(vertices, instances);
+
+        self
+    }
+
+    /// Runs `pipeline` over `workgroups` in a compute pass of its own,
+    /// ending the render pass being recorded. `bind_groups` are bound in
+    /// order from index 0
+    pub fn dispatch(
+        mut self,
+        pipeline: &ComputePipeline,
+        bind_groups: &[&BindGroup],
+        workgroups: [u32; 3],
+    ) -> Self {
+        self.end_pass();
+        self.time_pass_start();
+
+        let mut pass = self
+            .encoder
+            .begin_compute_pass(&ComputePassDescriptor { label: self.label });
+        pass.set_pipeline(pipeline.pipeline());
+        for (i, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        let [x, y, z] = workgroups;
+        pass.dispatch(x, y, z);
+        drop(pass);
+        self.time_pass_end();
+        self.stats.dispatches += 1;
+
+        self
+    }
+
+    pub fn submit(mut self, queue: &Queue) {
+        self.end_pass();
+        if let Some((profiler, slot)) = self.profiler {
+            profiler.resolve(slot, &mut self.encoder);
         }
         queue.submit(Some(self.encoder.finish()));
+        if let Some((profiler, slot)) = self.profiler {
+            profiler.submitted(slot);
+        }
     }
 }
@@ -0,0 +1,224 @@
+use std::str::FromStr;
+
+use wgpu::{
+    AdapterInfo, Backends, Features, Limits,
+    PowerPreference as WgpuPowerPreference, TextureFormat,
+};
+
+use super::PresentMode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Vulkan,
+    Gl,
+    Metal,
+    Dx12,
+    Primary,
+    All,
+}
+
+impl Backend {
+    pub fn to_wgpu(self) -> Backends {
+        match self {
+            Backend::Vulkan => Backends::VULKAN,
+            Backend::Gl => Backends::GL,
+            Backend::Metal => Backends::METAL,
+            Backend::Dx12 => Backends::DX12,
+            Backend::Primary => Backends::PRIMARY,
+            Backend::All => Backends::all(),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vulkan" | "vk" => Ok(Backend::Vulkan),
+            "gl" | "gles" | "opengl" => Ok(Backend::Gl),
+            "metal" => Ok(Backend::Metal),
+            "dx12" | "d3d12" => Ok(Backend::Dx12),
+            "primary" => Ok(Backend::Primary),
+            "all" => Ok(Backend::All),
+            _ => Err(format!("Unknown backend '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerPreference {
+    LowPower,
+    HighPerformance,
+}
+
+impl PowerPreference {
+    pub fn to_wgpu(self) -> WgpuPowerPreference {
+        match self {
+            PowerPreference::LowPower => WgpuPowerPreference::LowPower,
+            PowerPreference::HighPerformance => {
+                WgpuPowerPreference::HighPerformance
+            }
+        }
+    }
+}
+
+impl FromStr for PowerPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" | "lowpower" | "low-power" => Ok(PowerPreference::LowPower),
+            "high" | "highperformance" | "high-performance" => {
+                Ok(PowerPreference::HighPerformance)
+            }
+            _ => Err(format!("Unknown power preference '{}'", s)),
+        }
+    }
+}
+
+/// Encoding of the swapchain. With `Srgb` the hardware converts the linear
+/// values written by shaders on output; with `Linear` they are stored as-is,
+/// so whatever writes the final image has to encode them itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    Srgb,
+    Linear,
+}
+
+impl SurfaceFormat {
+    /// Returns the variant of `format` with this encoding
+    pub fn apply(self, format: TextureFormat) -> TextureFormat {
+        match (self, format) {
+            (SurfaceFormat::Srgb, TextureFormat::Bgra8Unorm) => {
+                TextureFormat::Bgra8UnormSrgb
+            }
+            (SurfaceFormat::Srgb, TextureFormat::Rgba8Unorm) => {
+                TextureFormat::Rgba8UnormSrgb
+            }
+            (SurfaceFormat::Linear, TextureFormat::Bgra8UnormSrgb) => {
+                TextureFormat::Bgra8Unorm
+            }
+            (SurfaceFormat::Linear, TextureFormat::Rgba8UnormSrgb) => {
+                TextureFormat::Rgba8Unorm
+            }
+            (_, format) => format,
+        }
+    }
+}
+
+impl FromStr for SurfaceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "srgb" => Ok(SurfaceFormat::Srgb),
+            "linear" | "unorm" => Ok(SurfaceFormat::Linear),
+            _ => Err(format!("Unknown surface format '{}'", s)),
+        }
+    }
+}
+
+/// Options used by `Render::new` to pick an instance, adapter and device
+pub struct RenderConfig {
+    pub backends: Vec<Backend>,
+    pub power_preference: PowerPreference,
+    /// Only accept a software adapter (e.g. lavapipe, WARP), which lets the
+    /// renderer run on machines without a GPU
+    pub force_fallback_adapter: bool,
+    /// Index into the list returned by `Render::enumerate_adapters`. When
+    /// set, it overrides `power_preference` and `force_fallback_adapter`
+    pub adapter: Option<usize>,
+    pub features: Features,
+    /// Features requested only when the adapter supports them, for things
+    /// able to do without them like `GpuProfiler`
+    pub optional_features: Features,
+    pub limits: Limits,
+    pub present_mode: PresentMode,
+    pub surface_format: SurfaceFormat,
+}
+
+impl RenderConfig {
+    pub fn wgpu_backends(&self) -> Backends {
+        self.backends
+            .iter()
+            .fold(Backends::empty(), |acc, x| acc | x.to_wgpu())
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            backends: vec![Backend::Primary],
+            power_preference: PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            adapter: None,
+            features: Features::empty(),
+            optional_features: Features::empty(),
+            limits: Limits::downlevel_defaults(),
+            present_mode: PresentMode::Fifo,
+            surface_format: SurfaceFormat::Srgb,
+        }
+    }
+}
+
+/// Adapter found by `Render::enumerate_adapters`. `index` is the value to
+/// put in `RenderConfig::adapter` to select it
+pub struct AdapterDescription {
+    pub index: usize,
+    pub info: AdapterInfo,
+    pub features: Features,
+}
+
+impl std::fmt::Display for AdapterDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} ({:?}, {:?}, vendor 0x{:04x}, device 0x{:04x})",
+            self.index,
+            self.info.name,
+            self.info.backend,
+            self.info.device_type,
+            self.info.vendor,
+            self.info.device,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_backend() {
+        assert_eq!("vulkan".parse::<Backend>(), Ok(Backend::Vulkan));
+        assert_eq!("GL".parse::<Backend>(), Ok(Backend::Gl));
+        assert!("glide".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn combine_backends() {
+        let config = RenderConfig {
+            backends: vec![Backend::Vulkan, Backend::Gl],
+            ..Default::default()
+        };
+
+        assert_eq!(config.wgpu_backends(), Backends::VULKAN | Backends::GL);
+    }
+
+    #[test]
+    fn surface_format_variants() {
+        assert_eq!(
+            SurfaceFormat::Srgb.apply(TextureFormat::Bgra8Unorm),
+            TextureFormat::Bgra8UnormSrgb
+        );
+        assert_eq!(
+            SurfaceFormat::Linear.apply(TextureFormat::Rgba8UnormSrgb),
+            TextureFormat::Rgba8Unorm
+        );
+        assert_eq!(
+            SurfaceFormat::Linear.apply(TextureFormat::Rgba16Float),
+            TextureFormat::Rgba16Float
+        );
+    }
+}
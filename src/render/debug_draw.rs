@@ -0,0 +1,480 @@
+use std::f32::consts::TAU;
+use std::mem::size_of;
+
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use cgmath::{
+    point3, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, SquareMatrix,
+    Transform, Vector3, Vector4,
+};
+use wgpu::{
+    BindGroup, BlendState, CompareFunction, PrimitiveTopology, ShaderStages,
+    TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, Color, CommandBuffer, Light, Pipeline,
+    Render, ShaderAttribute, ShaderAttributeType,
+};
+use crate::camera::Camera;
+
+/// Segments of the circles making up spheres and cones
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Pairs of corners joined by the edges of a box, the corners being
+/// numbered with x in bit 0, y in bit 1 and z in bit 2
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct DebugUniforms {
+    view_proj: [[f32; 4]; 4],
+    encode_srgb: u32,
+    _padding: [u32; 3],
+}
+
+struct DebugLine {
+    vertices: [DebugVertex; 2],
+    depth_test: bool,
+    /// Seconds left to draw the line for
+    remaining: f32,
+}
+
+/// Lines, boxes, spheres, frustums and axes drawn over the scene to see
+/// what it is made of. Shapes are added from anywhere during the frame and
+/// drawn by the next `prepare` and `draw`, for one frame or for as long as
+/// `set_duration` asks
+pub struct DebugDraw {
+    /// Only with a depth attachment, drawing the lines that are depth tested
+    tested_pipeline: Option<Pipeline>,
+    overlay_pipeline: Pipeline,
+    uniforms: Buffer,
+    bind_group: BindGroup,
+    vertices: Buffer,
+    /// Vertices `vertices` has room for
+    capacity: usize,
+    lines: Vec<DebugLine>,
+    /// Vertex counts of the depth tested and overlay lines of the last
+    /// `prepare`
+    counts: (u32, u32),
+    depth_test: bool,
+    duration: f32,
+    encode_srgb: bool,
+}
+
+impl DebugDraw {
+    /// Debug drawing into framebuffers of `format` with a depth attachment
+    /// of `depth` if given. Without one, nothing is depth tested
+    pub fn new(
+        render: &Render,
+        format: TextureFormat,
+        depth: Option<TextureFormat>,
+    ) -> DebugDraw {
+        let layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/debug.wgsl"),
+        ));
+        let vertex_layout = render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::Vec3, 0),
+            ShaderAttribute::new(ShaderAttributeType::Vec4, 1),
+        ]);
+        let builder = || {
+            render
+                .build_pipeline(&shader)
+                .vertex_layout(&vertex_layout)
+                .bind_group_layout(&layout)
+                .topology(PrimitiveTopology::LineList)
+                .blended_target(format, Some(BlendState::ALPHA_BLENDING))
+        };
+        // Neither writes depth, so the lines never hide the scene
+        let (tested_pipeline, overlay_pipeline) = match depth {
+            Some(depth) => (
+                Some(
+                    builder()
+                        .depth(depth)
+                        .depth_compare(CompareFunction::LessEqual, false)
+                        .build(),
+                ),
+                builder()
+                    .depth(depth)
+                    .depth_compare(CompareFunction::Always, false)
+                    .build(),
+            ),
+            None => (None, builder().build()),
+        };
+
+        let uniforms = render.create_uniforms(&DebugUniforms::zeroed());
+        let bind_group = render
+            .create_bind_group_with(&layout, &[Binding::Buffer(&uniforms)]);
+        let capacity = 1024;
+
+        DebugDraw {
+            tested_pipeline,
+            overlay_pipeline,
+            uniforms,
+            bind_group,
+            vertices: render.create_dynamic_vertex_buffer(
+                (capacity * size_of::<DebugVertex>()) as u64,
+            ),
+            capacity,
+            lines: Vec::new(),
+            counts: (0, 0),
+            depth_test: true,
+            duration: 0.0,
+            encode_srgb: render.encodes_srgb(format),
+        }
+    }
+
+    /// Whether the shapes added from now on are hidden behind the scene,
+    /// or drawn over it. On by default
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test;
+    }
+
+    /// Seconds the shapes added from now on stay drawn for. Zero, the
+    /// default, draws them for a single frame
+    pub fn set_duration(&mut self, seconds: f32) {
+        self.duration = seconds.max(0.0);
+    }
+
+    /// Removes every shape, including the ones with time left
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: Color) {
+        let color = color.to_array();
+        self.lines.push(DebugLine {
+            vertices: [
+                DebugVertex {
+                    position: from.into(),
+                    color,
+                },
+                DebugVertex {
+                    position: to.into(),
+                    color,
+                },
+            ],
+            depth_test: self.depth_test,
+            remaining: self.duration,
+        });
+    }
+
+    /// Box aligned with the world axes
+    pub fn aabb(&mut self, min: Point3<f32>, max: Point3<f32>, color: Color) {
+        let corners = box_corners(|x, y, z| {
+            point3(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Box from -`half_extents` to `half_extents` in the space of
+    /// `transform`
+    pub fn oriented_box(
+        &mut self,
+        transform: Matrix4<f32>,
+        half_extents: Vector3<f32>,
+        color: Color,
+    ) {
+        let corners = box_corners(|x, y, z| {
+            let sign = |positive| if positive { 1.0 } else { -1.0 };
+            transform.transform_point(point3(
+                half_extents.x * sign(x),
+                half_extents.y * sign(y),
+                half_extents.z * sign(z),
+            ))
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// Circle around `normal`
+    pub fn circle(
+        &mut self,
+        center: Point3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: Color,
+    ) {
+        let points = circle_points(center, normal, radius);
+        for (i, from) in points.iter().enumerate() {
+            self.line(*from, points[(i + 1) % points.len()], color);
+        }
+    }
+
+    /// One circle in each of the planes of the world axes
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: Color) {
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+
+    /// Volume seen through `view_proj`, e.g. the one of another camera
+    pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: Color) {
+        if let Some(corners) = frustum_corners(view_proj) {
+            self.box_edges(&corners, color);
+        }
+    }
+
+    /// The X, Y and Z axes of `transform` in red, green and blue, `size`
+    /// units long before the transform
+    pub fn axes(&mut self, transform: Matrix4<f32>, size: f32) {
+        let origin = transform.transform_point(Point3::origin());
+        let axes = [
+            (Vector3::unit_x(), Color::new(1.0, 0.0, 0.0, 1.0)),
+            (Vector3::unit_y(), Color::new(0.0, 1.0, 0.0, 1.0)),
+            (Vector3::unit_z(), Color::new(0.0, 0.0, 1.0, 1.0)),
+        ];
+        for (axis, color) in axes {
+            let end = transform.transform_point(Point3::from_vec(axis * size));
+            self.line(origin, end, color);
+        }
+    }
+
+    /// Where `light` is and where it shines, in its own color: a small
+    /// sphere and one as large as the range of point lights, the cone of
+    /// spot lights, and a line towards the origin along the direction of
+    /// directional ones
+    pub fn light(&mut self, light: &Light) {
+        match *light {
+            Light::Directional {
+                direction, color, ..
+            } => {
+                let direction = direction.normalize();
+                let from = Point3::from_vec(-direction * 5.0);
+                self.sphere(from, 0.2, color);
+                self.line(from, Point3::origin(), color);
+            }
+            Light::Point {
+                position,
+                range,
+                color,
+                ..
+            } => {
+                self.sphere(position, 0.2, color);
+                self.sphere(position, range, color);
+            }
+            Light::Spot {
+                position,
+                direction,
+                range,
+                outer,
+                color,
+                ..
+            } => {
+                let direction = direction.normalize();
+                let center = position + direction * range;
+                let radius = range * Rad::from(outer).0.tan();
+                let rim = circle_points(center, direction, radius);
+
+                self.sphere(position, 0.2, color);
+                self.circle(center, direction, radius, color);
+                for point in rim.iter().step_by(CIRCLE_SEGMENTS / 4) {
+                    self.line(position, *point, color);
+                }
+            }
+        }
+    }
+
+    /// Uploads the shapes and the view of `camera`, then ages them by `dt`
+    /// seconds, dropping the ones whose time ran out. Call once per frame
+    /// before `draw`
+    pub fn prepare(&mut self, render: &Render, camera: &Camera, dt: f32) {
+        let uniforms = DebugUniforms {
+            view_proj: camera.view_proj().into(),
+            encode_srgb: self.encode_srgb as u32,
+            _padding: [0; 3],
+        };
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+
+        let depth_tested = self.tested_pipeline.is_some();
+        let (tested, overlay): (Vec<_>, Vec<_>) = self
+            .lines
+            .iter()
+            .partition(|line| line.depth_test && depth_tested);
+        let vertices = tested
+            .iter()
+            .chain(overlay.iter())
+            .flat_map(|line| line.vertices)
+            .collect::<Vec<_>>();
+        self.counts = (2 * tested.len() as u32, 2 * overlay.len() as u32);
+
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertices = render.create_dynamic_vertex_buffer(
+                (self.capacity * size_of::<DebugVertex>()) as u64,
+            );
+        }
+        if !vertices.is_empty() {
+            render.write_buffer(&self.vertices, cast_slice(&vertices));
+        }
+
+        for line in &mut self.lines {
+            line.remaining -= dt;
+        }
+        self.lines.retain(|line| line.remaining > 0.0);
+    }
+
+    /// Draws into the render pass being recorded, after the scene so the
+    /// depth tested lines can be hidden by it. Leaves a debug pipeline set
+    pub fn draw<'a>(
+        &'a self,
+        mut commands: CommandBuffer<'a>,
+    ) -> CommandBuffer<'a> {
+        let (tested, overlay) = self.counts;
+        if tested + overlay == 0 {
+            return commands;
+        }
+
+        if let (Some(pipeline), true) = (&self.tested_pipeline, tested > 0) {
+            commands = commands
+                .set_pipeline(pipeline)
+                .bind_resources(&self.bind_group)
+                .set_vertices(&self.vertices)
+                .draw_vertices(0..tested);
+        }
+        if overlay > 0 {
+            commands = commands
+                .set_pipeline(&self.overlay_pipeline)
+                .bind_resources(&self.bind_group)
+                .set_vertices(&self.vertices)
+                .draw_vertices(tested..tested + overlay);
+        }
+
+        commands
+    }
+
+    fn box_edges(&mut self, corners: &[Point3<f32>; 8], color: Color) {
+        for (from, to) in BOX_EDGES {
+            self.line(corners[from], corners[to], color);
+        }
+    }
+}
+
+/// Corners of a box, numbered as `BOX_EDGES` expects, from whether each
+/// one is on the positive side of x, y and z
+fn box_corners<F>(corner: F) -> [Point3<f32>; 8]
+where
+    F: Fn(bool, bool, bool) -> Point3<f32>,
+{
+    let mut corners = [Point3::origin(); 8];
+    for (i, point) in corners.iter_mut().enumerate() {
+        *point = corner(i & 1 != 0, i & 2 != 0, i & 4 != 0);
+    }
+
+    corners
+}
+
+/// World space corners of the clip space volume of `view_proj`, numbered
+/// as `BOX_EDGES` expects with the near plane first. `None` if it can't be
+/// inverted
+fn frustum_corners(view_proj: Matrix4<f32>) -> Option<[Point3<f32>; 8]> {
+    let inverse = view_proj.invert()?;
+
+    Some(box_corners(|x, y, z| {
+        let sign = |positive| if positive { 1.0 } else { -1.0 };
+        let corner = inverse
+            * Vector4::new(sign(x), sign(y), if z { 1.0 } else { 0.0 }, 1.0);
+        Point3::from_vec(corner.truncate() / corner.w)
+    }))
+}
+
+/// `CIRCLE_SEGMENTS` points evenly spaced on a circle around `normal`
+fn circle_points(
+    center: Point3<f32>,
+    normal: Vector3<f32>,
+    radius: f32,
+) -> Vec<Point3<f32>> {
+    let normal = normal.normalize();
+    // Any axis not too close to the normal gives a tangent
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = normal.cross(axis).normalize();
+    let v = normal.cross(u);
+
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_abs_diff_eq, vec3, Deg, MetricSpace};
+
+    use super::*;
+    use crate::camera::Projection;
+
+    #[test]
+    fn circle_around_normal() {
+        let center = point3(1.0, 2.0, 3.0);
+        let normal = vec3(1.0, 1.0, 0.0).normalize();
+        let points = circle_points(center, normal, 2.0);
+
+        assert_eq!(points.len(), CIRCLE_SEGMENTS);
+        for point in points {
+            assert_abs_diff_eq!(point.distance(center), 2.0, epsilon = 1e-5);
+            assert_abs_diff_eq!(
+                (point - center).dot(normal),
+                0.0,
+                epsilon = 1e-5
+            );
+        }
+    }
+
+    #[test]
+    fn frustum_matches_camera() {
+        let projection = Projection::Perspective {
+            fovy: Deg(60.0),
+            near: 0.5,
+            far: 20.0,
+        };
+        let mut camera = Camera::new(point3(1.0, 2.0, 3.0), projection, 1.5);
+        camera.look_at(point3(-4.0, 0.0, 1.0));
+
+        let corners = frustum_corners(camera.view_proj()).unwrap();
+        let expected = camera.frustum_corners(0.5, 20.0);
+        for corner in expected {
+            let closest = corners
+                .iter()
+                .map(|point| point.distance(corner))
+                .fold(f32::MAX, f32::min);
+            assert!(closest < 1e-3, "{:?} not a corner", corner);
+        }
+        // Near plane first
+        assert!(corners[0].distance(camera.position) < 1.0);
+        assert!(corners[7].distance(camera.position) > 10.0);
+    }
+}
@@ -0,0 +1,334 @@
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation,
+    BlendState, Face, ShaderStages, TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, Color, CommandBuffer, Framebuffer, Mesh,
+    Pipeline, Render, ShaderAttribute, ShaderAttributeType,
+};
+
+const ALBEDO_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const POSITION_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const MATERIAL_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+/// Light shining in every direction, reaching nothing beyond `radius`
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    pub radius: f32,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct LightInstance {
+    light: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct CameraUniforms {
+    view_proj: [[f32; 4]; 4],
+    position: [f32; 4],
+    ambient: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ObjectUniforms {
+    model: [[f32; 4]; 4],
+    albedo: [f32; 4],
+    material: [f32; 4],
+}
+
+/// Per-object transform and surface parameters written to the G-buffer
+pub struct DeferredObject {
+    _uniforms: Buffer,
+    bind_group: BindGroup,
+}
+
+/// Deferred shading path. Meshes are drawn once into a G-buffer holding
+/// albedo, normal, position and material parameters, then lights are
+/// accumulated into the output by only shading the pixels each light can
+/// reach, which keeps hundreds of small lights cheap
+pub struct DeferredRenderer {
+    gbuffer: Framebuffer,
+    camera: Buffer,
+    camera_group: BindGroup,
+    object_layout: BindGroupLayout,
+    gbuffer_layout: BindGroupLayout,
+    gbuffer_group: BindGroup,
+    geometry: Pipeline,
+    ambient: Pipeline,
+    lighting: Pipeline,
+    lights: Option<Buffer>,
+    light_count: u32,
+}
+
+impl DeferredRenderer {
+    pub fn new(
+        render: &Render,
+        output_format: TextureFormat,
+    ) -> DeferredRenderer {
+        let camera_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let object_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let gbuffer_layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Texture,
+                BindingLayout::Texture,
+                BindingLayout::Texture,
+                BindingLayout::Texture,
+            ],
+            ShaderStages::FRAGMENT,
+        );
+
+        let geometry_shader = render
+            .create_wgsl_shader(include_str!("../../shaders/gbuffer.wgsl"));
+        let geometry = render
+            .build_pipeline(&geometry_shader)
+            .vertex_layout(&Mesh::layout(render))
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&object_layout)
+            .target(ALBEDO_FORMAT)
+            .target(NORMAL_FORMAT)
+            .target(POSITION_FORMAT)
+            .target(MATERIAL_FORMAT)
+            .depth(DEPTH_FORMAT)
+            .build();
+
+        let lighting_source = concat!(
+            include_str!("../../shaders/fullscreen.wgsl"),
+            include_str!("../../shaders/deferred_lighting.wgsl"),
+        );
+        let ambient_shader = render.create_wgsl_shader_with_entries(
+            lighting_source,
+            "vs_main",
+            Some("fs_ambient"),
+        );
+        let ambient = render
+            .build_pipeline(&ambient_shader)
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&gbuffer_layout)
+            .target(output_format)
+            .build();
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let light_shader = render.create_wgsl_shader_with_entries(
+            lighting_source,
+            "vs_light",
+            Some("fs_light"),
+        );
+        let lighting = render
+            .build_pipeline(&light_shader)
+            .instance_layout(&render.create_shader_layout([
+                ShaderAttribute::new(ShaderAttributeType::Vec4, 0),
+                ShaderAttribute::new(ShaderAttributeType::Vec4, 1),
+            ]))
+            .bind_group_layout(&camera_layout)
+            .bind_group_layout(&gbuffer_layout)
+            // Light volumes are drawn from the inside, so a light still
+            // shades everything around the camera within its radius
+            .cull_mode(Face::Front)
+            .blended_target(
+                output_format,
+                Some(BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+            )
+            .build();
+
+        let camera = render.create_uniforms(&CameraUniforms {
+            view_proj: Matrix4::identity().into(),
+            position: [0.0, 0.0, 0.0, 1.0],
+            ambient: [0.0, 0.0, 0.0, 1.0],
+        });
+        let camera_group = render.create_bind_group_with(
+            &camera_layout,
+            &[Binding::Buffer(&camera)],
+        );
+        let gbuffer = DeferredRenderer::create_gbuffer(render);
+        let gbuffer_group = DeferredRenderer::create_gbuffer_group(
+            render,
+            &gbuffer_layout,
+            &gbuffer,
+        );
+
+        DeferredRenderer {
+            gbuffer,
+            camera,
+            camera_group,
+            object_layout,
+            gbuffer_layout,
+            gbuffer_group,
+            geometry,
+            ambient,
+            lighting,
+            lights: None,
+            light_count: 0,
+        }
+    }
+
+    fn create_gbuffer(render: &Render) -> Framebuffer {
+        let (width, height) = render.size();
+
+        render.create_framebuffer(
+            width,
+            height,
+            &[
+                ALBEDO_FORMAT,
+                NORMAL_FORMAT,
+                POSITION_FORMAT,
+                MATERIAL_FORMAT,
+            ],
+            Some(DEPTH_FORMAT),
+        )
+    }
+
+    fn create_gbuffer_group(
+        render: &Render,
+        layout: &BindGroupLayout,
+        gbuffer: &Framebuffer,
+    ) -> BindGroup {
+        let bindings = gbuffer
+            .get_textures()
+            .iter()
+            .map(Binding::Texture)
+            .collect::<Vec<_>>();
+
+        render.create_bind_group_with(layout, &bindings)
+    }
+
+    /// Recreates the G-buffer at the swapchain size. Call after
+    /// `Render::reconfigure`
+    pub fn resize(&mut self, render: &Render) {
+        self.gbuffer = DeferredRenderer::create_gbuffer(render);
+        self.gbuffer_group = DeferredRenderer::create_gbuffer_group(
+            render,
+            &self.gbuffer_layout,
+            &self.gbuffer,
+        );
+    }
+
+    pub fn set_camera(
+        &self,
+        render: &Render,
+        view_proj: Matrix4<f32>,
+        position: Point3<f32>,
+        ambient: Color,
+    ) {
+        let uniforms = CameraUniforms {
+            view_proj: view_proj.into(),
+            position: [position.x, position.y, position.z, 1.0],
+            ambient: ambient.to_array(),
+        };
+
+        render.write_buffer(&self.camera, bytes_of(&uniforms));
+    }
+
+    pub fn set_lights(&mut self, render: &Render, lights: &[PointLight]) {
+        let instances = lights
+            .iter()
+            .map(|light| LightInstance {
+                light: [
+                    light.position[0],
+                    light.position[1],
+                    light.position[2],
+                    light.radius,
+                ],
+                color: [
+                    light.color.r,
+                    light.color.g,
+                    light.color.b,
+                    light.intensity,
+                ],
+            })
+            .collect::<Vec<_>>();
+
+        self.light_count = instances.len() as u32;
+        self.lights = if instances.is_empty() {
+            None
+        } else {
+            Some(render.create_vertex_buffer(cast_slice(&instances)))
+        };
+    }
+
+    /// `roughness` goes from mirror-like (0) to fully diffuse (1)
+    pub fn create_object(
+        &self,
+        render: &Render,
+        model: Matrix4<f32>,
+        albedo: Color,
+        roughness: f32,
+        metallic: f32,
+    ) -> DeferredObject {
+        let data = ObjectUniforms {
+            model: model.into(),
+            albedo: albedo.to_array(),
+            material: [roughness, metallic, 0.0, 0.0],
+        };
+        let uniforms = render.create_uniforms(&data);
+        let bind_group = render.create_bind_group_with(
+            &self.object_layout,
+            &[Binding::Buffer(&uniforms)],
+        );
+
+        DeferredObject {
+            _uniforms: uniforms,
+            bind_group,
+        }
+    }
+
+    /// Records the geometry and lighting passes. `output` must have the
+    /// format given to `new` and no depth attachment
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        objects: &[(&'a Mesh, &'a DeferredObject)],
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let mut commands = commands
+            .label("gbuffer")
+            .configure_draw(&self.geometry, &self.gbuffer)
+            .bind_resources(&self.camera_group);
+        for (mesh, object) in objects {
+            commands =
+                mesh.draw(commands.bind_resources_at(1, &object.bind_group));
+        }
+
+        commands = commands
+            .label("lighting")
+            .configure_draw(&self.ambient, output)
+            .bind_resources(&self.camera_group)
+            .bind_resources_at(1, &self.gbuffer_group)
+            .draw_vertices(0..3);
+
+        if let Some(ref lights) = self.lights {
+            commands = commands
+                .set_pipeline(&self.lighting)
+                .bind_resources(&self.camera_group)
+                .bind_resources_at(1, &self.gbuffer_group)
+                .set_vertices(lights)
+                .draw_instanced(0..36, 0..self.light_count);
+        }
+
+        commands
+    }
+}
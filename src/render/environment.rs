@@ -0,0 +1,497 @@
+use std::{fs, path::Path};
+
+use bytemuck::cast_slice;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, FilterMode, ShaderStages,
+    TextureFormat, TextureViewDescriptor, TextureViewDimension,
+};
+
+use super::{
+    color::srgb_to_linear, Binding, BindingLayout, Buffer, ComputePipeline,
+    Render, Sampler, Texture,
+};
+
+const CUBE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const ENVIRONMENT_SIZE: u32 = 256;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Mip levels of the prefiltered map, from smooth to fully rough
+const PREFILTERED_LEVELS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 128;
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Maps of image-based lighting generated from a cubemap of the
+/// surroundings: the diffuse irradiance reaching each direction, the
+/// environment blurred for increasing roughnesses along the mip levels of a
+/// cubemap, and the lookup table of the split-sum specular BRDF
+pub struct Environment {
+    cubemap: Texture,
+    irradiance: Texture,
+    prefiltered: Texture,
+    brdf_lut: Texture,
+    sampler: Sampler,
+}
+
+impl Environment {
+    /// Convolves an equirectangular image of `width` x `height` linear RGBA
+    /// texels on the GPU
+    pub fn from_equirect(
+        render: &Render,
+        width: u32,
+        height: u32,
+        texels: &[f32],
+    ) -> Environment {
+        let cubemap = equirect_to_cubemap(
+            render,
+            width,
+            height,
+            texels,
+            ENVIRONMENT_SIZE,
+        );
+
+        Environment::from_cubemap(render, cubemap)
+    }
+
+    /// Convolves `cubemap` on the GPU, keeping it for skyboxes
+    pub fn from_cubemap(render: &Render, cubemap: Texture) -> Environment {
+        let irradiance = render.create_cubemap(IRRADIANCE_SIZE, 1, CUBE_FORMAT);
+        let prefiltered = render.create_cubemap(
+            PREFILTERED_SIZE,
+            PREFILTERED_LEVELS,
+            CUBE_FORMAT,
+        );
+        let brdf_lut = render.create_storage_texture(
+            BRDF_LUT_SIZE,
+            BRDF_LUT_SIZE,
+            CUBE_FORMAT,
+        );
+        let sampler =
+            render.create_sampler(FilterMode::Linear, AddressMode::ClampToEdge);
+
+        let cube_output = BindingLayout::StorageTexture {
+            format: CUBE_FORMAT,
+            dimension: TextureViewDimension::D2Array,
+        };
+        let irradiance_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Cubemap, BindingLayout::Sampler, cube_output],
+            ShaderStages::COMPUTE,
+        );
+        let prefilter_layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Cubemap,
+                BindingLayout::Sampler,
+                cube_output,
+            ],
+            ShaderStages::COMPUTE,
+        );
+        let lut_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::StorageTexture {
+                format: CUBE_FORMAT,
+                dimension: TextureViewDimension::D2,
+            }],
+            ShaderStages::COMPUTE,
+        );
+
+        let irradiance_pipeline = ibl_pipeline(
+            render,
+            include_str!("../../shaders/irradiance.wgsl"),
+            &irradiance_layout,
+        );
+        let prefilter_pipeline = ibl_pipeline(
+            render,
+            include_str!("../../shaders/prefilter.wgsl"),
+            &prefilter_layout,
+        );
+        let lut_pipeline = ibl_pipeline(
+            render,
+            include_str!("../../shaders/brdf_lut.wgsl"),
+            &lut_layout,
+        );
+
+        let irradiance_output = mip_view(&irradiance, 0);
+        let irradiance_group = render.create_bind_group_with(
+            &irradiance_layout,
+            &[
+                Binding::Texture(&cubemap),
+                Binding::Sampler(&sampler),
+                Binding::TextureView(&irradiance_output),
+            ],
+        );
+        let prefilter_levels = (0..PREFILTERED_LEVELS)
+            .map(|level| {
+                let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+                let params =
+                    render.create_uniforms(&[roughness, 0.0, 0.0, 0.0]);
+                let output = mip_view(&prefiltered, level);
+                (params, output)
+            })
+            .collect::<Vec<(Buffer, _)>>();
+        let prefilter_groups = prefilter_levels
+            .iter()
+            .map(|(params, output)| {
+                render.create_bind_group_with(
+                    &prefilter_layout,
+                    &[
+                        Binding::Buffer(params),
+                        Binding::Texture(&cubemap),
+                        Binding::Sampler(&sampler),
+                        Binding::TextureView(output),
+                    ],
+                )
+            })
+            .collect::<Vec<BindGroup>>();
+        let lut_group = render.create_bind_group_with(
+            &lut_layout,
+            &[Binding::Texture(&brdf_lut)],
+        );
+
+        let mut commands =
+            render.start_commands().label("environment").dispatch(
+                &irradiance_pipeline,
+                &[&irradiance_group],
+                workgroups(IRRADIANCE_SIZE),
+            );
+        for (level, group) in prefilter_groups.iter().enumerate() {
+            commands = commands.dispatch(
+                &prefilter_pipeline,
+                &[group],
+                workgroups(PREFILTERED_SIZE >> level),
+            );
+        }
+        let lut_workgroups = workgroups(BRDF_LUT_SIZE);
+        commands
+            .dispatch(
+                &lut_pipeline,
+                &[&lut_group],
+                [lut_workgroups[0], lut_workgroups[1], 1],
+            )
+            .submit(&render.queue);
+
+        Environment {
+            cubemap,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+            sampler,
+        }
+    }
+
+    /// Loads an equirectangular image, see `read_equirect`
+    pub fn load<P: AsRef<Path>>(
+        render: &Render,
+        path: P,
+    ) -> Result<Environment, String> {
+        let (width, height, texels) = read_equirect(path.as_ref())?;
+
+        Ok(Environment::from_equirect(render, width, height, &texels))
+    }
+
+    /// Cubemap the maps were generated from
+    pub fn cubemap(&self) -> &Texture {
+        &self.cubemap
+    }
+
+    /// Bind group layout entries of the maps, in the order of `bindings`
+    pub fn binding_layouts() -> [BindingLayout; 4] {
+        [
+            BindingLayout::Cubemap,
+            BindingLayout::Cubemap,
+            BindingLayout::Texture,
+            BindingLayout::Sampler,
+        ]
+    }
+
+    /// Irradiance map, prefiltered map and BRDF lookup table, then a
+    /// sampler clamping all three to their edges
+    pub fn bindings(&self) -> [Binding<'_>; 4] {
+        [
+            Binding::Texture(&self.irradiance),
+            Binding::Texture(&self.prefiltered),
+            Binding::Texture(&self.brdf_lut),
+            Binding::Sampler(&self.sampler),
+        ]
+    }
+}
+
+/// Compute pipeline running `cs_main` of `source`, appended to ibl.wgsl
+fn ibl_pipeline(
+    render: &Render,
+    source: &str,
+    layout: &BindGroupLayout,
+) -> ComputePipeline {
+    let shader = render.create_wgsl_shader_with_entries(
+        &[include_str!("../../shaders/ibl.wgsl"), source].concat(),
+        "cs_main",
+        None,
+    );
+
+    render.create_compute_pipeline(&shader, "cs_main", &[layout])
+}
+
+/// Projects an equirectangular image of `width` x `height` linear RGBA
+/// texels onto a cubemap with faces of `size` on the GPU
+pub(super) fn equirect_to_cubemap(
+    render: &Render,
+    width: u32,
+    height: u32,
+    texels: &[f32],
+    size: u32,
+) -> Texture {
+    let equirect = render.create_texture_with_format(
+        width,
+        height,
+        cast_slice(texels),
+        TextureFormat::Rgba32Float,
+    );
+    let cubemap = render.create_cubemap(size, 1, CUBE_FORMAT);
+
+    let layout = render.create_bind_group_layout_with(
+        &[
+            BindingLayout::UnfilterableTexture,
+            BindingLayout::StorageTexture {
+                format: CUBE_FORMAT,
+                dimension: TextureViewDimension::D2Array,
+            },
+        ],
+        ShaderStages::COMPUTE,
+    );
+    let pipeline = ibl_pipeline(
+        render,
+        include_str!("../../shaders/equirect_to_cube.wgsl"),
+        &layout,
+    );
+    let output = mip_view(&cubemap, 0);
+    let group = render.create_bind_group_with(
+        &layout,
+        &[Binding::Texture(&equirect), Binding::TextureView(&output)],
+    );
+
+    render
+        .start_commands()
+        .dispatch(&pipeline, &[&group], workgroups(size))
+        .submit(&render.queue);
+
+    cubemap
+}
+
+/// Reads an equirectangular image into linear RGBA texels: Radiance HDR
+/// (`.hdr`) as it is, anything else through the image crate, decoding sRGB
+pub(super) fn read_equirect(
+    path: &Path,
+) -> Result<(u32, u32, Vec<f32>), String> {
+    let is_hdr = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+    let image = if is_hdr {
+        fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| decode_hdr(&bytes))
+    } else {
+        image::open(path)
+            .map(|image| {
+                let image = image.to_rgba8();
+                let texels = image
+                    .pixels()
+                    .flat_map(|pixel| {
+                        let [r, g, b, a] = pixel.0;
+                        [
+                            srgb_to_linear(r as f32 / 255.0),
+                            srgb_to_linear(g as f32 / 255.0),
+                            srgb_to_linear(b as f32 / 255.0),
+                            a as f32 / 255.0,
+                        ]
+                    })
+                    .collect();
+                (image.width(), image.height(), texels)
+            })
+            .map_err(|err| err.to_string())
+    };
+
+    image.map_err(|err| format!("Failed to load {}: {}", path.display(), err))
+}
+
+/// Workgroups covering the six faces of a cubemap of `size`
+fn workgroups(size: u32) -> [u32; 3] {
+    let count = size.div_ceil(WORKGROUP_SIZE);
+
+    [count, count, 6]
+}
+
+/// Every face of one mip level of a cubemap, for compute shaders to write
+fn mip_view(cubemap: &Texture, level: u32) -> wgpu::TextureView {
+    cubemap.get_texture().create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        base_mip_level: level,
+        mip_level_count: std::num::NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+/// Decodes a Radiance RGBE image into linear RGBA texels, top row first.
+/// Only the usual `-Y height +X width` orientation is supported
+fn decode_hdr(bytes: &[u8]) -> Result<(u32, u32, Vec<f32>), String> {
+    if !bytes.starts_with(b"#?") {
+        return Err("Not a Radiance HDR image".to_string());
+    }
+
+    // Header lines up to an empty one, then the resolution line
+    let mut lines = bytes.split(|byte| *byte == b'\n');
+    let mut offset = 0;
+    for line in lines.by_ref() {
+        offset += line.len() + 1;
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+            return Err("Only RGBE images are supported".to_string());
+        }
+        if line.is_empty() {
+            break;
+        }
+    }
+    let resolution = lines.next().ok_or("Missing image resolution")?;
+    offset += resolution.len() + 1;
+    let resolution = String::from_utf8_lossy(resolution);
+    let (height, width) =
+        match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (
+                height.parse::<u32>().map_err(|err| err.to_string())?,
+                width.parse::<u32>().map_err(|err| err.to_string())?,
+            ),
+            _ => {
+                return Err(format!(
+                    "Unsupported image orientation '{}'",
+                    resolution
+                ))
+            }
+        };
+
+    let mut data = bytes.get(offset..).unwrap_or_default();
+    let mut texels = Vec::with_capacity((width * height * 4) as usize);
+    let mut scanline = vec![0_u8; width as usize * 4];
+    for _ in 0..height {
+        data = read_scanline(data, &mut scanline)?;
+        for rgbe in scanline.chunks_exact(4) {
+            let scale = if rgbe[3] == 0 {
+                0.0
+            } else {
+                2.0_f32.powi(rgbe[3] as i32 - 136)
+            };
+            texels.extend_from_slice(&[
+                rgbe[0] as f32 * scale,
+                rgbe[1] as f32 * scale,
+                rgbe[2] as f32 * scale,
+                1.0,
+            ]);
+        }
+    }
+
+    Ok((width, height, texels))
+}
+
+/// Reads one scanline of RGBE texels into `scanline`, either run-length
+/// encoded one channel after the other or stored flat. Returns the rest of
+/// `data`
+fn read_scanline<'a>(
+    data: &'a [u8],
+    scanline: &mut [u8],
+) -> Result<&'a [u8], String> {
+    let truncated = || "Truncated image data".to_string();
+    let width = scanline.len() / 4;
+    let encoded = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && ((data[2] as usize) << 8 | data[3] as usize) == width;
+
+    if !encoded {
+        let flat = data.get(..scanline.len()).ok_or_else(truncated)?;
+        scanline.copy_from_slice(flat);
+        return Ok(&data[scanline.len()..]);
+    }
+
+    let mut data = &data[4..];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let (&count, rest) = data.split_first().ok_or_else(truncated)?;
+            let (run, count) = if count > 128 {
+                (true, count as usize - 128)
+            } else {
+                (false, count as usize)
+            };
+            if count == 0 || x + count > width {
+                return Err("Invalid run length".to_string());
+            }
+
+            let used = if run { 1 } else { count };
+            let values = rest.get(..used).ok_or_else(truncated)?;
+            for i in 0..count {
+                scanline[(x + i) * 4 + channel] =
+                    if run { values[0] } else { values[i] };
+            }
+            x += count;
+            data = &rest[used..];
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+
+    #[test]
+    fn flat_hdr() {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(b"-Y 1 +X 2\n");
+        // 1.0 and 0.5 share the exponent 129, 2.0 needs 130
+        bytes.extend_from_slice(&[128, 64, 0, 129, 128, 0, 0, 130]);
+
+        let (width, height, texels) = decode_hdr(&bytes).unwrap();
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(texels, [1.0, 0.5, 0.0, 1.0, 2.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn run_length_encoded_hdr() {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(b"-Y 1 +X 8\n");
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        // Red: a run of 8, green: 8 literals, blue: two runs of 4,
+        // exponent: a run of 8
+        bytes.extend_from_slice(&[128 + 8, 128]);
+        bytes.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        bytes.extend_from_slice(&[128 + 4, 0, 128 + 4, 128]);
+        bytes.extend_from_slice(&[128 + 8, 129]);
+
+        let (_, _, texels) = decode_hdr(&bytes).unwrap();
+        let texel = |x: usize| &texels[x * 4..x * 4 + 3];
+        assert_eq!(texel(0), [1.0, 0.0, 0.0]);
+        assert_eq!(texel(2), [1.0, 0.25, 0.0]);
+        assert_eq!(texel(7), [1.0, 0.875, 1.0]);
+    }
+
+    #[test]
+    fn sample_sky() {
+        let (width, height, texels) =
+            read_equirect(Path::new("assets/sky.hdr")).unwrap();
+
+        assert_eq!((width, height), (64, 32));
+        assert_eq!(texels.len(), 64 * 32 * 4);
+        // Brighter above the horizon than below
+        let luminance = |y: usize| texels[y * 64 * 4 + 1];
+        assert!(luminance(4) > luminance(28));
+    }
+
+    #[test]
+    fn truncated_hdr() {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(b"-Y 2 +X 2\n");
+        bytes.extend_from_slice(&[128, 64, 0, 129]);
+
+        assert!(decode_hdr(&bytes).is_err());
+        assert!(decode_hdr(b"P6\n").is_err());
+    }
+}
@@ -1,15 +1,95 @@
 use wgpu::TextureView;
 
+use super::Texture;
+
 pub struct Framebuffer {
-    pub target: TextureView,
+    /// Color targets viewing textures the framebuffer doesn't own, e.g. the
+    /// swapchain image
+    views: Vec<TextureView>,
+    colors: Vec<Texture>,
+    depth: Option<Texture>,
+    /// Depth target owned by another texture, e.g. one layer of an array
+    depth_view: Option<TextureView>,
 }
 
 impl<'a> Framebuffer {
     pub fn new(target: TextureView) -> Framebuffer {
-        Framebuffer { target }
+        Framebuffer {
+            views: vec![target],
+            colors: Vec::new(),
+            depth: None,
+            depth_view: None,
+        }
+    }
+
+    /// Framebuffer rendering into `colors`, in the order of the fragment
+    /// shader outputs, and optionally testing against `depth`. Every
+    /// attachment can be sampled by later passes
+    pub fn offscreen(
+        colors: Vec<Texture>,
+        depth: Option<Texture>,
+    ) -> Framebuffer {
+        Framebuffer {
+            views: Vec::new(),
+            colors,
+            depth,
+            depth_view: None,
+        }
+    }
+
+    /// Framebuffer only writing depth into `view`, for depth-only passes
+    /// such as shadow maps
+    pub fn depth_only(view: TextureView) -> Framebuffer {
+        Framebuffer {
+            views: Vec::new(),
+            colors: Vec::new(),
+            depth: None,
+            depth_view: Some(view),
+        }
+    }
+
+    /// Framebuffer rendering into `colors` and testing against `depth`,
+    /// all viewing textures owned elsewhere, see `Render::attach_depth`
+    pub fn with_views(
+        colors: Vec<TextureView>,
+        depth: TextureView,
+    ) -> Framebuffer {
+        Framebuffer {
+            views: colors,
+            colors: Vec::new(),
+            depth: None,
+            depth_view: Some(depth),
+        }
+    }
+
+    pub fn get_targets(&'a self) -> Vec<&'a TextureView> {
+        if self.views.is_empty() {
+            self.colors.iter().map(|color| color.get_view()).collect()
+        } else {
+            self.views.iter().collect()
+        }
+    }
+
+    /// First color attachment of an offscreen framebuffer. `None` for the
+    /// swapchain
+    pub fn get_texture(&'a self) -> Option<&'a Texture> {
+        self.colors.first()
+    }
+
+    /// Color attachments of an offscreen framebuffer, in the order they
+    /// were created
+    pub fn get_textures(&'a self) -> &'a [Texture] {
+        &self.colors
+    }
+
+    pub fn get_depth(&'a self) -> Option<&'a Texture> {
+        self.depth.as_ref()
     }
 
-    pub fn get_target(&'a self) -> &'a TextureView {
-        &self.target
+    /// View the depth attachment is rendered through, if any
+    pub fn get_depth_view(&'a self) -> Option<&'a TextureView> {
+        self.get_depth()
+            .map(|depth| depth.get_view())
+            .or(self.depth_view.as_ref())
     }
 }
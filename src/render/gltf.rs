@@ -0,0 +1,1349 @@
+use std::{fs, path::Path, sync::Arc};
+
+use cgmath::{InnerSpace, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3};
+use serde::Deserialize;
+
+use super::{
+    mesh::{compute_normals, MeshVertex},
+    AnimationChannel, AnimationClip, Color, Interpolation, Joint, JointPose,
+    Keyframes, Material, Mesh, Model, ModelNode, MorphAnimation, MorphTarget,
+    PbrMaterial, Render, Skeleton, SkinVertex, TextureMap,
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON: u32 = 0x4e4f534a;
+const GLB_BIN: u32 = 0x004e4942;
+
+const TRIANGLES: u32 = 4;
+
+// The subset of the glTF 2.0 JSON schema the loader understands. The
+// `gltf` crate would bring image 0.25 next to the 0.23 decoding every
+// other texture, and its readers would still need converting to `Model`
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfDocument {
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<GltfScene>,
+    #[serde(default)]
+    nodes: Vec<GltfNode>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<GltfAccessor>,
+    #[serde(default)]
+    buffer_views: Vec<GltfBufferView>,
+    #[serde(default)]
+    buffers: Vec<GltfBuffer>,
+    #[serde(default)]
+    materials: Vec<GltfMaterial>,
+    #[serde(default)]
+    textures: Vec<GltfTexture>,
+    #[serde(default)]
+    images: Vec<GltfImage>,
+    #[serde(default)]
+    skins: Vec<GltfSkin>,
+    #[serde(default)]
+    animations: Vec<GltfAnimation>,
+}
+
+#[derive(Deserialize)]
+struct GltfScene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct GltfNode {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    /// Replaces the default weights of the mesh's morph targets
+    weights: Option<Vec<f32>>,
+    /// Column-major, replaces the other three when given
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    /// x, y, z, w
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    primitives: Vec<GltfPrimitive>,
+    /// Default weight of each morph target
+    #[serde(default)]
+    weights: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GltfPrimitive {
+    attributes: GltfAttributes,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: Option<u32>,
+    #[serde(default)]
+    targets: Vec<GltfMorphTarget>,
+}
+
+#[derive(Deserialize)]
+struct GltfAttributes {
+    #[serde(rename = "POSITION")]
+    position: usize,
+    #[serde(rename = "NORMAL")]
+    normal: Option<usize>,
+    #[serde(rename = "TEXCOORD_0")]
+    uv: Option<usize>,
+    #[serde(rename = "JOINTS_0")]
+    joints: Option<usize>,
+    #[serde(rename = "WEIGHTS_0")]
+    weights: Option<usize>,
+}
+
+/// Deltas of the attributes of a primitive
+#[derive(Deserialize)]
+struct GltfMorphTarget {
+    #[serde(rename = "POSITION")]
+    position: Option<usize>,
+    #[serde(rename = "NORMAL")]
+    normal: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfAccessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfSkin {
+    /// Nodes moved by the joints, in the order of the joint indices of the
+    /// vertices
+    joints: Vec<usize>,
+    inverse_bind_matrices: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct GltfAnimation {
+    #[serde(default)]
+    name: String,
+    channels: Vec<GltfChannel>,
+    samplers: Vec<GltfSampler>,
+}
+
+#[derive(Deserialize)]
+struct GltfChannel {
+    sampler: usize,
+    target: GltfTarget,
+}
+
+#[derive(Deserialize)]
+struct GltfTarget {
+    node: Option<usize>,
+    /// translation, rotation, scale or weights
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct GltfSampler {
+    input: usize,
+    output: usize,
+    #[serde(default = "GltfSampler::default_interpolation")]
+    interpolation: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfBufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct GltfBuffer {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfMaterial {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    pbr_metallic_roughness: GltfPbr,
+    #[serde(default)]
+    emissive_factor: [f32; 3],
+    normal_texture: Option<GltfTextureRef>,
+    occlusion_texture: Option<GltfTextureRef>,
+    emissive_texture: Option<GltfTextureRef>,
+    #[serde(default = "GltfMaterial::default_alpha_mode")]
+    alpha_mode: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfPbr {
+    base_color_factor: [f32; 4],
+    base_color_texture: Option<GltfTextureRef>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    metallic_roughness_texture: Option<GltfTextureRef>,
+}
+
+#[derive(Deserialize)]
+struct GltfTextureRef {
+    index: usize,
+    /// Only read for normal textures
+    #[serde(default = "GltfTextureRef::one")]
+    scale: f32,
+    /// Only read for occlusion textures
+    #[serde(default = "GltfTextureRef::one")]
+    strength: f32,
+}
+
+#[derive(Deserialize)]
+struct GltfTexture {
+    source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfImage {
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+impl Default for GltfPbr {
+    fn default() -> Self {
+        GltfPbr {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+impl GltfTextureRef {
+    fn one() -> f32 {
+        1.0
+    }
+
+    fn map(&self, maps: &[Option<TextureMap>]) -> Option<TextureMap> {
+        maps.get(self.index).cloned().flatten()
+    }
+}
+
+impl GltfMaterial {
+    fn default_alpha_mode() -> String {
+        "OPAQUE".to_string()
+    }
+
+    fn opacity(&self) -> f32 {
+        if self.alpha_mode == "BLEND" {
+            self.pbr_metallic_roughness.base_color_factor[3]
+        } else {
+            1.0
+        }
+    }
+
+    /// Approximates the metallic-roughness parameters with Blinn-Phong
+    /// ones: metals tint their highlights and lose their diffuse color,
+    /// rough surfaces get wide and dim highlights
+    fn to_material(&self, maps: &[Option<TextureMap>]) -> Material {
+        let pbr = &self.pbr_metallic_roughness;
+        let [r, g, b, _] = pbr.base_color_factor;
+        let metallic = pbr.metallic_factor.clamp(0.0, 1.0);
+        let roughness = pbr.roughness_factor.clamp(0.05, 1.0);
+        let specular = |channel: f32| 0.04 + (channel - 0.04) * metallic;
+        let [er, eg, eb] = self.emissive_factor;
+
+        Material {
+            name: self.name.clone(),
+            diffuse: Color::new(
+                r * (1.0 - metallic),
+                g * (1.0 - metallic),
+                b * (1.0 - metallic),
+                1.0,
+            ),
+            specular: Color::new(specular(r), specular(g), specular(b), 1.0),
+            shininess: (2.0 / roughness.powi(4) - 2.0).clamp(1.0, 1000.0),
+            opacity: self.opacity(),
+            emissive: Color::new(er, eg, eb, 1.0),
+            diffuse_map: pbr
+                .base_color_texture
+                .as_ref()
+                .and_then(|texture| texture.map(maps)),
+            ..Default::default()
+        }
+    }
+
+    fn to_pbr_material(&self, maps: &[Option<TextureMap>]) -> PbrMaterial {
+        let pbr = &self.pbr_metallic_roughness;
+        let [r, g, b, _] = pbr.base_color_factor;
+        let [er, eg, eb] = self.emissive_factor;
+        let map = |texture: &Option<GltfTextureRef>| {
+            texture.as_ref().and_then(|texture| texture.map(maps))
+        };
+
+        PbrMaterial {
+            name: self.name.clone(),
+            base_color: Color::new(r, g, b, self.opacity()),
+            metallic: pbr.metallic_factor,
+            roughness: pbr.roughness_factor,
+            emissive: Color::new(er, eg, eb, 1.0),
+            base_color_map: map(&pbr.base_color_texture),
+            metallic_roughness_map: map(&pbr.metallic_roughness_texture),
+            normal_map: map(&self.normal_texture),
+            normal_scale: self
+                .normal_texture
+                .as_ref()
+                .map_or(1.0, |texture| texture.scale),
+            occlusion_map: map(&self.occlusion_texture),
+            occlusion_strength: self
+                .occlusion_texture
+                .as_ref()
+                .map_or(1.0, |texture| texture.strength),
+            emissive_map: map(&self.emissive_texture),
+        }
+    }
+}
+
+/// One primitive of a glTF mesh, ready to upload
+struct MeshData {
+    vertices: Vec<MeshVertex>,
+    indices: Vec<u16>,
+    material: Option<usize>,
+    skin: Option<Vec<SkinVertex>>,
+    targets: Vec<MorphTarget>,
+}
+
+/// Everything `load_gltf` uploads, read on the CPU
+struct GltfData {
+    meshes: Vec<MeshData>,
+    materials: Vec<Material>,
+    pbr_materials: Vec<PbrMaterial>,
+    nodes: Vec<ModelNode>,
+    skeletons: Vec<Skeleton>,
+    animations: Vec<AnimationClip>,
+    morph_animations: Vec<MorphAnimation>,
+}
+
+/// Loads the default scene of a glTF 2.0 file, either JSON (`.gltf`) or
+/// binary (`.glb`). Each primitive becomes a `Mesh`. Metallic-roughness
+/// materials are kept as they are for PBR shading and approximated with
+/// Blinn-Phong ones. Each skin becomes a `Skeleton`, and each animation an
+/// `AnimationClip` per skeleton it moves and a `MorphAnimation` per node
+/// whose morph target weights it changes
+pub fn load_gltf<P: AsRef<Path>>(
+    render: &Render,
+    path: P,
+) -> Result<Model, String> {
+    let path = path.as_ref();
+    let data = read_gltf(path)
+        .map_err(|err| format!("Failed to load {}: {}", path.display(), err))?;
+
+    Ok(Model {
+        meshes: data
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let gpu_mesh = Mesh::new(render, &mesh.vertices, &mesh.indices)
+                    .with_material(mesh.material)
+                    .with_morph_targets(render, &mesh.targets);
+                match mesh.skin {
+                    Some(ref skin) => gpu_mesh.with_skin(render, skin),
+                    None => gpu_mesh,
+                }
+            })
+            .collect(),
+        materials: data.materials,
+        pbr_materials: data.pbr_materials,
+        nodes: data.nodes,
+        skeletons: data.skeletons,
+        animations: data.animations,
+        morph_animations: data.morph_animations,
+    })
+}
+
+fn read_gltf(path: &Path) -> Result<GltfData, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let (json, mut bin) = if bytes.starts_with(GLB_MAGIC) {
+        let (json, bin) = split_glb(&bytes)?;
+        (json, bin.map(|bin| bin.to_vec()))
+    } else {
+        (&bytes[..], None)
+    };
+    let document: GltfDocument =
+        serde_json::from_slice(json).map_err(|err| err.to_string())?;
+
+    let buffers = document
+        .buffers
+        .iter()
+        .map(|buffer| match buffer.uri {
+            Some(ref uri) => read_uri(uri, dir),
+            // Only the first buffer of a GLB file may live in its BIN chunk
+            None => bin.take().ok_or_else(|| "Buffer without data".to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let reader = Reader {
+        document: &document,
+        buffers: &buffers,
+    };
+
+    let images = document
+        .images
+        .iter()
+        .map(|image| reader.image(image, dir))
+        .collect::<Result<Vec<_>, _>>()?;
+    let maps = document
+        .textures
+        .iter()
+        .map(|texture| {
+            texture
+                .source
+                .and_then(|source| images.get(source).cloned())
+        })
+        .collect::<Vec<_>>();
+    let materials = document
+        .materials
+        .iter()
+        .map(|material| material.to_material(&maps))
+        .collect();
+    let pbr_materials = document
+        .materials
+        .iter()
+        .map(|material| material.to_pbr_material(&maps))
+        .collect();
+
+    // Every primitive is a mesh of its own
+    let mut meshes = Vec::new();
+    let mut primitives = Vec::new();
+    for mesh in document.meshes.iter() {
+        let first = meshes.len();
+        for primitive in mesh.primitives.iter() {
+            meshes.push(reader.primitive(primitive)?);
+        }
+        primitives.push((first..meshes.len()).collect::<Vec<_>>());
+    }
+
+    let roots = match document.scene.or(document.scenes.first().map(|_| 0)) {
+        Some(scene) => document
+            .scenes
+            .get(scene)
+            .ok_or(format!("Missing scene {}", scene))?
+            .nodes
+            .clone(),
+        // Without scenes, every node that isn't a child is a root
+        None => (0..document.nodes.len())
+            .filter(|&i| {
+                !document.nodes.iter().any(|node| node.children.contains(&i))
+            })
+            .collect(),
+    };
+
+    let mut nodes = Vec::new();
+    // Index into `nodes` of each node of the document in the scene
+    let mut node_indices = vec![None; document.nodes.len()];
+    let mut stack = roots
+        .into_iter()
+        .rev()
+        .map(|root| (root, None))
+        .collect::<Vec<_>>();
+    while let Some((i, parent)) = stack.pop() {
+        let node =
+            document.nodes.get(i).ok_or(format!("Missing node {}", i))?;
+        if nodes.len() > document.nodes.len() {
+            return Err("The node hierarchy has a cycle".to_string());
+        }
+
+        let (translation, rotation, scale) = node.transform();
+        let meshes = match node.mesh {
+            Some(mesh) => primitives
+                .get(mesh)
+                .ok_or(format!("Missing mesh {}", mesh))?
+                .clone(),
+            None => Vec::new(),
+        };
+        let weights = node.weights.clone().unwrap_or_else(|| {
+            node.mesh
+                .and_then(|mesh| document.meshes.get(mesh))
+                .map_or(Vec::new(), |mesh| mesh.weights.clone())
+        });
+        if let Some(skin) = node.skin.filter(|&s| s >= document.skins.len()) {
+            return Err(format!("Missing skin {}", skin));
+        }
+        nodes.push(ModelNode {
+            name: node.name.clone(),
+            parent,
+            translation,
+            rotation,
+            scale,
+            meshes,
+            skin: node.skin,
+            weights,
+        });
+
+        let index = nodes.len() - 1;
+        node_indices[i] = Some(index);
+        stack.extend(node.children.iter().rev().map(|&j| (j, Some(index))));
+    }
+
+    let hierarchy = Hierarchy::new(&document)?;
+    let skeletons = document
+        .skins
+        .iter()
+        .enumerate()
+        .map(|(i, skin)| reader.skeleton(i, skin, &hierarchy))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut animations = Vec::new();
+    let mut morph_animations = Vec::new();
+    for animation in document.animations.iter() {
+        for (i, skin) in document.skins.iter().enumerate() {
+            if let Some(clip) = reader.animation(animation, i, skin)? {
+                animations.push(clip);
+            }
+        }
+        morph_animations
+            .extend(reader.morph_animations(animation, &node_indices)?);
+    }
+
+    Ok(GltfData {
+        meshes,
+        materials,
+        pbr_materials,
+        nodes,
+        skeletons,
+        animations,
+        morph_animations,
+    })
+}
+
+impl GltfSampler {
+    fn default_interpolation() -> String {
+        "LINEAR".to_string()
+    }
+}
+
+impl GltfNode {
+    fn pose(&self) -> JointPose {
+        let (translation, rotation, scale) = self.transform();
+        JointPose {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn transform(&self) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+        if let Some(matrix) = self.matrix {
+            return decompose(&matrix);
+        }
+
+        let [tx, ty, tz] = self.translation.unwrap_or([0.0; 3]);
+        let [x, y, z, w] = self.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let [sx, sy, sz] = self.scale.unwrap_or([1.0; 3]);
+
+        (
+            Vector3::new(tx, ty, tz),
+            Quaternion::new(w, x, y, z),
+            Vector3::new(sx, sy, sz),
+        )
+    }
+}
+
+/// Splits a column-major matrix without shear into translation, rotation
+/// and scale
+fn decompose(
+    matrix: &[f32; 16],
+) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+    let column = |i: usize| {
+        Vector3::new(matrix[4 * i], matrix[4 * i + 1], matrix[4 * i + 2])
+    };
+    let (x, y, z) = (column(0), column(1), column(2));
+    let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+    // A mirroring matrix flips one axis
+    if x.cross(y).dot(z) < 0.0 {
+        scale.x = -scale.x;
+    }
+    let rotation = Matrix3::from_cols(x / scale.x, y / scale.y, z / scale.z);
+
+    (column(3), Quaternion::from(rotation), scale)
+}
+
+/// Parents of the nodes of a document, to place them in the world
+struct Hierarchy<'a> {
+    nodes: &'a [GltfNode],
+    parents: Vec<Option<usize>>,
+}
+
+impl<'a> Hierarchy<'a> {
+    fn new(document: &'a GltfDocument) -> Result<Hierarchy<'a>, String> {
+        let mut parents = vec![None; document.nodes.len()];
+        for (i, node) in document.nodes.iter().enumerate() {
+            for &child in node.children.iter() {
+                *parents
+                    .get_mut(child)
+                    .ok_or(format!("Missing node {}", child))? = Some(i);
+            }
+        }
+
+        Ok(Hierarchy {
+            nodes: &document.nodes,
+            parents,
+        })
+    }
+
+    /// Nodes above `node`, nearest first. Stops at cycles
+    fn ancestors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.parents[node], move |&i| self.parents[i])
+            .take(self.nodes.len())
+    }
+
+    /// Transform of a node at rest, relative to the root of the scene
+    fn world(&self, node: usize) -> Matrix4<f32> {
+        std::iter::once(node)
+            .chain(self.ancestors(node))
+            .fold(Matrix4::identity(), |world, i| {
+                self.nodes[i].pose().matrix() * world
+            })
+    }
+}
+
+/// Looks accessors and images up in the buffers of a document
+struct Reader<'a> {
+    document: &'a GltfDocument,
+    buffers: &'a [Vec<u8>],
+}
+
+impl<'a> Reader<'a> {
+    /// Bytes of a buffer view
+    fn view(&self, index: usize) -> Result<&'a [u8], String> {
+        let view = self
+            .document
+            .buffer_views
+            .get(index)
+            .ok_or(format!("Missing buffer view {}", index))?;
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .ok_or(format!("Missing buffer {}", view.buffer))?;
+
+        buffer
+            .get(view.byte_offset..view.byte_offset + view.byte_length)
+            .ok_or(format!("Buffer view {} is out of bounds", index))
+    }
+
+    /// Components of every element of an accessor, in order. Normalized
+    /// integers are mapped to [0, 1] or [-1, 1]
+    fn read(&self, index: usize) -> Result<(Vec<f32>, usize), String> {
+        let accessor = self
+            .document
+            .accessors
+            .get(index)
+            .ok_or(format!("Missing accessor {}", index))?;
+        if accessor.sparse.is_some() {
+            return Err("Sparse accessors are not supported".to_string());
+        }
+
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT4" => 16,
+            kind => return Err(format!("Unsupported accessor type {}", kind)),
+        };
+        let size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            kind => return Err(format!("Unknown component type {}", kind)),
+        };
+
+        let view_index = match accessor.buffer_view {
+            Some(view) => view,
+            // Accessors without data are all zeros
+            None => return Ok((vec![0.0; accessor.count * components], 0)),
+        };
+        let view = self.view(view_index)?;
+        let stride = self.document.buffer_views[view_index]
+            .byte_stride
+            .unwrap_or(components * size);
+
+        let mut values = Vec::with_capacity(accessor.count * components);
+        for element in 0..accessor.count {
+            for component in 0..components {
+                let offset =
+                    accessor.byte_offset + element * stride + component * size;
+                let bytes = view.get(offset..offset + size).ok_or(format!(
+                    "Accessor {} reads past the end of its buffer view",
+                    index
+                ))?;
+                values.push(read_component(
+                    bytes,
+                    accessor.component_type,
+                    accessor.normalized,
+                ));
+            }
+        }
+
+        Ok((values, components))
+    }
+
+    fn primitive(&self, primitive: &GltfPrimitive) -> Result<MeshData, String> {
+        if primitive.mode.unwrap_or(TRIANGLES) != TRIANGLES {
+            return Err("Only triangle primitives are supported".to_string());
+        }
+
+        let (positions, _) = self.read(primitive.attributes.position)?;
+        let count = positions.len() / 3;
+        let indices = match primitive.indices {
+            Some(indices) => self
+                .read(indices)?
+                .0
+                .into_iter()
+                .map(|index| index as u32)
+                .collect(),
+            None => (0..count as u32).collect::<Vec<_>>(),
+        };
+        if let Some(index) = indices.iter().find(|&&i| i as usize >= count) {
+            return Err(format!("Vertex index {} is out of bounds", index));
+        }
+        if count > u16::MAX as usize + 1 {
+            return Err(format!(
+                "A primitive has {} vertices, more than 16-bit indices reach",
+                count
+            ));
+        }
+
+        let normals = match primitive.attributes.normal {
+            Some(normals) => self.read(normals)?.0,
+            None => compute_normals(&positions, &indices),
+        };
+        let uvs = match primitive.attributes.uv {
+            Some(uvs) => self.read(uvs)?.0,
+            None => vec![0.0; count * 2],
+        };
+        if normals.len() != count * 3 || uvs.len() != count * 2 {
+            return Err("Vertex attributes differ in length".to_string());
+        }
+        let targets = primitive
+            .targets
+            .iter()
+            .map(|target| self.morph_target(target, count))
+            .collect::<Result<Vec<_>, _>>()?;
+        let skin =
+            match (primitive.attributes.joints, primitive.attributes.weights) {
+                (Some(joints), Some(weights)) => {
+                    Some(self.skin(joints, weights, count)?)
+                }
+                _ => None,
+            };
+
+        let vertices = (0..count)
+            .map(|i| MeshVertex {
+                position: [
+                    positions[3 * i],
+                    positions[3 * i + 1],
+                    positions[3 * i + 2],
+                ],
+                normal: [
+                    normals[3 * i],
+                    normals[3 * i + 1],
+                    normals[3 * i + 2],
+                ],
+                // glTF already puts the origin of texture space at the top
+                uv: [uvs[2 * i], uvs[2 * i + 1]],
+            })
+            .collect();
+
+        Ok(MeshData {
+            vertices,
+            indices: indices.into_iter().map(|i| i as u16).collect(),
+            material: primitive.material,
+            skin,
+            targets,
+        })
+    }
+
+    /// Position and normal deltas of `count` vertices
+    fn morph_target(
+        &self,
+        target: &GltfMorphTarget,
+        count: usize,
+    ) -> Result<MorphTarget, String> {
+        let read = |accessor: Option<usize>| -> Result<Vec<[f32; 3]>, String> {
+            let values = match accessor {
+                Some(accessor) => self.read(accessor)?.0,
+                None => return Ok(Vec::new()),
+            };
+            if values.len() != count * 3 {
+                return Err("Morph targets differ in length".to_string());
+            }
+
+            Ok(values.chunks(3).map(|v| [v[0], v[1], v[2]]).collect())
+        };
+
+        Ok(MorphTarget {
+            positions: read(target.position)?,
+            normals: read(target.normal)?,
+        })
+    }
+
+    /// Joints and weights of `count` vertices, with the weights rescaled to
+    /// add up to 1
+    fn skin(
+        &self,
+        joints: usize,
+        weights: usize,
+        count: usize,
+    ) -> Result<Vec<SkinVertex>, String> {
+        let (joints, _) = self.read(joints)?;
+        let (weights, _) = self.read(weights)?;
+        if joints.len() != count * 4 || weights.len() != count * 4 {
+            return Err("Vertex attributes differ in length".to_string());
+        }
+
+        Ok(joints
+            .chunks(4)
+            .zip(weights.chunks(4))
+            .map(|(joints, weights)| {
+                let sum: f32 = weights.iter().sum();
+                let scale = if sum > 0.0 { 1.0 / sum } else { 0.0 };
+                SkinVertex {
+                    joints: [0, 1, 2, 3].map(|i| joints[i] as u32),
+                    weights: [0, 1, 2, 3].map(|i| weights[i] * scale),
+                }
+            })
+            .collect())
+    }
+
+    /// Skeleton of the skin at `index`. A joint's parent is its nearest
+    /// ancestor among the joints; the nodes above the first root joint, up
+    /// to the first mesh using the skin, end up in `Skeleton::root`
+    fn skeleton(
+        &self,
+        index: usize,
+        skin: &GltfSkin,
+        hierarchy: &Hierarchy,
+    ) -> Result<Skeleton, String> {
+        let nodes = &self.document.nodes;
+        if let Some(&node) = skin.joints.iter().find(|&&n| n >= nodes.len()) {
+            return Err(format!("Missing node {}", node));
+        }
+
+        let inverse_binds = match skin.inverse_bind_matrices {
+            Some(accessor) => {
+                let (values, components) = self.read(accessor)?;
+                if components != 16 || values.len() < skin.joints.len() * 16 {
+                    return Err(format!(
+                        "Skin {} needs a matrix per joint",
+                        index
+                    ));
+                }
+                values
+                    .chunks(16)
+                    .map(|m| {
+                        Matrix4::new(
+                            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7],
+                            m[8], m[9], m[10], m[11], m[12], m[13], m[14],
+                            m[15],
+                        )
+                    })
+                    .collect()
+            }
+            None => vec![Matrix4::identity(); skin.joints.len()],
+        };
+
+        let joints = skin
+            .joints
+            .iter()
+            .zip(inverse_binds)
+            .map(|(&node, inverse_bind)| Joint {
+                name: nodes[node].name.clone(),
+                parent: hierarchy.ancestors(node).find_map(|ancestor| {
+                    skin.joints.iter().position(|&joint| joint == ancestor)
+                }),
+                inverse_bind,
+                rest: nodes[node].pose(),
+            })
+            .collect::<Vec<_>>();
+
+        let above_joints = joints
+            .iter()
+            .position(|joint| joint.parent.is_none())
+            .and_then(|root| hierarchy.parents[skin.joints[root]])
+            .map_or(Matrix4::identity(), |parent| hierarchy.world(parent));
+        let mesh = nodes
+            .iter()
+            .position(|node| node.skin == Some(index))
+            .map_or(Matrix4::identity(), |node| hierarchy.world(node));
+
+        Ok(Skeleton {
+            joints,
+            root: mesh.invert().unwrap_or(mesh) * above_joints,
+        })
+    }
+
+    /// Channels of `animation` moving the joints of `skin`, `None` if it
+    /// moves none of them. Morph target weights are left to
+    /// `morph_animations`
+    fn animation(
+        &self,
+        animation: &GltfAnimation,
+        index: usize,
+        skin: &GltfSkin,
+    ) -> Result<Option<AnimationClip>, String> {
+        let mut channels = Vec::new();
+        for channel in animation.channels.iter() {
+            let joint = match channel.target.node.and_then(|node| {
+                skin.joints.iter().position(|&joint| joint == node)
+            }) {
+                Some(joint) if channel.target.path != "weights" => joint,
+                _ => continue,
+            };
+
+            let (interpolation, times, values, width) =
+                self.keyframes(animation, channel)?;
+            let keyframes = match (channel.target.path.as_str(), width) {
+                ("translation", 3) => Keyframes::Translation(
+                    values
+                        .chunks(3)
+                        .map(|v| Vector3::new(v[0], v[1], v[2]))
+                        .collect(),
+                ),
+                ("rotation", 4) => Keyframes::Rotation(
+                    values
+                        .chunks(4)
+                        .map(|v| Quaternion::new(v[3], v[0], v[1], v[2]))
+                        .collect(),
+                ),
+                ("scale", 3) => Keyframes::Scale(
+                    values
+                        .chunks(3)
+                        .map(|v| Vector3::new(v[0], v[1], v[2]))
+                        .collect(),
+                ),
+                (path, _) => {
+                    return Err(format!(
+                        "Animation {} has an invalid {} channel",
+                        animation.name, path
+                    ))
+                }
+            };
+
+            channels.push(AnimationChannel {
+                joint,
+                interpolation,
+                times,
+                keyframes,
+            });
+        }
+
+        Ok((!channels.is_empty()).then(|| AnimationClip {
+            name: animation.name.clone(),
+            skeleton: index,
+            channels,
+        }))
+    }
+
+    /// Weight channels of `animation`. `node_indices` maps the nodes of the
+    /// document to indices into `Model::nodes`
+    fn morph_animations(
+        &self,
+        animation: &GltfAnimation,
+        node_indices: &[Option<usize>],
+    ) -> Result<Vec<MorphAnimation>, String> {
+        let mut animations = Vec::new();
+        for channel in animation.channels.iter() {
+            let node = match channel
+                .target
+                .node
+                .and_then(|node| node_indices.get(node).copied().flatten())
+            {
+                Some(node) if channel.target.path == "weights" => node,
+                _ => continue,
+            };
+
+            let (interpolation, times, values, width) =
+                self.keyframes(animation, channel)?;
+            animations.push(MorphAnimation {
+                name: animation.name.clone(),
+                node,
+                interpolation,
+                times,
+                weights: values
+                    .chunks(width)
+                    .map(|weights| weights.to_vec())
+                    .collect(),
+            });
+        }
+
+        Ok(animations)
+    }
+
+    /// Interpolation, times and values of the sampler of `channel`, with
+    /// the number of values per time. Cubic splines keep three of those per
+    /// time, the value between its in-tangent and out-tangent
+    fn keyframes(
+        &self,
+        animation: &GltfAnimation,
+        channel: &GltfChannel,
+    ) -> Result<(Interpolation, Vec<f32>, Vec<f32>, usize), String> {
+        let sampler = animation
+            .samplers
+            .get(channel.sampler)
+            .ok_or(format!("Missing sampler {}", channel.sampler))?;
+        let interpolation = match sampler.interpolation.as_str() {
+            "STEP" => Interpolation::Step,
+            "LINEAR" => Interpolation::Linear,
+            "CUBICSPLINE" => Interpolation::CubicSpline,
+            other => {
+                return Err(format!(
+                    "Animation {} has an unknown interpolation {}",
+                    animation.name, other
+                ))
+            }
+        };
+
+        let (times, _) = self.read(sampler.input)?;
+        let (values, _) = self.read(sampler.output)?;
+        let slots = match interpolation {
+            Interpolation::CubicSpline => 3 * times.len(),
+            _ => times.len(),
+        };
+        if values.is_empty() || slots == 0 || values.len() % slots != 0 {
+            return Err(format!(
+                "Animation {} has a sampler without a value per time",
+                animation.name
+            ));
+        }
+
+        // Weight channels hold a scalar per morph target at each time
+        let width = values.len() / slots;
+
+        Ok((interpolation, times, values, width))
+    }
+
+    /// External images are loaded with the material, embedded ones are
+    /// decoded right away
+    fn image(
+        &self,
+        image: &GltfImage,
+        dir: &Path,
+    ) -> Result<TextureMap, String> {
+        let bytes = match (&image.uri, image.buffer_view) {
+            (Some(uri), _) if !uri.starts_with("data:") => {
+                return Ok(TextureMap::File(dir.join(uri)));
+            }
+            (Some(uri), _) => read_uri(uri, dir)?,
+            (None, Some(view)) => self.view(view)?.to_vec(),
+            (None, None) => return Err("Image without data".to_string()),
+        };
+        let image = image::load_from_memory(&bytes)
+            .map_err(|err| err.to_string())?
+            .to_rgba8();
+
+        Ok(TextureMap::Image(Arc::new(image)))
+    }
+}
+
+fn read_component(bytes: &[u8], component_type: u32, normalized: bool) -> f32 {
+    let (value, max) = match component_type {
+        5120 => (bytes[0] as i8 as f32, i8::MAX as f32),
+        5121 => (bytes[0] as f32, u8::MAX as f32),
+        5122 => (
+            i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            i16::MAX as f32,
+        ),
+        5123 => (
+            u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            u16::MAX as f32,
+        ),
+        5125 => {
+            let value =
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            return value as f32;
+        }
+        _ => {
+            return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+    };
+
+    if normalized {
+        (value / max).max(-1.0)
+    } else {
+        value
+    }
+}
+
+/// JSON chunk and optional BIN chunk of a GLB file
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|word| {
+                u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+            })
+            .ok_or_else(|| "Truncated GLB file".to_string())
+    };
+    if word(4)? != 2 {
+        return Err(format!("Unsupported GLB version {}", word(4)?));
+    }
+
+    let length = (word(8)? as usize).min(bytes.len());
+    let (mut json, mut bin) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (size, kind) = (word(offset)? as usize, word(offset + 4)?);
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + size)
+            .ok_or_else(|| "Truncated GLB chunk".to_string())?;
+        match kind {
+            GLB_JSON => json = json.or(Some(chunk)),
+            GLB_BIN => bin = bin.or(Some(chunk)),
+            // Unknown chunks are skipped
+            _ => {}
+        }
+        offset += 8 + size;
+    }
+
+    Ok((json.ok_or("GLB file without JSON chunk")?, bin))
+}
+
+/// Data URI or path relative to the glTF file
+fn read_uri(uri: &str, dir: &Path) -> Result<Vec<u8>, String> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (_, base64) = data
+                .split_once(";base64,")
+                .ok_or("Only base64 data URIs are supported")?;
+            decode_base64(base64)
+        }
+        None => {
+            let path = dir.join(uri);
+            fs::read(&path)
+                .map_err(|err| format!("{}: {}", path.display(), err))
+        }
+    }
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0_u32, 0);
+
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => {
+                return Err(format!("Invalid base64 character '{}'", c as char))
+            }
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Matrix4, Rad, Rotation3};
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("Zg==").unwrap(), b"f");
+        assert_eq!(decode_base64("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(decode_base64("Zm9v\nYmFy").unwrap(), b"foobar");
+        assert!(decode_base64("Zm9*").is_err());
+    }
+
+    #[test]
+    fn decompose_matrix() {
+        let rotation = Quaternion::from_angle_y(Rad(0.5));
+        let matrix = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+            * Matrix4::from(rotation)
+            * Matrix4::from_nonuniform_scale(2.0, 3.0, 4.0);
+        let columns: [[f32; 4]; 4] = matrix.into();
+        let mut flat = [0.0; 16];
+        flat.copy_from_slice(columns.concat().as_slice());
+
+        let (translation, decomposed, scale) = decompose(&flat);
+        assert_eq!(translation, Vector3::new(1.0, 2.0, 3.0));
+        assert!((scale - Vector3::new(2.0, 3.0, 4.0)).magnitude() < 1e-5);
+        assert!(decomposed.dot(rotation).abs() > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn load_gltf_file() {
+        let data = read_gltf(Path::new("assets/box.gltf")).unwrap();
+
+        assert_eq!(data.meshes.len(), 1);
+        let mesh = &data.meshes[0];
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_eq!(mesh.indices.len(), 36);
+        assert_eq!(mesh.material, Some(0));
+        for vertex in mesh.vertices.iter() {
+            let normal = Vector3::from(vertex.normal);
+            let position = Vector3::from(vertex.position);
+            // Faces point away from the center of the box
+            assert!((normal.magnitude() - 1.0).abs() < 1e-5);
+            assert!(normal.dot(position) > 0.0);
+        }
+
+        let material = &data.materials[0];
+        assert_eq!(material.name, "checker");
+        assert_eq!(material.diffuse, Color::new(1.0, 0.5, 0.25, 1.0));
+        assert!(!material.is_transparent());
+        match material.diffuse_map {
+            Some(TextureMap::Image(ref image)) => {
+                assert_eq!(image.dimensions(), (2, 2));
+                assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0, 255]);
+            }
+            ref map => panic!("Expected an embedded image, got {:?}", map),
+        }
+
+        let pbr = &data.pbr_materials[0];
+        assert_eq!(pbr.base_color, Color::new(1.0, 0.5, 0.25, 1.0));
+        assert_eq!((pbr.metallic, pbr.roughness), (0.0, 0.5));
+        assert_eq!(pbr.base_color_map, material.diffuse_map);
+        assert_eq!(pbr.normal_map, material.diffuse_map);
+        assert_eq!(pbr.normal_scale, 0.5);
+        assert_eq!(pbr.occlusion_map, None);
+        assert_eq!(pbr.occlusion_strength, 1.0);
+
+        let names = data
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.parent))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("base", None), ("lid", Some(0))]);
+        assert_eq!(data.nodes[0].meshes, [0]);
+        assert_eq!(data.nodes[1].translation, Vector3::new(0.0, 1.5, 0.0));
+        assert_eq!(data.nodes[1].scale, Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn load_glb_file() {
+        let data = read_gltf(Path::new("assets/pyramid.glb")).unwrap();
+
+        // One mesh made of two primitives
+        assert_eq!(data.meshes.len(), 2);
+        assert_eq!(data.meshes[0].material, Some(0));
+        assert_eq!(data.meshes[1].material, Some(1));
+        assert_eq!(data.meshes[1].indices, [0, 1, 2, 0, 2, 3]);
+        // Normals are generated for the base facing down
+        for vertex in data.meshes[1].vertices.iter() {
+            assert_eq!(vertex.normal, [0.0, -1.0, 0.0]);
+        }
+
+        let base = &data.materials[1];
+        assert_eq!(base.emissive, Color::new(0.0, 0.0, 1.0, 1.0));
+        assert!(base.is_transparent());
+        match base.diffuse_map {
+            Some(TextureMap::Image(ref image)) => {
+                assert_eq!(image.dimensions(), (1, 1))
+            }
+            ref map => panic!("Expected an embedded image, got {:?}", map),
+        }
+
+        assert_eq!(data.nodes.len(), 1);
+        let node = &data.nodes[0];
+        assert_eq!(node.name, "pyramid");
+        assert_eq!(node.meshes, [0, 1]);
+        assert_eq!(node.translation, Vector3::new(0.0, 0.0, -2.0));
+        assert!((node.scale - Vector3::new(2.0, 2.0, 2.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn load_skin_and_animation() {
+        let data = read_gltf(Path::new("assets/bend.gltf")).unwrap();
+
+        let skin = data.meshes[0].skin.as_ref().unwrap();
+        assert_eq!(skin.len(), 6);
+        assert_eq!(skin[2].joints, [0, 1, 0, 0]);
+        assert_eq!(skin[2].weights, [0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(data.nodes[1].skin, Some(0));
+
+        let skeleton = &data.skeletons[0];
+        let names = skeleton
+            .joints
+            .iter()
+            .map(|joint| (joint.name.as_str(), joint.parent))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("root", None), ("tip", Some(0))]);
+        // The armature node is above both the mesh and the joints
+        assert_eq!(skeleton.root, Matrix4::identity());
+        // Bound at rest, the mesh is left as it is
+        for matrix in skeleton.joint_matrices(&skeleton.rest_pose()) {
+            assert_eq!(matrix, Matrix4::identity());
+        }
+
+        assert_eq!(data.animations.len(), 1);
+        let clip = &data.animations[0];
+        assert_eq!((clip.name.as_str(), clip.skeleton), ("bend", 0));
+        assert_eq!(clip.duration(), 2.0);
+        assert_eq!(clip.channels[0].joint, 1);
+        assert_eq!(clip.channels[1].interpolation, Interpolation::Step);
+
+        let mut pose = skeleton.rest_pose();
+        clip.sample(1.0, &mut pose);
+        let expected =
+            Quaternion::from_angle_z(Rad(std::f32::consts::PI / 4.0));
+        assert!(pose[1].rotation.dot(expected).abs() > 1.0 - 1e-5);
+        assert_eq!(pose[0].translation, Vector3::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn load_morph_targets() {
+        let data = read_gltf(Path::new("assets/blink.gltf")).unwrap();
+
+        let targets = &data.meshes[0].targets;
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].positions[3], [0.0, 0.5, 0.0]);
+        assert!(targets[0].normals.is_empty());
+        assert_eq!(targets[1].normals.len(), 4);
+        // The weights of the node override the ones of the mesh
+        assert_eq!(data.nodes[0].weights, [0.0, 0.5]);
+
+        assert_eq!(data.morph_animations.len(), 1);
+        let animation = &data.morph_animations[0];
+        assert_eq!((animation.name.as_str(), animation.node), ("blink", 0));
+        assert_eq!(animation.weights, [[1.0, 0.0], [0.0, 1.0], [1.0, 0.0]]);
+        assert_eq!(animation.sample(0.25), [0.5, 0.5]);
+        assert!(data.animations.is_empty());
+    }
+}
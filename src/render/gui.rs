@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::Range;
+use std::time::Instant;
+
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use egui::{
+    epaint::{ImageDelta, Primitive, Vertex},
+    ClippedPrimitive, Context, ImageData, Rect, TextureFilter, TextureId,
+    TextureOptions, TextureWrapMode, TexturesDelta, ViewportId,
+};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BlendState, FilterMode,
+    ShaderStages, TextureFormat,
+};
+
+use crate::window::Window;
+
+use super::{
+    Binding, BindingLayout, Buffer, ColorSpace, CommandBuffer, Framebuffer,
+    Pipeline, Render, Sampler, ShaderAttribute, ShaderAttributeType, Texture,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ScreenUniforms {
+    size: [f32; 2],
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+/// Texture managed by egui, like the font atlas, with the bind group
+/// drawing with it
+struct GuiTexture {
+    texture: Texture,
+    bind_group: BindGroup,
+}
+
+/// Textures the application registered, by the id the GUI shows them with,
+/// holding what draws with each of them
+struct UserTextures<T> {
+    bound: HashMap<TextureId, T>,
+    /// Ids are never reused, so a stale one shows nothing
+    next: u64,
+}
+
+impl<T> UserTextures<T> {
+    fn new() -> UserTextures<T> {
+        UserTextures {
+            bound: HashMap::new(),
+            next: 0,
+        }
+    }
+
+    fn register(&mut self, bound: T) -> TextureId {
+        let id = TextureId::User(self.next);
+        self.next += 1;
+        self.bound.insert(id, bound);
+
+        id
+    }
+
+    /// Replaces what `id` is bound to, ignoring ids not registered
+    fn update(&mut self, id: TextureId, bound: T) {
+        if let Some(registered) = self.bound.get_mut(&id) {
+            *registered = bound;
+        }
+    }
+
+    fn unregister(&mut self, id: TextureId) -> Option<T> {
+        self.bound.remove(&id)
+    }
+
+    fn get(&self, id: TextureId) -> Option<&T> {
+        self.bound.get(&id)
+    }
+}
+
+/// Indices of one tessellated mesh, drawn clipped to `scissor`
+struct GuiDraw {
+    texture: TextureId,
+    /// Position and size in pixels
+    scissor: ([u32; 2], [u32; 2]),
+    indices: Range<u32>,
+    base_vertex: i32,
+}
+
+/// Immediate-mode GUI (egui) fed with the input of a `Window` and drawn
+/// over the frame in a render pass of its own. The UI is built once per
+/// frame in `run`, then uploaded by `prepare` and recorded by `draw`
+pub struct Gui {
+    context: Context,
+    /// Display scale replacing `Window::dpi_scale`
+    scale: Option<f32>,
+    start: Instant,
+    pipeline: Pipeline,
+    texture_layout: BindGroupLayout,
+    uniforms: Buffer,
+    screen_group: BindGroup,
+    samplers: HashMap<TextureOptions, Sampler>,
+    textures: HashMap<TextureId, GuiTexture>,
+    user_textures: UserTextures<BindGroup>,
+    /// Output of the last `run`, waiting for `prepare`
+    output: Option<(TexturesDelta, Vec<ClippedPrimitive>, f32)>,
+    /// Textures the GUI stopped using, freed once the frame using them
+    /// last was submitted
+    freed: Vec<TextureId>,
+    vertices: Buffer,
+    indices: Buffer,
+    /// Vertices and indices the buffers have room for
+    capacity: (usize, usize),
+    draws: Vec<GuiDraw>,
+    encode_srgb: bool,
+}
+
+impl Gui {
+    /// GUI drawn into framebuffers of `format`, the size of the surface and
+    /// without a depth attachment
+    pub fn new(render: &Render, format: TextureFormat) -> Gui {
+        let screen_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let texture_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Texture, BindingLayout::Sampler],
+            ShaderStages::FRAGMENT,
+        );
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/gui.wgsl"),
+        ));
+        let vertex_layout = render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 0),
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 1),
+            ShaderAttribute::new(ShaderAttributeType::UnormVec4, 2),
+        ]);
+        let pipeline = render
+            .build_pipeline(&shader)
+            .vertex_layout(&vertex_layout)
+            .bind_group_layout(&screen_layout)
+            .bind_group_layout(&texture_layout)
+            .blended_target(
+                format,
+                Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            )
+            .build();
+        let uniforms = render.create_uniforms(&ScreenUniforms::zeroed());
+        let screen_group = render.create_bind_group_with(
+            &screen_layout,
+            &[Binding::Buffer(&uniforms)],
+        );
+        let capacity = (4096, 4 * 4096);
+
+        Gui {
+            context: Context::default(),
+            scale: None,
+            start: Instant::now(),
+            pipeline,
+            texture_layout,
+            uniforms,
+            screen_group,
+            samplers: HashMap::new(),
+            textures: HashMap::new(),
+            user_textures: UserTextures::new(),
+            output: None,
+            freed: Vec::new(),
+            vertices: render.create_dynamic_vertex_buffer(
+                (capacity.0 * size_of::<Vertex>()) as u64,
+            ),
+            indices: render.create_dynamic_index_buffer(
+                (capacity.1 * size_of::<u16>()) as u64,
+            ),
+            capacity,
+            draws: Vec::new(),
+            encode_srgb: render.encodes_srgb(format),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Scales the GUI by `scale` instead of the DPI scale of the display,
+    /// on top of the zoom egui applies with Ctrl and +/-
+    pub fn set_scale(&mut self, scale: Option<f32>) {
+        self.scale = scale;
+    }
+
+    /// Whether the pointer is over the GUI or dragging something in it, in
+    /// which case the application should ignore the mouse
+    pub fn wants_pointer(&self) -> bool {
+        self.context.wants_pointer_input()
+            || self.context.is_pointer_over_area()
+    }
+
+    /// Whether a text field has focus, in which case the application
+    /// should ignore the keyboard
+    pub fn wants_keyboard(&self) -> bool {
+        self.context.wants_keyboard_input()
+    }
+
+    /// Builds the UI of this frame with `ui`, fed with the input `window`
+    /// received in its last `update`. Call once per frame before `prepare`.
+    /// Fails if the text the GUI copied couldn't reach the clipboard, the
+    /// frame being built regardless
+    pub fn run(
+        &mut self,
+        window: &mut Window,
+        ui: impl FnMut(&Context),
+    ) -> Result<(), String> {
+        let scale = self.scale.unwrap_or_else(|| window.dpi_scale());
+        let pixels_per_point = scale * self.context.zoom_factor();
+
+        let mut input = window.gui_input(pixels_per_point);
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(scale);
+
+        let output = self.context.run(input, ui);
+        let handled = window.handle_gui_output(&output.platform_output);
+
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        self.output =
+            Some((output.textures_delta, primitives, output.pixels_per_point));
+        handled
+    }
+
+    /// Makes `texture` available to the GUI, e.g. to `egui::Image`, until
+    /// `unregister_texture`
+    pub fn register_texture(
+        &mut self,
+        render: &Render,
+        texture: &Texture,
+        options: TextureOptions,
+    ) -> TextureId {
+        let bind_group = self.bind_texture(render, texture, options);
+        self.user_textures.register(bind_group)
+    }
+
+    /// Points a registered `id` to another texture, e.g. a render target
+    /// recreated after a resize
+    #[allow(dead_code)]
+    pub fn update_texture(
+        &mut self,
+        render: &Render,
+        id: TextureId,
+        texture: &Texture,
+        options: TextureOptions,
+    ) {
+        let bind_group = self.bind_texture(render, texture, options);
+        self.user_textures.update(id, bind_group);
+    }
+
+    /// Stops showing a registered texture, whose `id` then shows nothing
+    #[allow(dead_code)]
+    pub fn unregister_texture(&mut self, id: TextureId) {
+        self.user_textures.unregister(id);
+    }
+
+    fn bind_texture(
+        &mut self,
+        render: &Render,
+        texture: &Texture,
+        options: TextureOptions,
+    ) -> BindGroup {
+        let sampler = self.samplers.entry(options).or_insert_with(|| {
+            let filter = match options.magnification {
+                TextureFilter::Nearest => FilterMode::Nearest,
+                TextureFilter::Linear => FilterMode::Linear,
+            };
+            let address_mode = match options.wrap_mode {
+                TextureWrapMode::ClampToEdge => AddressMode::ClampToEdge,
+                TextureWrapMode::Repeat => AddressMode::Repeat,
+                TextureWrapMode::MirroredRepeat => AddressMode::MirrorRepeat,
+            };
+            render.create_sampler(filter, address_mode)
+        });
+
+        render.create_bind_group_with(
+            &self.texture_layout,
+            &[Binding::Texture(texture), Binding::Sampler(sampler)],
+        )
+    }
+
+    /// Creates or updates part of a texture managed by egui, like the font
+    /// atlas
+    fn set_texture(
+        &mut self,
+        render: &Render,
+        id: TextureId,
+        delta: &ImageDelta,
+    ) {
+        let [width, height] = delta.image.size().map(|size| size as u32);
+        let pixels: Vec<u8> = match delta.image {
+            ImageData::Color(ref image) => cast_slice(&image.pixels).to_vec(),
+            ImageData::Font(ref image) => image
+                .srgba_pixels(None)
+                .flat_map(|color| color.to_array())
+                .collect(),
+        };
+
+        match delta.pos {
+            Some([x, y]) => {
+                if let Some(registered) = self.textures.get(&id) {
+                    render.write_texture(
+                        &registered.texture,
+                        [x as u32, y as u32],
+                        width,
+                        height,
+                        &pixels,
+                    );
+                }
+            }
+            None => {
+                let texture = render.create_texture(
+                    width,
+                    height,
+                    &pixels,
+                    ColorSpace::Srgb,
+                );
+                let bind_group =
+                    self.bind_texture(render, &texture, delta.options);
+                self.textures.insert(
+                    id,
+                    GuiTexture {
+                        texture,
+                        bind_group,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Uploads the textures and meshes of the last `run`. Call once per
+    /// frame before `draw`
+    pub fn prepare(&mut self, render: &Render) {
+        for id in self.freed.drain(..) {
+            self.textures.remove(&id);
+        }
+        self.draws.clear();
+        let (textures, primitives, pixels_per_point) = match self.output.take()
+        {
+            Some(output) => output,
+            None => return,
+        };
+        for (id, delta) in &textures.set {
+            self.set_texture(render, *id, delta);
+        }
+        self.freed = textures.free;
+
+        let (width, height) = render.size();
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u16> = Vec::new();
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in primitives
+        {
+            let mesh = match primitive {
+                Primitive::Mesh(mesh) => mesh,
+                // Custom painting isn't supported
+                Primitive::Callback(_) => continue,
+            };
+            let scissor = match scissor_rect(
+                clip_rect,
+                pixels_per_point,
+                [width, height],
+            ) {
+                Some(scissor) => scissor,
+                None => continue,
+            };
+
+            for mesh in mesh.split_to_u16() {
+                let start = indices.len() as u32;
+                self.draws.push(GuiDraw {
+                    texture: mesh.texture_id,
+                    scissor,
+                    indices: start..start + mesh.indices.len() as u32,
+                    base_vertex: vertices.len() as i32,
+                });
+                vertices.extend_from_slice(&mesh.vertices);
+                indices.extend_from_slice(&mesh.indices);
+            }
+        }
+        // Buffer writes go by multiples of 4 bytes
+        if indices.len() % 2 == 1 {
+            indices.push(0);
+        }
+
+        if vertices.len() > self.capacity.0 {
+            self.capacity.0 = vertices.len().next_power_of_two();
+            self.vertices = render.create_dynamic_vertex_buffer(
+                (self.capacity.0 * size_of::<Vertex>()) as u64,
+            );
+        }
+        if indices.len() > self.capacity.1 {
+            self.capacity.1 = indices.len().next_power_of_two();
+            self.indices = render.create_dynamic_index_buffer(
+                (self.capacity.1 * size_of::<u16>()) as u64,
+            );
+        }
+        if !indices.is_empty() {
+            render.write_buffer(&self.vertices, cast_slice(&vertices));
+            render.write_buffer(&self.indices, cast_slice(&indices));
+        }
+
+        let uniforms = ScreenUniforms {
+            size: [
+                width as f32 / pixels_per_point,
+                height as f32 / pixels_per_point,
+            ],
+            encode_srgb: self.encode_srgb as u32,
+            _padding: 0,
+        };
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+    }
+
+    /// Draws the GUI over what `output` already holds, in a render pass of
+    /// its own
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        if self.draws.is_empty() {
+            return commands;
+        }
+
+        let mut commands = commands
+            .label("gui")
+            .configure_draw_over(&self.pipeline, output)
+            .bind_resources(&self.screen_group)
+            .set_vertices(&self.vertices)
+            .set_indices(&self.indices);
+        for draw in &self.draws {
+            let bind_group = match self.textures.get(&draw.texture) {
+                Some(texture) => &texture.bind_group,
+                None => match self.user_textures.get(draw.texture) {
+                    Some(bind_group) => bind_group,
+                    None => continue,
+                },
+            };
+            let (position, size) = draw.scissor;
+            commands = commands
+                .set_scissor(position, size)
+                .bind_resources_at(1, bind_group)
+                .draw_from(draw.indices.clone(), draw.base_vertex);
+        }
+
+        commands
+    }
+}
+
+/// Pixels of a target of `size` inside `clip`, given in points. `None`
+/// when nothing is left
+fn scissor_rect(
+    clip: Rect,
+    pixels_per_point: f32,
+    size: [u32; 2],
+) -> Option<([u32; 2], [u32; 2])> {
+    let [width, height] = size.map(|size| size as f32);
+    let min_x = (clip.min.x * pixels_per_point).round().clamp(0.0, width);
+    let min_y = (clip.min.y * pixels_per_point).round().clamp(0.0, height);
+    let max_x = (clip.max.x * pixels_per_point).round().clamp(min_x, width);
+    let max_y = (clip.max.y * pixels_per_point).round().clamp(min_y, height);
+
+    let size = [(max_x - min_x) as u32, (max_y - min_y) as u32];
+    if size[0] == 0 || size[1] == 0 {
+        return None;
+    }
+
+    Some(([min_x as u32, min_y as u32], size))
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2};
+
+    use super::*;
+
+    #[test]
+    fn scissors_in_pixels_within_target() {
+        let clip = Rect::from_min_size(pos2(10.0, 20.0), vec2(100.0, 50.0));
+        assert_eq!(
+            scissor_rect(clip, 2.0, [1000, 1000]),
+            Some(([20, 40], [200, 100]))
+        );
+        assert_eq!(
+            scissor_rect(clip, 1.0, [60, 40]),
+            Some(([10, 20], [50, 20]))
+        );
+
+        let everything = Rect::EVERYTHING;
+        assert_eq!(
+            scissor_rect(everything, 1.5, [640, 480]),
+            Some(([0, 0], [640, 480]))
+        );
+        assert_eq!(scissor_rect(clip, 1.0, [5, 5]), None);
+    }
+
+    #[test]
+    fn user_textures_register_update_and_unregister() {
+        let mut textures = UserTextures::new();
+        let atlas = textures.register("atlas");
+        let target = textures.register("target");
+        assert_ne!(atlas, target);
+        assert_eq!(textures.get(atlas), Some(&"atlas"));
+
+        // A render target recreated after a resize keeps its id
+        textures.update(target, "resized target");
+        assert_eq!(textures.get(target), Some(&"resized target"));
+
+        assert_eq!(textures.unregister(atlas), Some("atlas"));
+        assert_eq!(textures.get(atlas), None);
+        textures.update(atlas, "stale");
+        assert_eq!(textures.get(atlas), None);
+
+        // Ids of unregistered textures aren't handed out again
+        let sprite = textures.register("sprite");
+        assert!(sprite != atlas && sprite != target);
+    }
+}
@@ -0,0 +1,391 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
+
+use super::Color;
+
+/// Lights past this many are ignored when shading
+pub const MAX_LIGHTS: usize = 16;
+/// Shadow map layers given to the shadowed directional light, one per
+/// cascade. Spot light shadows come after them
+pub const MAX_CASCADES: usize = 4;
+/// Spot lights past this many don't cast shadows
+pub const MAX_SPOT_SHADOWS: usize = 4;
+
+const DIRECTIONAL: u32 = 0;
+const POINT: u32 = 1;
+const SPOT: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    /// Parallel rays coming from infinitely far away, like the sun.
+    /// `direction` is where the light travels to
+    /// Only the first directional light with `shadows` set casts them
+    Directional {
+        direction: Vector3<f32>,
+        color: Color,
+        intensity: f32,
+        shadows: bool,
+    },
+    /// Shines in every direction, reaching nothing beyond `range`
+    Point {
+        position: Point3<f32>,
+        range: f32,
+        color: Color,
+        intensity: f32,
+    },
+    /// Cone of light, at full strength within `inner` degrees of
+    /// `direction` and fading out until `outer`
+    Spot {
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        range: f32,
+        inner: Deg<f32>,
+        outer: Deg<f32>,
+        color: Color,
+        intensity: f32,
+        shadows: bool,
+    },
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct LightData {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+    /// Cosines of the inner and outer cone angles of spot lights
+    cone: [f32; 2],
+    /// First shadow map layer of the light, -1 without shadows
+    shadow: i32,
+    _padding: f32,
+}
+
+impl Light {
+    fn casts_shadows(&self) -> bool {
+        match *self {
+            Light::Directional { shadows, .. }
+            | Light::Spot { shadows, .. } => shadows,
+            Light::Point { .. } => false,
+        }
+    }
+
+    fn to_data(self, shadow: Option<u32>) -> LightData {
+        let mut data = LightData::zeroed();
+        let (color, intensity) = match self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+                ..
+            } => {
+                data.kind = DIRECTIONAL;
+                data.direction = direction.normalize().into();
+                (color, intensity)
+            }
+            Light::Point {
+                position,
+                range,
+                color,
+                intensity,
+            } => {
+                data.kind = POINT;
+                data.position = position.into();
+                data.range = range;
+                (color, intensity)
+            }
+            Light::Spot {
+                position,
+                direction,
+                range,
+                inner,
+                outer,
+                color,
+                intensity,
+                ..
+            } => {
+                data.kind = SPOT;
+                data.position = position.into();
+                data.direction = direction.normalize().into();
+                data.range = range;
+                let inner = Rad::from(inner).0.cos();
+                // Keeps the fade from dividing by zero when both are equal
+                let outer = Rad::from(outer).0.cos().min(inner - 1e-4);
+                data.cone = [inner, outer];
+                (color, intensity)
+            }
+        };
+        data.color = [color.r, color.g, color.b];
+        data.intensity = intensity;
+        data.shadow = shadow.map_or(-1, |layer| layer as i32);
+
+        data
+    }
+}
+
+/// Layout of the `Lights` uniform block of the lit shaders
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct LightUniforms {
+    ambient: [f32; 3],
+    count: u32,
+    lights: [LightData; MAX_LIGHTS],
+}
+
+/// Handle to a light added to `Lights`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightId(u64);
+
+/// Set of lights shading a frame, plus a constant ambient term
+pub struct Lights {
+    pub ambient: Color,
+    lights: Vec<(LightId, Light)>,
+    next_id: u64,
+}
+
+impl Lights {
+    pub fn new(ambient: Color) -> Lights {
+        Lights {
+            ambient,
+            lights: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn add(&mut self, light: Light) -> LightId {
+        let id = LightId(self.next_id);
+        self.next_id += 1;
+        self.lights.push((id, light));
+
+        id
+    }
+
+    /// Returns the removed light, or `None` if it was already gone
+    #[allow(dead_code)]
+    pub fn remove(&mut self, id: LightId) -> Option<Light> {
+        let index = self
+            .lights
+            .iter()
+            .position(|(light_id, _)| *light_id == id)?;
+
+        Some(self.lights.remove(index).1)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        self.lights
+            .iter_mut()
+            .find(|(light_id, _)| *light_id == id)
+            .map(|(_, light)| light)
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter().map(|(_, light)| light)
+    }
+
+    /// Lights within `MAX_LIGHTS` with their first shadow map layer, for
+    /// the ones that get shadows
+    fn shadow_layers(&self) -> impl Iterator<Item = (&Light, Option<u32>)> {
+        let mut sun_taken = false;
+        let mut spots = 0;
+
+        self.lights.iter().take(MAX_LIGHTS).map(move |(_, light)| {
+            let layer = match light {
+                _ if !light.casts_shadows() => None,
+                Light::Directional { .. } if !sun_taken => {
+                    sun_taken = true;
+                    Some(0)
+                }
+                Light::Spot { .. } if spots < MAX_SPOT_SHADOWS => {
+                    spots += 1;
+                    Some((MAX_CASCADES + spots - 1) as u32)
+                }
+                _ => None,
+            };
+
+            (light, layer)
+        })
+    }
+
+    /// Lights that get shadows, with their first shadow map layer
+    pub fn shadow_casters(&self) -> Vec<(Light, u32)> {
+        self.shadow_layers()
+            .filter_map(|(light, layer)| layer.map(|layer| (*light, layer)))
+            .collect()
+    }
+
+    /// Packs the lights for upload, keeping the first `MAX_LIGHTS`
+    pub fn to_uniforms(&self) -> LightUniforms {
+        let mut uniforms = LightUniforms::zeroed();
+        uniforms.ambient = [self.ambient.r, self.ambient.g, self.ambient.b];
+        for (data, (light, layer)) in
+            uniforms.lights.iter_mut().zip(self.shadow_layers())
+        {
+            *data = light.to_data(layer);
+        }
+        uniforms.count = self.lights.len().min(MAX_LIGHTS) as u32;
+
+        uniforms
+    }
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        Lights::new(Color::BLACK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{point3, vec3};
+
+    fn point(x: f32) -> Light {
+        Light::Point {
+            position: point3(x, 0.0, 0.0),
+            range: 5.0,
+            color: Color::WHITE,
+            intensity: 1.0,
+        }
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut lights = Lights::default();
+        let a = lights.add(point(1.0));
+        let b = lights.add(point(2.0));
+        let c = lights.add(point(3.0));
+        let sun = lights.add(Light::Directional {
+            direction: vec3(0.0, -1.0, 0.0),
+            color: Color::WHITE,
+            intensity: 1.0,
+            shadows: true,
+        });
+
+        assert_eq!(lights.remove(b), Some(point(2.0)));
+        assert_eq!(lights.remove(b), None);
+        assert_eq!(lights.len(), 3);
+
+        *lights.get_mut(c).unwrap() = point(4.0);
+        let uniforms = lights.to_uniforms();
+        assert_eq!(uniforms.count, 3);
+        assert_eq!(uniforms.lights[0].position, [1.0, 0.0, 0.0]);
+        assert_eq!(uniforms.lights[1].position, [4.0, 0.0, 0.0]);
+        assert_eq!(uniforms.lights[2].kind, DIRECTIONAL);
+        assert_eq!(lights.shadow_casters().len(), 1);
+
+        // The sun takes its shadow maps along
+        assert!(lights.remove(sun).is_some());
+        assert_eq!(lights.to_uniforms().count, 2);
+        assert!(lights.shadow_casters().is_empty());
+        assert!(lights.get_mut(a).is_some());
+
+        lights.clear();
+        assert!(lights.is_empty());
+        assert_eq!(lights.to_uniforms().count, 0);
+        assert!(lights.get_mut(a).is_none());
+    }
+
+    #[test]
+    fn packing() {
+        let mut lights = Lights::new(Color::new(0.1, 0.2, 0.3, 1.0));
+        lights.add(Light::Directional {
+            direction: vec3(0.0, -2.0, 0.0),
+            color: Color::new(1.0, 0.5, 0.25, 1.0),
+            intensity: 3.0,
+            shadows: false,
+        });
+        lights.add(Light::Spot {
+            position: point3(0.0, 1.0, 0.0),
+            direction: vec3(0.0, 0.0, -1.0),
+            range: 10.0,
+            inner: Deg(0.0),
+            outer: Deg(90.0),
+            color: Color::WHITE,
+            intensity: 1.0,
+            shadows: false,
+        });
+        let uniforms = lights.to_uniforms();
+
+        assert_eq!(uniforms.ambient, [0.1, 0.2, 0.3]);
+        let sun = uniforms.lights[0];
+        assert_eq!(sun.kind, DIRECTIONAL);
+        assert_eq!(sun.direction, [0.0, -1.0, 0.0]);
+        assert_eq!(sun.color, [1.0, 0.5, 0.25]);
+        assert_eq!(sun.intensity, 3.0);
+
+        let spot = uniforms.lights[1];
+        assert_eq!(spot.kind, SPOT);
+        assert_eq!(spot.range, 10.0);
+        assert!((spot.cone[0] - 1.0).abs() < 1e-6);
+        assert!(spot.cone[1].abs() < 1e-6);
+        assert_eq!(spot.shadow, -1);
+    }
+
+    #[test]
+    fn shadow_layers() {
+        let sun = |shadows| Light::Directional {
+            direction: vec3(0.0, -1.0, 0.0),
+            color: Color::WHITE,
+            intensity: 1.0,
+            shadows,
+        };
+        let spot = |shadows| Light::Spot {
+            position: point3(0.0, 1.0, 0.0),
+            direction: vec3(0.0, -1.0, 0.0),
+            range: 10.0,
+            inner: Deg(10.0),
+            outer: Deg(20.0),
+            color: Color::WHITE,
+            intensity: 1.0,
+            shadows,
+        };
+
+        let mut lights = Lights::default();
+        lights.add(spot(false));
+        lights.add(point(0.0));
+        lights.add(sun(true));
+        lights.add(sun(true));
+        for _ in 0..MAX_SPOT_SHADOWS + 1 {
+            lights.add(spot(true));
+        }
+
+        let expected = [-1, -1, 0, -1, 4, 5, 6, 7, -1];
+        assert_eq!(lights.len(), expected.len());
+        let layers = lights
+            .to_uniforms()
+            .lights
+            .iter()
+            .take(expected.len())
+            .map(|light| light.shadow)
+            .collect::<Vec<_>>();
+        assert_eq!(layers, expected);
+        assert_eq!(lights.shadow_casters().len(), 1 + MAX_SPOT_SHADOWS);
+    }
+
+    #[test]
+    fn extra_lights_are_dropped() {
+        let mut lights = Lights::default();
+        for i in 0..MAX_LIGHTS + 4 {
+            lights.add(point(i as f32));
+        }
+
+        assert_eq!(lights.to_uniforms().count, MAX_LIGHTS as u32);
+    }
+}
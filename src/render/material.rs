@@ -0,0 +1,278 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::RgbaImage;
+
+use super::{Color, ColorSpace, Render, Texture};
+
+/// Image a texture map of a `Material` is read from
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextureMap {
+    /// Image file, loaded when the material is uploaded
+    File(PathBuf),
+    /// Image that came embedded in a model file, already decoded
+    Image(Arc<RgbaImage>),
+}
+
+impl TextureMap {
+    pub fn load(
+        &self,
+        render: &Render,
+        color_space: ColorSpace,
+    ) -> Result<Texture, String> {
+        match self {
+            TextureMap::File(path) => render.load_texture(path, color_space),
+            TextureMap::Image(image) => Ok(render.create_texture(
+                image.width(),
+                image.height(),
+                image,
+                color_space,
+            )),
+        }
+    }
+}
+
+/// Blinn-Phong surface parameters, the way MTL files describe them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// `Ka`, scales the ambient light reflected with the diffuse color
+    pub ambient: Color,
+    /// `Kd`
+    pub diffuse: Color,
+    /// `Ks`
+    pub specular: Color,
+    /// `Ns`, the higher the smaller and sharper the highlights
+    pub shininess: f32,
+    /// `d`, from fully transparent (0) to opaque (1)
+    pub opacity: f32,
+    /// `Ke`, light given off regardless of the lights around
+    pub emissive: Color,
+    /// `map_Kd`, multiplied with `diffuse`
+    pub diffuse_map: Option<TextureMap>,
+    /// `map_Ks`, multiplied with `specular`
+    pub specular_map: Option<TextureMap>,
+}
+
+impl Material {
+    /// Matte material of a single color
+    pub fn from_color(color: Color) -> Material {
+        Material {
+            diffuse: color,
+            ..Default::default()
+        }
+    }
+
+    /// Converts a material parsed by tobj. Texture paths are relative to
+    /// `dir`, the directory of the MTL file
+    pub fn from_mtl(material: &tobj::Material, dir: &Path) -> Material {
+        let color = |[r, g, b]: [f32; 3]| Color::new(r, g, b, 1.0);
+        let map = |path: &str| {
+            if path.is_empty() {
+                None
+            } else {
+                Some(TextureMap::File(dir.join(path)))
+            }
+        };
+        // tobj doesn't know about `Ke` and keeps it as text
+        let emissive = material
+            .unknown_param
+            .get("Ke")
+            .and_then(|value| parse_color(value))
+            .unwrap_or(Color::BLACK);
+
+        Material {
+            name: material.name.clone(),
+            ambient: color(material.ambient),
+            diffuse: color(material.diffuse),
+            specular: color(material.specular),
+            shininess: material.shininess,
+            opacity: material.dissolve,
+            emissive,
+            diffuse_map: map(&material.diffuse_texture),
+            specular_map: map(&material.specular_texture),
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.opacity < 1.0
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::new(),
+            ambient: Color::WHITE,
+            diffuse: Color::WHITE,
+            specular: Color::BLACK,
+            shininess: 1.0,
+            opacity: 1.0,
+            emissive: Color::BLACK,
+            diffuse_map: None,
+            specular_map: None,
+        }
+    }
+}
+
+/// Metallic-roughness surface parameters, the way glTF describes them
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    pub name: String,
+    /// Albedo of dielectrics and reflectance of metals, a = opacity
+    pub base_color: Color,
+    /// From dielectric (0) to metal (1)
+    pub metallic: f32,
+    /// From mirror-like (0) to fully diffuse reflections (1)
+    pub roughness: f32,
+    pub emissive: Color,
+    /// Multiplied with `base_color`
+    pub base_color_map: Option<TextureMap>,
+    /// Roughness in the green channel and metalness in the blue one,
+    /// multiplied with `roughness` and `metallic`
+    pub metallic_roughness_map: Option<TextureMap>,
+    /// Tangent-space normals
+    pub normal_map: Option<TextureMap>,
+    /// Scales the XY components of `normal_map`
+    pub normal_scale: f32,
+    /// Ambient occlusion in the red channel
+    pub occlusion_map: Option<TextureMap>,
+    /// How much of `occlusion_map` applies, from none (0) to all (1)
+    pub occlusion_strength: f32,
+    /// Multiplied with `emissive`
+    pub emissive_map: Option<TextureMap>,
+}
+
+impl PbrMaterial {
+    /// Approximates a Blinn-Phong material: a dielectric whose roughness
+    /// gives highlights as wide as its shininess did
+    pub fn from_phong(material: &Material) -> PbrMaterial {
+        let roughness = (2.0 / (material.shininess.max(0.0) + 2.0)).powf(0.25);
+
+        PbrMaterial {
+            name: material.name.clone(),
+            base_color: Color {
+                a: material.opacity,
+                ..material.diffuse
+            },
+            roughness,
+            emissive: material.emissive,
+            base_color_map: material.diffuse_map.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.base_color.a < 1.0
+    }
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        PbrMaterial {
+            name: String::new(),
+            base_color: Color::WHITE,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Color::BLACK,
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            normal_scale: 1.0,
+            occlusion_map: None,
+            occlusion_strength: 1.0,
+            emissive_map: None,
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let channels = value
+        .split_whitespace()
+        .map(|channel| channel.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match channels[..] {
+        [r, g, b] => Some(Color::new(r, g, b, 1.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Material {
+        let (materials, _) =
+            tobj::load_mtl_buf(&mut source.as_bytes()).unwrap();
+
+        Material::from_mtl(&materials[0], Path::new("assets"))
+    }
+
+    #[test]
+    fn parse_mtl() {
+        let material = parse(
+            "newmtl shiny\n\
+             Ns 250.0\n\
+             Ka 0.1 0.1 0.1\n\
+             Kd 0.8 0.2 0.2\n\
+             Ks 0.5 0.5 0.5\n\
+             Ke 0.0 1.0 0.5\n\
+             d 0.25\n",
+        );
+
+        assert_eq!(material.name, "shiny");
+        assert_eq!(material.ambient, Color::new(0.1, 0.1, 0.1, 1.0));
+        assert_eq!(material.diffuse, Color::new(0.8, 0.2, 0.2, 1.0));
+        assert_eq!(material.specular, Color::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(material.emissive, Color::new(0.0, 1.0, 0.5, 1.0));
+        assert_eq!(material.shininess, 250.0);
+        assert_eq!(material.opacity, 0.25);
+        assert!(material.is_transparent());
+        assert_eq!(material.diffuse_map, None);
+    }
+
+    #[test]
+    fn texture_paths() {
+        let material = parse(
+            "newmtl textured\n\
+             map_Kd textures/albedo.png\n\
+             map_Ks spec.png\n",
+        );
+
+        assert_eq!(
+            material.diffuse_map,
+            Some(TextureMap::File(PathBuf::from(
+                "assets/textures/albedo.png"
+            )))
+        );
+        assert_eq!(
+            material.specular_map,
+            Some(TextureMap::File(PathBuf::from("assets/spec.png")))
+        );
+        assert_eq!(material.emissive, Color::BLACK);
+        assert!(!material.is_transparent());
+    }
+
+    #[test]
+    fn pbr_from_phong() {
+        let material = parse(
+            "newmtl glossy\n\
+             Ns 30.0\n\
+             Kd 0.8 0.2 0.2\n\
+             d 0.5\n\
+             map_Kd albedo.png\n",
+        );
+        let pbr = PbrMaterial::from_phong(&material);
+
+        assert_eq!(pbr.name, "glossy");
+        assert_eq!(pbr.base_color, Color::new(0.8, 0.2, 0.2, 0.5));
+        assert_eq!(pbr.metallic, 0.0);
+        assert_eq!(pbr.roughness, 0.5);
+        assert_eq!(pbr.base_color_map, material.diffuse_map);
+        assert!(pbr.is_transparent());
+        assert!(!PbrMaterial::default().is_transparent());
+    }
+}
@@ -0,0 +1,433 @@
+use std::path::Path;
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use cgmath::{One, Point3, Quaternion, Vector3};
+
+use super::{
+    morph::MorphDeltas, Aabb, AnimationClip, BoundingSphere, Buffer,
+    CommandBuffer, Material, MorphAnimation, MorphTarget, PbrMaterial, Render,
+    ShaderAttribute, ShaderAttributeType, ShaderLayout, Skeleton,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// Joints deforming a vertex of a skinned mesh, kept in a second vertex
+/// buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct SkinVertex {
+    /// Indices into the joint matrices of the skin
+    pub joints: [u32; 4],
+    /// Adding up to 1
+    pub weights: [f32; 4],
+}
+
+/// Indexed triangle list living on the GPU
+pub struct Mesh {
+    vertices: Buffer,
+    vertex_count: u32,
+    indices: Buffer,
+    index_count: u32,
+    material: Option<usize>,
+    skin: Option<Buffer>,
+    morph: Option<MorphDeltas>,
+    /// Of the vertices at rest, ignoring joints and morph targets
+    bounds: Aabb,
+    sphere: BoundingSphere,
+}
+
+/// A mesh as one node draws it, see `SceneRenderer` and `ShadowMaps`
+#[derive(Clone, Copy)]
+pub struct MeshInstance<'a> {
+    pub mesh: &'a Mesh,
+    /// Replaces the vertices of the mesh, e.g. with its morph targets
+    /// blended by a `MorphInstance`
+    pub vertices: Option<&'a Buffer>,
+    /// Offset of the node's uniforms into the object buffer
+    pub offset: u32,
+    /// Whether the node has joint matrices posing a skinned mesh
+    pub posed: bool,
+}
+
+/// Meshes of a model file along with its materials and the nodes placing
+/// the meshes
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    /// `Mesh::material` indexes into this
+    pub materials: Vec<Material>,
+    /// Metallic-roughness versions of `materials`, in the same order
+    pub pbr_materials: Vec<PbrMaterial>,
+    /// Parents come before their children
+    pub nodes: Vec<ModelNode>,
+    /// Skeletons of the skinned meshes, `ModelNode::skin` indexes into this
+    pub skeletons: Vec<Skeleton>,
+    pub animations: Vec<AnimationClip>,
+    pub morph_animations: Vec<MorphAnimation>,
+}
+
+/// Node of the hierarchy of a model file, placed relative to its parent
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelNode {
+    pub name: String,
+    /// Index into `Model::nodes`, `None` at the top of the hierarchy
+    pub parent: Option<usize>,
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+    /// Indices into `Model::meshes` drawn at the node
+    pub meshes: Vec<usize>,
+    /// Index into `Model::skeletons` deforming the meshes, if skinned
+    pub skin: Option<usize>,
+    /// Weights of the morph targets of the meshes, empty without any
+    pub weights: Vec<f32>,
+}
+
+impl ModelNode {
+    /// Node at the top of the hierarchy, drawing `meshes` untransformed
+    pub fn root(name: &str, meshes: Vec<usize>) -> ModelNode {
+        ModelNode {
+            name: name.to_string(),
+            parent: None,
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            meshes,
+            skin: None,
+            weights: Vec::new(),
+        }
+    }
+}
+
+impl Mesh {
+    pub fn new(
+        render: &Render,
+        vertices: &[MeshVertex],
+        indices: &[u16],
+    ) -> Mesh {
+        let bounds = Aabb::from_points(
+            vertices.iter().map(|vertex| Point3::from(vertex.position)),
+        );
+
+        // Compute shaders read the vertices to blend morph targets
+        Mesh {
+            vertices: render.create_vertex_storage_buffer(cast_slice(vertices)),
+            vertex_count: vertices.len() as u32,
+            indices: render.create_index_buffer(cast_slice(indices)),
+            index_count: indices.len() as u32,
+            material: None,
+            skin: None,
+            morph: None,
+            bounds,
+            sphere: BoundingSphere::from_aabb(&bounds),
+        }
+    }
+
+    /// Converts a mesh loaded with `single_index` set. Smooth normals are
+    /// generated when the file has none. tobj splits objects at each
+    /// `usemtl`, so every mesh has a single material. Fails for meshes with
+    /// more vertices than 16-bit indices reach
+    pub fn from_obj(
+        render: &Render,
+        mesh: &tobj::Mesh,
+    ) -> Result<Mesh, String> {
+        let count = mesh.positions.len() / 3;
+        if count > u16::MAX as usize + 1 {
+            return Err(format!(
+                "A mesh has {} vertices, more than 16-bit indices reach",
+                count
+            ));
+        }
+        let indices =
+            mesh.indices.iter().map(|x| *x as u16).collect::<Vec<_>>();
+        let normals = if mesh.normals.is_empty() {
+            compute_normals(&mesh.positions, &mesh.indices)
+        } else {
+            mesh.normals.clone()
+        };
+
+        let vertices = (0..count)
+            .map(|i| MeshVertex {
+                position: [
+                    mesh.positions[3 * i],
+                    mesh.positions[3 * i + 1],
+                    mesh.positions[3 * i + 2],
+                ],
+                normal: [
+                    normals[3 * i],
+                    normals[3 * i + 1],
+                    normals[3 * i + 2],
+                ],
+                uv: if mesh.texcoords.is_empty() {
+                    [0.0, 0.0]
+                } else {
+                    // OBJ puts the origin of texture space at the bottom
+                    [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]]
+                },
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Mesh {
+            material: mesh.material_id,
+            ..Mesh::new(render, &vertices, &indices)
+        })
+    }
+
+    pub fn with_material(self, material: Option<usize>) -> Mesh {
+        Mesh { material, ..self }
+    }
+
+    /// Makes the mesh skinned, `skin` holding the joints of each vertex
+    pub fn with_skin(self, render: &Render, skin: &[SkinVertex]) -> Mesh {
+        Mesh {
+            skin: Some(render.create_vertex_buffer(cast_slice(skin))),
+            ..self
+        }
+    }
+
+    pub fn is_skinned(&self) -> bool {
+        self.skin.is_some()
+    }
+
+    /// Gives the mesh blend shapes, with a delta for each of its vertices
+    pub fn with_morph_targets(
+        self,
+        render: &Render,
+        targets: &[MorphTarget],
+    ) -> Mesh {
+        Mesh {
+            morph: MorphDeltas::new(
+                render,
+                self.vertex_count as usize,
+                targets,
+            ),
+            ..self
+        }
+    }
+
+    pub(super) fn morph_deltas(&self) -> Option<&MorphDeltas> {
+        self.morph.as_ref()
+    }
+
+    pub(super) fn vertices(&self) -> &Buffer {
+        &self.vertices
+    }
+
+    pub(super) fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// Box around the vertices at rest, in model space
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    /// Sphere around the vertices at rest, in model space
+    pub fn bounding_sphere(&self) -> &BoundingSphere {
+        &self.sphere
+    }
+
+    /// Index of the material the mesh was assigned in its file
+    pub fn material(&self) -> Option<usize> {
+        self.material
+    }
+
+    /// Vertex layout of `MeshVertex`: position, normal and uv at locations
+    /// 0, 1 and 2
+    pub fn layout(render: &Render) -> ShaderLayout<3> {
+        render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::Vec3, 0),
+            ShaderAttribute::new(ShaderAttributeType::Vec3, 1),
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 2),
+        ])
+    }
+
+    /// Vertex layout of `SkinVertex`, in the second slot of skinned
+    /// pipelines: joints and weights at locations 3 and 4
+    pub fn skin_layout(render: &Render) -> ShaderLayout<2> {
+        render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::UVec4, 3),
+            ShaderAttribute::new(ShaderAttributeType::Vec4, 4),
+        ])
+    }
+
+    /// Records the draw into the current pass, whose pipeline must use
+    /// `Mesh::layout`, followed by `Mesh::skin_layout` for skinned meshes
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+    ) -> CommandBuffer<'a> {
+        self.draw_with_vertices(commands, &self.vertices)
+    }
+
+    /// Same as `draw` with other vertices laid out as `Mesh::layout`, one
+    /// for each vertex of the mesh
+    pub fn draw_with_vertices<'a>(
+        &'a self,
+        mut commands: CommandBuffer<'a>,
+        vertices: &'a Buffer,
+    ) -> CommandBuffer<'a> {
+        if let Some(ref skin) = self.skin {
+            commands = commands.set_vertices_at(1, skin);
+        }
+
+        commands
+            .set_vertices(vertices)
+            .set_indices(&self.indices)
+            .draw(0..self.index_count)
+    }
+}
+
+impl<'a> MeshInstance<'a> {
+    /// Whether to draw with a pipeline deforming the mesh by the joint
+    /// matrices of the node
+    pub fn is_skinned(&self) -> bool {
+        self.posed && self.mesh.is_skinned()
+    }
+
+    pub fn draw(&self, commands: CommandBuffer<'a>) -> CommandBuffer<'a> {
+        match self.vertices {
+            Some(vertices) => self.mesh.draw_with_vertices(commands, vertices),
+            None => self.mesh.draw(commands),
+        }
+    }
+}
+
+/// Loads every object of an OBJ file, triangulated and with a single index
+/// per vertex, and the materials they use. OBJ has no hierarchy, each
+/// object gets a node of its own
+pub fn load_obj<P: AsRef<Path>>(
+    render: &Render,
+    path: P,
+) -> Result<Model, String> {
+    let path = path.as_ref();
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|err| format!("Failed to load {}: {}", path.display(), err))?;
+    let materials = materials.map_err(|err| {
+        format!(
+            "Failed to load the materials of {}: {}",
+            path.display(),
+            err
+        )
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let materials = materials
+        .iter()
+        .map(|material| Material::from_mtl(material, dir))
+        .collect::<Vec<_>>();
+
+    Ok(Model {
+        meshes: models
+            .iter()
+            .map(|model| {
+                Mesh::from_obj(render, &model.mesh).map_err(|err| {
+                    format!("Failed to load {}: {}", path.display(), err)
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        pbr_materials: materials.iter().map(PbrMaterial::from_phong).collect(),
+        materials,
+        nodes: models
+            .iter()
+            .enumerate()
+            .map(|(i, model)| ModelNode::root(&model.name, vec![i]))
+            .collect(),
+        skeletons: Vec::new(),
+        animations: Vec::new(),
+        morph_animations: Vec::new(),
+    })
+}
+
+/// Averages the normals of the triangles sharing each vertex, weighted by
+/// their area
+pub(super) fn compute_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
+    let mut normals = vec![0.0_f32; positions.len()];
+    let position = |i: u32| {
+        let i = i as usize * 3;
+        [positions[i], positions[i + 1], positions[i + 2]]
+    };
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [
+            position(triangle[0]),
+            position(triangle[1]),
+            position(triangle[2]),
+        ];
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let normal = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+
+        for index in triangle {
+            let i = *index as usize * 3;
+            for axis in 0..3 {
+                normals[i + axis] += normal[axis];
+            }
+        }
+    }
+
+    for normal in normals.chunks_exact_mut(3) {
+        let length = (normal[0] * normal[0]
+            + normal[1] * normal[1]
+            + normal[2] * normal[2])
+            .sqrt();
+        if length > 0.0 {
+            normal.iter_mut().for_each(|x| *x /= length);
+        }
+    }
+
+    normals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normals_of_a_quad() {
+        let positions = [
+            0.0, 0.0, 0.0, //
+            1.0, 0.0, 0.0, //
+            1.0, 1.0, 0.0, //
+            0.0, 1.0, 0.0,
+        ];
+        let normals = compute_normals(&positions, &[0, 1, 2, 0, 2, 3]);
+
+        for normal in normals.chunks_exact(3) {
+            assert_eq!(normal, [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn normals_are_averaged() {
+        // Two faces of a cube meeting at the edge between vertices 0 and 1
+        let positions = [
+            0.0, 0.0, 0.0, //
+            1.0, 0.0, 0.0, //
+            1.0, 1.0, 0.0, //
+            0.0, 0.0, 1.0,
+        ];
+        let normals = compute_normals(&positions, &[0, 1, 2, 0, 1, 3]);
+        let half = 0.5_f32.sqrt();
+
+        assert!((normals[0] - 0.0).abs() < 1e-6);
+        assert!((normals[1] + half).abs() < 1e-6);
+        assert!((normals[2] - half).abs() < 1e-6);
+        assert_eq!(&normals[6..9], [0.0, 0.0, 1.0]);
+    }
+}
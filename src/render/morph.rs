@@ -0,0 +1,282 @@
+use std::mem::size_of;
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use wgpu::{BindGroup, BindGroupLayout, ShaderStages};
+
+use super::{
+    animation::{hermite, keyframe},
+    mesh::MeshVertex,
+    Binding, BindingLayout, Buffer, CommandBuffer, ComputePipeline,
+    Interpolation, Mesh, Render,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Blend shape of a mesh: offsets added to its vertices in proportion to
+/// the weight of the target
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget {
+    /// One per vertex of the mesh
+    pub positions: Vec<[f32; 3]>,
+    /// One per vertex of the mesh, or empty to leave the normals alone
+    pub normals: Vec<[f32; 3]>,
+}
+
+/// Keyframed weights of the morph targets of a node's meshes
+#[derive(Clone, Debug, PartialEq)]
+pub struct MorphAnimation {
+    pub name: String,
+    /// Index into `Model::nodes`
+    pub node: usize,
+    pub interpolation: Interpolation,
+    /// Increasing times of the keyframes, in seconds
+    pub times: Vec<f32>,
+    /// Weight of every target, for each time, or in-tangents, weights and
+    /// out-tangents with `Interpolation::CubicSpline`
+    pub weights: Vec<Vec<f32>>,
+}
+
+impl MorphAnimation {
+    /// Time of the last keyframe, in seconds
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    /// Weights at `time`, holding the first or last keyframe outside of
+    /// them. Empty without keyframes
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let (from, to, t) = match keyframe(&self.times, time) {
+            Some(keyframe) => keyframe,
+            None => return Vec::new(),
+        };
+        let t = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::CubicSpline => {
+                let span = self.times[to] - self.times[from];
+                let [start, out, end, into] = hermite(t);
+                let weights = |i: usize| self.weights[i].iter();
+
+                return weights(3 * from + 1)
+                    .zip(weights(3 * from + 2))
+                    .zip(weights(3 * to + 1).zip(weights(3 * to)))
+                    .map(|((value, out_tangent), (next, in_tangent))| {
+                        value * start
+                            + out_tangent * out * span
+                            + next * end
+                            + in_tangent * into * span
+                    })
+                    .collect();
+            }
+        };
+
+        self.weights[from]
+            .iter()
+            .zip(self.weights[to].iter())
+            .map(|(from, to)| from + (to - from) * t)
+            .collect()
+    }
+}
+
+/// Deltas of the morph targets of a `Mesh`, as `morph.wgsl` reads them
+pub(super) struct MorphDeltas {
+    pub buffer: Buffer,
+    pub target_count: u32,
+}
+
+impl MorphDeltas {
+    /// `None` without targets. Missing deltas are zero
+    pub fn new(
+        render: &Render,
+        vertex_count: usize,
+        targets: &[MorphTarget],
+    ) -> Option<MorphDeltas> {
+        if targets.is_empty() {
+            return None;
+        }
+
+        let mut deltas = vec![[0.0f32; 4]; 2 * vertex_count * targets.len()];
+        for (t, target) in targets.iter().enumerate() {
+            let first = 2 * t * vertex_count;
+            for (v, [x, y, z]) in
+                target.positions.iter().take(vertex_count).enumerate()
+            {
+                deltas[first + 2 * v] = [*x, *y, *z, 0.0];
+            }
+            for (v, [x, y, z]) in
+                target.normals.iter().take(vertex_count).enumerate()
+            {
+                deltas[first + 2 * v + 1] = [*x, *y, *z, 0.0];
+            }
+        }
+
+        Some(MorphDeltas {
+            buffer: render.create_storage_buffer_with(cast_slice(&deltas)),
+            target_count: targets.len() as u32,
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct MorphParams {
+    vertex_count: u32,
+    target_count: u32,
+    _padding: [u32; 2],
+}
+
+/// Blends the morph targets of meshes on the GPU. Every instance gets a
+/// vertex buffer of its own, drawn in place of the vertices of the mesh,
+/// so the same mesh can take a different shape at each node
+pub struct Morpher {
+    pipeline: ComputePipeline,
+    layout: BindGroupLayout,
+}
+
+/// Vertices of a morphed mesh under the weights of one instance
+pub struct MorphInstance {
+    /// Index of the mesh the instance was created for
+    pub mesh: usize,
+    vertices: Buffer,
+    weights: Buffer,
+    _params: Buffer,
+    bind_group: BindGroup,
+    vertex_count: u32,
+    target_count: u32,
+}
+
+impl Morpher {
+    pub fn new(render: &Render) -> Morpher {
+        let layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Storage { read_only: true },
+                BindingLayout::Storage { read_only: true },
+                BindingLayout::Storage { read_only: true },
+                BindingLayout::Storage { read_only: false },
+            ],
+            ShaderStages::COMPUTE,
+        );
+        let shader = render.create_wgsl_shader_with_entries(
+            include_str!("../../shaders/morph.wgsl"),
+            "cs_main",
+            None,
+        );
+        let pipeline =
+            render.create_compute_pipeline(&shader, "cs_main", &[&layout]);
+
+        Morpher { pipeline, layout }
+    }
+
+    /// Instance of `mesh`, the one at `index` in the meshes it is drawn
+    /// with, at rest until `MorphInstance::set_weights`. `None` if the mesh
+    /// has no morph targets
+    pub fn create_instance(
+        &self,
+        render: &Render,
+        index: usize,
+        mesh: &Mesh,
+    ) -> Option<MorphInstance> {
+        let deltas = mesh.morph_deltas()?;
+        let vertex_count = mesh.vertex_count();
+        let vertices =
+            render.create_vertex_storage_buffer(&vec![
+                0;
+                vertex_count as usize
+                    * size_of::<MeshVertex>(
+                    )
+            ]);
+        let weights = render.create_storage_buffer(
+            deltas.target_count as u64 * size_of::<f32>() as u64,
+        );
+        let params = render.create_uniforms(&MorphParams {
+            vertex_count,
+            target_count: deltas.target_count,
+            _padding: [0; 2],
+        });
+        let bind_group = render.create_bind_group_with(
+            &self.layout,
+            &[
+                Binding::Buffer(&params),
+                Binding::Buffer(mesh.vertices()),
+                Binding::Buffer(&deltas.buffer),
+                Binding::Buffer(&weights),
+                Binding::Buffer(&vertices),
+            ],
+        );
+
+        Some(MorphInstance {
+            mesh: index,
+            vertices,
+            weights,
+            _params: params,
+            bind_group,
+            vertex_count,
+            target_count: deltas.target_count,
+        })
+    }
+
+    /// Blends the vertices of every instance, in compute passes recorded
+    /// before the render passes drawing them
+    pub fn dispatch<'a, I>(
+        &self,
+        mut commands: CommandBuffer<'a>,
+        instances: I,
+    ) -> CommandBuffer<'a>
+    where
+        I: IntoIterator<Item = &'a MorphInstance>,
+    {
+        commands = commands.label("morph");
+        for instance in instances {
+            let groups = instance.vertex_count.div_ceil(WORKGROUP_SIZE);
+            commands = commands.dispatch(
+                &self.pipeline,
+                &[&instance.bind_group],
+                [groups, 1, 1],
+            );
+        }
+
+        commands
+    }
+}
+
+impl MorphInstance {
+    /// Uploads the weight of each target. Missing weights are zero, extra
+    /// ones are ignored
+    pub fn set_weights(&self, render: &Render, weights: &[f32]) {
+        let mut data = weights.to_vec();
+        data.resize(self.target_count as usize, 0.0);
+        render.write_buffer(&self.weights, cast_slice(&data));
+    }
+
+    /// Blended vertices, laid out as `Mesh::layout`
+    pub fn vertices(&self) -> &Buffer {
+        &self.vertices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_weights() {
+        let animation = MorphAnimation {
+            name: "blink".to_string(),
+            node: 0,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0, 2.0],
+            weights: vec![vec![0.0, 1.0], vec![1.0, 0.0], vec![0.0, 0.0]],
+        };
+        assert_eq!(animation.duration(), 2.0);
+        assert_eq!(animation.sample(0.25), [0.25, 0.75]);
+        assert_eq!(animation.sample(1.5), [0.5, 0.0]);
+        assert_eq!(animation.sample(-1.0), [0.0, 1.0]);
+
+        let step = MorphAnimation {
+            interpolation: Interpolation::Step,
+            ..animation
+        };
+        assert_eq!(step.sample(0.75), [0.0, 1.0]);
+    }
+}
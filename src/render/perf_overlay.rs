@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{
+    Color, CullStats, DrawStats, MemoryUsage, TextRenderer, TextStyle,
+};
+
+/// Frames the graph shows
+const HISTORY: usize = 120;
+/// Seconds the frame rate is averaged over
+const FPS_PERIOD: f32 = 0.5;
+/// Weight of the newest frame in the averaged stage times
+const SMOOTHING: f32 = 0.1;
+const WIDTH: f32 = 260.0;
+const GRAPH_HEIGHT: f32 = 48.0;
+/// Frame time reaching the top of the graph, in milliseconds
+const GRAPH_MAX_MS: f32 = 50.0;
+const MARGIN: f32 = 8.0;
+
+/// Time spent in a named part of the frame, in milliseconds
+struct Timing {
+    name: String,
+    current: f32,
+    average: f32,
+}
+
+/// Panel of frame statistics drawn with a `TextRenderer`: a graph of the
+/// last frame times, the frame rate, CPU and GPU time per stage, draw
+/// counts and memory usage. Fed once per frame by the application
+pub struct PerfOverlay {
+    pub visible: bool,
+    /// Milliseconds, oldest first
+    frame_times: VecDeque<f32>,
+    fps: f32,
+    period: (f32, u32),
+    cpu: Vec<Timing>,
+    gpu: Vec<Timing>,
+    draws: DrawStats,
+    /// Only shown once given
+    cull: Option<CullStats>,
+    memory: MemoryUsage,
+}
+
+impl PerfOverlay {
+    pub fn new() -> PerfOverlay {
+        PerfOverlay {
+            visible: true,
+            frame_times: VecDeque::with_capacity(HISTORY),
+            fps: 0.0,
+            period: (0.0, 0),
+            cpu: Vec::new(),
+            gpu: Vec::new(),
+            draws: DrawStats::default(),
+            cull: None,
+            memory: MemoryUsage::default(),
+        }
+    }
+
+    /// Shows or hides the overlay, returning whether it is now visible
+    pub fn toggle(&mut self) -> bool {
+        self.visible = !self.visible;
+        self.visible
+    }
+
+    /// Frames per second averaged over the last half second
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Starts a frame, `dt` seconds after the previous one started. The
+    /// stage times of the previous frame go into their averages
+    pub fn begin_frame(&mut self, dt: f32) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt * 1000.0);
+
+        let (elapsed, frames) = &mut self.period;
+        *elapsed += dt;
+        *frames += 1;
+        if *elapsed >= FPS_PERIOD {
+            self.fps = *frames as f32 / *elapsed;
+            self.period = (0.0, 0);
+        }
+
+        for timing in &mut self.cpu {
+            timing.average += (timing.current - timing.average) * SMOOTHING;
+            timing.current = 0.0;
+        }
+    }
+
+    /// Adds `time` to the CPU time of the stage `name` in this frame.
+    /// Stages are listed in the order they were first reported
+    pub fn cpu_stage(&mut self, name: &str, time: Duration) {
+        let ms = time.as_secs_f32() * 1000.0;
+        match self.cpu.iter_mut().find(|timing| timing.name == name) {
+            Some(timing) => timing.current += ms,
+            None => self.cpu.push(Timing {
+                name: name.to_string(),
+                current: ms,
+                average: ms,
+            }),
+        }
+    }
+
+    /// GPU time of each pass of a frame, in milliseconds, replacing the
+    /// ones of the previous frame
+    pub fn set_gpu_timings(&mut self, timings: &[(String, f32)]) {
+        let previous = std::mem::take(&mut self.gpu);
+        self.gpu = timings
+            .iter()
+            .map(|(name, ms)| {
+                let average = previous
+                    .iter()
+                    .find(|timing| timing.name == *name)
+                    .map_or(*ms, |timing| {
+                        timing.average + (ms - timing.average) * SMOOTHING
+                    });
+                Timing {
+                    name: name.clone(),
+                    current: *ms,
+                    average,
+                }
+            })
+            .collect();
+    }
+
+    pub fn set_draw_stats(&mut self, draws: DrawStats) {
+        self.draws = draws;
+    }
+
+    pub fn set_cull_stats(&mut self, cull: CullStats) {
+        self.cull = Some(cull);
+    }
+
+    pub fn set_memory_usage(&mut self, memory: MemoryUsage) {
+        self.memory = memory;
+    }
+
+    /// Lines of text under the graph
+    fn lines(&self) -> Vec<String> {
+        let timings = |title: &str, timings: &[Timing]| {
+            let total =
+                timings.iter().map(|timing| timing.average).sum::<f32>();
+            let mut lines = vec![format!("{:<12}{:>7.2} ms", title, total)];
+            lines.extend(timings.iter().map(|timing| {
+                format!("  {:<10}{:>7.2} ms", timing.name, timing.average)
+            }));
+            lines
+        };
+
+        let frame = self.frame_times.back().copied().unwrap_or(0.0);
+        let mut lines = vec![format!("{:.1} fps  {:.2} ms", self.fps, frame)];
+        lines.extend(timings("CPU", &self.cpu));
+        if !self.gpu.is_empty() {
+            lines.extend(timings("GPU", &self.gpu));
+        }
+        lines.push(format!(
+            "{} draws  {} triangles",
+            self.draws.draw_calls,
+            format_count(self.draws.triangles)
+        ));
+        lines.push(format!("{} dispatches", self.draws.dispatches));
+        if let Some(cull) = self.cull {
+            lines.push(format!(
+                "{} objects drawn  {} culled",
+                cull.drawn, cull.culled
+            ));
+        }
+        lines.push(format!(
+            "buffers {}  textures {}",
+            format_bytes(self.memory.buffers),
+            format_bytes(self.memory.textures)
+        ));
+
+        lines
+    }
+
+    /// Queues the panel into `text` with its top left corner at
+    /// `position`, in pixels. Nothing while hidden
+    pub fn draw(&self, text: &mut TextRenderer, position: [f32; 2]) {
+        if !self.visible {
+            return;
+        }
+
+        let lines = self.lines().join("\n");
+        let [_, text_height] = text.font().measure(&lines, 1.0);
+        let [x, y] = position;
+        let height = 3.0 * MARGIN + GRAPH_HEIGHT + text_height;
+        text.rect([x, y], [WIDTH, height], Color::new(0.0, 0.0, 0.0, 0.6));
+
+        // One bar per frame, the newest on the right
+        let bar_width = (WIDTH - 2.0 * MARGIN) / HISTORY as f32;
+        let bottom = y + MARGIN + GRAPH_HEIGHT;
+        let first = HISTORY - self.frame_times.len();
+        for (i, ms) in self.frame_times.iter().enumerate() {
+            let bar = (ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT;
+            text.rect(
+                [x + MARGIN + (first + i) as f32 * bar_width, bottom - bar],
+                [bar_width, bar],
+                frame_color(*ms),
+            );
+        }
+        // 60 fps mark
+        let mark = 1000.0 / 60.0 / GRAPH_MAX_MS * GRAPH_HEIGHT;
+        text.rect(
+            [x + MARGIN, bottom - mark],
+            [WIDTH - 2.0 * MARGIN, 1.0],
+            Color::new(1.0, 1.0, 1.0, 0.3),
+        );
+
+        text.text(&lines, [x + MARGIN, bottom + MARGIN], &TextStyle::default());
+    }
+}
+
+impl Default for PerfOverlay {
+    fn default() -> Self {
+        PerfOverlay::new()
+    }
+}
+
+/// Green for frames within 60 fps, yellow within 30, red beyond
+fn frame_color(ms: f32) -> Color {
+    if ms <= 1000.0 / 60.0 {
+        Color::new(0.1, 0.8, 0.2, 1.0)
+    } else if ms <= 1000.0 / 30.0 {
+        Color::new(0.9, 0.8, 0.1, 1.0)
+    } else {
+        Color::new(0.9, 0.15, 0.1, 1.0)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_count(count: u64) -> String {
+    match count {
+        0..=9_999 => count.to_string(),
+        10_000..=999_999 => format!("{:.1}k", count as f64 / 1e3),
+        _ => format!("{:.2}M", count as f64 / 1e6),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rate_and_stages() {
+        let mut overlay = PerfOverlay::new();
+        for _ in 0..HISTORY + 10 {
+            overlay.begin_frame(0.02);
+            overlay.cpu_stage("update", Duration::from_millis(2));
+            overlay.cpu_stage("draw", Duration::from_millis(1));
+            overlay.cpu_stage("update", Duration::from_millis(1));
+        }
+
+        assert_eq!(overlay.frame_times.len(), HISTORY);
+        assert!((overlay.fps() - 50.0).abs() < 0.1);
+        assert_eq!(overlay.cpu.len(), 2);
+        assert_eq!(overlay.cpu[0].name, "update");
+        assert!((overlay.cpu[0].average - 3.0).abs() < 1e-3);
+
+        let lines = overlay.lines();
+        assert_eq!(lines[0], "50.0 fps  20.00 ms");
+        assert_eq!(lines[1], "CPU            4.00 ms");
+        assert_eq!(lines[2], "  update       3.00 ms");
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+        assert_eq!(format_count(1234), "1234");
+        assert_eq!(format_count(12_345), "12.3k");
+        assert_eq!(format_count(2_500_000), "2.50M");
+    }
+}
@@ -1,4 +1,13 @@
-use wgpu::RenderPipeline;
+use wgpu::{
+    BindGroupLayout, BlendState, ColorTargetState, ColorWrites,
+    CompareFunction, ComputePipeline as WgpuComputePipeline,
+    ComputePipelineDescriptor, DepthBiasState, DepthStencilState, Face,
+    FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, TextureFormat,
+    VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use super::{Render, Shader, ShaderLayout};
 
 pub struct Pipeline {
     pipeline: RenderPipeline,
@@ -13,3 +22,233 @@ impl<'a> Pipeline {
         &self.pipeline
     }
 }
+
+/// Runs one compute entry point, see `CommandBuffer::dispatch`
+pub struct ComputePipeline {
+    pipeline: WgpuComputePipeline,
+}
+
+impl<'a> ComputePipeline {
+    /// Pipeline running `entry_point` of `shader` with `bind_group_layouts`
+    /// bound in order
+    pub fn new(
+        render: &Render,
+        shader: &Shader,
+        entry_point: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> ComputePipeline {
+        let device = &render.device;
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline =
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                module: shader.module(),
+                entry_point,
+            });
+
+        ComputePipeline { pipeline }
+    }
+
+    pub fn pipeline(&'a self) -> &'a WgpuComputePipeline {
+        &self.pipeline
+    }
+}
+
+/// Describes a pipeline piece by piece. Without any `target`, the pipeline
+/// renders to the swapchain format
+pub struct PipelineBuilder<'a> {
+    render: &'a Render,
+    shader: &'a Shader,
+    vertex_layouts: Vec<(u64, Vec<VertexAttribute>, VertexStepMode)>,
+    bind_group_layouts: Vec<&'a BindGroupLayout>,
+    targets: Vec<ColorTargetState>,
+    depth_stencil: Option<DepthStencilState>,
+    primitive: PrimitiveState,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    pub fn new(render: &'a Render, shader: &'a Shader) -> PipelineBuilder<'a> {
+        PipelineBuilder {
+            render,
+            shader,
+            vertex_layouts: Vec::new(),
+            bind_group_layouts: Vec::new(),
+            targets: Vec::new(),
+            depth_stencil: None,
+            primitive: PrimitiveState::default(),
+        }
+    }
+
+    /// Adds a vertex buffer slot, in the order `set_vertices` binds them
+    pub fn vertex_layout<const T: usize>(
+        mut self,
+        layout: &ShaderLayout<T>,
+    ) -> Self {
+        let (stride, attrs) = layout.wgpu_attributes();
+        self.vertex_layouts.push((
+            stride as u64,
+            attrs.to_vec(),
+            VertexStepMode::Vertex,
+        ));
+
+        self
+    }
+
+    /// Adds a vertex buffer slot advancing once per instance instead of once
+    /// per vertex
+    pub fn instance_layout<const T: usize>(
+        mut self,
+        layout: &ShaderLayout<T>,
+    ) -> Self {
+        let (stride, attrs) = layout.wgpu_attributes();
+        self.vertex_layouts.push((
+            stride as u64,
+            attrs.to_vec(),
+            VertexStepMode::Instance,
+        ));
+
+        self
+    }
+
+    /// Adds a bind group layout, in the order `bind_resources_at` uses
+    pub fn bind_group_layout(mut self, layout: &'a BindGroupLayout) -> Self {
+        self.bind_group_layouts.push(layout);
+
+        self
+    }
+
+    /// Skips triangles facing away from (`Face::Back`) or towards
+    /// (`Face::Front`) the camera. Nothing is culled by default
+    pub fn cull_mode(mut self, face: Face) -> Self {
+        self.primitive.cull_mode = Some(face);
+
+        self
+    }
+
+    /// How vertices are assembled into primitives. Triangle lists by
+    /// default
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+
+        self
+    }
+
+    pub fn target(self, format: TextureFormat) -> Self {
+        self.blended_target(format, None)
+    }
+
+    pub fn blended_target(
+        mut self,
+        format: TextureFormat,
+        blend: Option<BlendState>,
+    ) -> Self {
+        self.targets.push(ColorTargetState {
+            format,
+            blend,
+            write_mask: ColorWrites::ALL,
+        });
+
+        self
+    }
+
+    /// Tests and writes depth, keeping the closest fragments. Required to
+    /// draw into framebuffers with a depth attachment of `format`
+    pub fn depth(mut self, format: TextureFormat) -> Self {
+        self.depth_stencil = Some(DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::Less,
+            stencil: Default::default(),
+            bias: DepthBiasState::default(),
+        });
+
+        self
+    }
+
+    /// Changes how `depth` tests fragments and whether the ones passing
+    /// write their depth
+    pub fn depth_compare(
+        mut self,
+        compare: CompareFunction,
+        write: bool,
+    ) -> Self {
+        if let Some(ref mut depth_stencil) = self.depth_stencil {
+            depth_stencil.depth_compare = compare;
+            depth_stencil.depth_write_enabled = write;
+        }
+
+        self
+    }
+
+    /// Offsets the depth written by `depth`, by `constant` units plus
+    /// `slope_scale` times the depth slope of each triangle. Keeps shadow
+    /// maps from shadowing the surfaces they were rendered from
+    pub fn depth_bias(mut self, constant: i32, slope_scale: f32) -> Self {
+        if let Some(ref mut depth_stencil) = self.depth_stencil {
+            depth_stencil.bias = DepthBiasState {
+                constant,
+                slope_scale,
+                clamp: 0.0,
+            };
+        }
+
+        self
+    }
+
+    pub fn build(self) -> Pipeline {
+        let device = &self.render.device;
+        let pipeline_layout =
+            device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &self.bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        let targets = if self.targets.is_empty() {
+            vec![self.render.swapchain_format.into()]
+        } else {
+            self.targets
+        };
+        let buffers = self
+            .vertex_layouts
+            .iter()
+            .map(|(stride, attrs, step_mode)| VertexBufferLayout {
+                array_stride: *stride,
+                step_mode: *step_mode,
+                attributes: attrs,
+            })
+            .collect::<Vec<_>>();
+
+        let (vert_module, vert_entry) = self.shader.vertex();
+        let fragment =
+            self.shader
+                .fragment()
+                .map(|(module, entry_point)| FragmentState {
+                    module,
+                    entry_point,
+                    targets: &targets,
+                });
+
+        let pipeline =
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: vert_module,
+                    entry_point: vert_entry,
+                    buffers: &buffers,
+                },
+                fragment,
+                primitive: self.primitive,
+                depth_stencil: self.depth_stencil,
+                multisample: MultisampleState::default(),
+            });
+
+        Pipeline::new(pipeline)
+    }
+}
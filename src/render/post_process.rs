@@ -0,0 +1,876 @@
+use std::{fs, path::Path};
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use serde::Deserialize;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, FilterMode, ShaderStages,
+    TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, ColorSpace, CommandBuffer, Framebuffer,
+    Pipeline, Render, Sampler, Texture,
+};
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BloomConfig {
+    pub enabled: bool,
+    /// Luminance above which pixels start to glow
+    pub threshold: f32,
+    pub intensity: f32,
+    /// Spread of the blur, in texels of the half resolution target
+    pub radius: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        BloomConfig {
+            enabled: true,
+            threshold: 0.8,
+            intensity: 0.6,
+            radius: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FxaaConfig {
+    pub enabled: bool,
+    pub edge_threshold: f32,
+    pub edge_threshold_min: f32,
+}
+
+impl Default for FxaaConfig {
+    fn default() -> Self {
+        FxaaConfig {
+            enabled: true,
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct VignetteConfig {
+    pub enabled: bool,
+    pub strength: f32,
+    pub radius: f32,
+    pub softness: f32,
+}
+
+impl Default for VignetteConfig {
+    fn default() -> Self {
+        VignetteConfig {
+            enabled: true,
+            strength: 0.5,
+            radius: 0.4,
+            softness: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ColorGradingConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Lookup table image: `size` slices of `size` x `size` texels laid out
+    /// horizontally. Without one, an identity table is used
+    #[serde(default)]
+    pub lut: Option<String>,
+    #[serde(default = "ColorGradingConfig::default_strength")]
+    pub strength: f32,
+}
+
+impl ColorGradingConfig {
+    fn default_strength() -> f32 {
+        1.0
+    }
+}
+
+/// One entry of the chain, applied in the order of the config file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectConfig {
+    Bloom(BloomConfig),
+    Fxaa(FxaaConfig),
+    Vignette(VignetteConfig),
+    ColorGrading(ColorGradingConfig),
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct PostProcessConfig {
+    pub effects: Vec<EffectConfig>,
+}
+
+impl PostProcessConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PostProcessConfig, String> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|err| {
+            format!("Failed to open {}: {}", path.display(), err)
+        })?;
+
+        PostProcessConfig::from_json(&json)
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err))
+    }
+
+    pub fn from_json(json: &str) -> Result<PostProcessConfig, String> {
+        serde_json::from_str(json).map_err(|err| err.to_string())
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct BloomUniforms {
+    texel_size: [f32; 2],
+    direction: [f32; 2],
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    _padding: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct FxaaUniforms {
+    texel_size: [f32; 2],
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct VignetteUniforms {
+    strength: f32,
+    radius: f32,
+    softness: f32,
+    aspect: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ColorGradingUniforms {
+    size: f32,
+    strength: f32,
+    decode_srgb: u32,
+    _padding: u32,
+}
+
+/// Layouts and sampler shared by every effect of a chain
+struct Shared {
+    format: TextureFormat,
+    sampler: Sampler,
+    /// Uniforms, source texture, sampler
+    single_layout: BindGroupLayout,
+    /// Same as `single_layout` plus a second texture
+    dual_layout: BindGroupLayout,
+}
+
+impl Shared {
+    fn shader_source(effect: &str) -> String {
+        let source = match effect {
+            "bloom" => include_str!("../../shaders/bloom.wgsl"),
+            "fxaa" => include_str!("../../shaders/fxaa.wgsl"),
+            "vignette" => include_str!("../../shaders/vignette.wgsl"),
+            "color_grading" => include_str!("../../shaders/color_grading.wgsl"),
+            "copy" => include_str!("../../shaders/copy.wgsl"),
+            _ => unreachable!(),
+        };
+
+        format!(
+            "{}{}{}",
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/fullscreen.wgsl"),
+            source
+        )
+    }
+
+    fn pipeline(
+        &self,
+        render: &Render,
+        effect: &str,
+        frag_entry: &'static str,
+        layout: &BindGroupLayout,
+    ) -> Pipeline {
+        let shader = render.create_wgsl_shader_with_entries(
+            &Shared::shader_source(effect),
+            "vs_main",
+            Some(frag_entry),
+        );
+
+        render
+            .build_pipeline(&shader)
+            .bind_group_layout(layout)
+            .target(self.format)
+            .build()
+    }
+
+    fn single_group(
+        &self,
+        render: &Render,
+        uniforms: &Buffer,
+        source: &Framebuffer,
+    ) -> BindGroup {
+        render.create_bind_group_with(
+            &self.single_layout,
+            &[
+                Binding::Buffer(uniforms),
+                Binding::Texture(source.get_texture().unwrap()),
+                Binding::Sampler(&self.sampler),
+            ],
+        )
+    }
+
+    fn dual_group(
+        &self,
+        render: &Render,
+        uniforms: &Buffer,
+        source: &Framebuffer,
+        extra: &Texture,
+    ) -> BindGroup {
+        render.create_bind_group_with(
+            &self.dual_layout,
+            &[
+                Binding::Buffer(uniforms),
+                Binding::Texture(source.get_texture().unwrap()),
+                Binding::Sampler(&self.sampler),
+                Binding::Texture(extra),
+            ],
+        )
+    }
+}
+
+struct BloomPass {
+    config: BloomConfig,
+    bright: Pipeline,
+    blur: Pipeline,
+    composite: Pipeline,
+    bright_uniforms: Buffer,
+    blur_h_uniforms: Buffer,
+    blur_v_uniforms: Buffer,
+    composite_uniforms: Buffer,
+    /// Half resolution targets the blur ping-pongs between
+    half: [Framebuffer; 2],
+    /// Bright pass reading each of the chain targets
+    bright_groups: Vec<BindGroup>,
+    blur_h_group: BindGroup,
+    blur_v_group: BindGroup,
+    composite_groups: Vec<BindGroup>,
+}
+
+impl BloomPass {
+    fn uniforms(
+        &self,
+        texel_size: [f32; 2],
+        direction: [f32; 2],
+    ) -> BloomUniforms {
+        BloomUniforms {
+            texel_size,
+            direction,
+            threshold: self.config.threshold,
+            intensity: self.config.intensity,
+            radius: self.config.radius,
+            _padding: 0.0,
+        }
+    }
+
+    fn new(
+        render: &Render,
+        shared: &Shared,
+        config: &BloomConfig,
+        targets: &[Framebuffer; 2],
+    ) -> BloomPass {
+        let empty = BloomUniforms::zeroed();
+        let half = BloomPass::create_half_targets(render, shared, targets);
+        let bright_uniforms = render.create_uniforms(&empty);
+        let blur_h_uniforms = render.create_uniforms(&empty);
+        let blur_v_uniforms = render.create_uniforms(&empty);
+        let composite_uniforms = render.create_uniforms(&empty);
+
+        let mut pass = BloomPass {
+            config: config.clone(),
+            bright: shared.pipeline(
+                render,
+                "bloom",
+                "fs_bright",
+                &shared.single_layout,
+            ),
+            blur: shared.pipeline(
+                render,
+                "bloom",
+                "fs_blur",
+                &shared.single_layout,
+            ),
+            composite: shared.pipeline(
+                render,
+                "bloom",
+                "fs_composite",
+                &shared.dual_layout,
+            ),
+            bright_groups: Vec::new(),
+            blur_h_group: shared.single_group(
+                render,
+                &blur_h_uniforms,
+                &half[0],
+            ),
+            blur_v_group: shared.single_group(
+                render,
+                &blur_v_uniforms,
+                &half[1],
+            ),
+            composite_groups: Vec::new(),
+            bright_uniforms,
+            blur_h_uniforms,
+            blur_v_uniforms,
+            composite_uniforms,
+            half,
+        };
+        pass.resize(render, shared, targets);
+
+        pass
+    }
+
+    fn create_half_targets(
+        render: &Render,
+        shared: &Shared,
+        targets: &[Framebuffer; 2],
+    ) -> [Framebuffer; 2] {
+        let texture = targets[0].get_texture().unwrap();
+        let width = (texture.width() / 2).max(1);
+        let height = (texture.height() / 2).max(1);
+
+        [
+            render.create_offscreen_framebuffer(width, height, shared.format),
+            render.create_offscreen_framebuffer(width, height, shared.format),
+        ]
+    }
+
+    fn resize(
+        &mut self,
+        render: &Render,
+        shared: &Shared,
+        targets: &[Framebuffer; 2],
+    ) {
+        self.half = BloomPass::create_half_targets(render, shared, targets);
+
+        let full = targets[0].get_texture().unwrap();
+        let half = self.half[0].get_texture().unwrap();
+        let full_texel =
+            [1.0 / full.width() as f32, 1.0 / full.height() as f32];
+        let half_texel =
+            [1.0 / half.width() as f32, 1.0 / half.height() as f32];
+        render.write_buffer(
+            &self.bright_uniforms,
+            bytes_of(&self.uniforms(full_texel, [0.0, 0.0])),
+        );
+        render.write_buffer(
+            &self.blur_h_uniforms,
+            bytes_of(&self.uniforms(half_texel, [1.0, 0.0])),
+        );
+        render.write_buffer(
+            &self.blur_v_uniforms,
+            bytes_of(&self.uniforms(half_texel, [0.0, 1.0])),
+        );
+        render.write_buffer(
+            &self.composite_uniforms,
+            bytes_of(&self.uniforms(full_texel, [0.0, 0.0])),
+        );
+
+        self.bright_groups = targets
+            .iter()
+            .map(|target| {
+                shared.single_group(render, &self.bright_uniforms, target)
+            })
+            .collect();
+        self.blur_h_group =
+            shared.single_group(render, &self.blur_h_uniforms, &self.half[0]);
+        self.blur_v_group =
+            shared.single_group(render, &self.blur_v_uniforms, &self.half[1]);
+        self.composite_groups = targets
+            .iter()
+            .map(|target| {
+                shared.dual_group(
+                    render,
+                    &self.composite_uniforms,
+                    target,
+                    self.half[0].get_texture().unwrap(),
+                )
+            })
+            .collect();
+    }
+
+    fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        source: usize,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        commands
+            .configure_draw(&self.bright, &self.half[0])
+            .bind_resources(&self.bright_groups[source])
+            .draw_vertices(0..3)
+            .configure_draw(&self.blur, &self.half[1])
+            .bind_resources(&self.blur_h_group)
+            .draw_vertices(0..3)
+            .configure_draw(&self.blur, &self.half[0])
+            .bind_resources(&self.blur_v_group)
+            .draw_vertices(0..3)
+            .configure_draw(&self.composite, output)
+            .bind_resources(&self.composite_groups[source])
+            .draw_vertices(0..3)
+    }
+}
+
+/// Effect made of a single full-screen pass
+struct SimplePass {
+    pipeline: Pipeline,
+    uniforms: Buffer,
+    /// Extra texture bound after the sampler, e.g. a lookup table
+    texture: Option<Texture>,
+    /// One bind group reading each of the chain targets
+    bind_groups: Vec<BindGroup>,
+}
+
+impl SimplePass {
+    fn new(
+        render: &Render,
+        shared: &Shared,
+        effect: &str,
+        uniforms: Buffer,
+        texture: Option<Texture>,
+    ) -> SimplePass {
+        let layout = if texture.is_some() {
+            &shared.dual_layout
+        } else {
+            &shared.single_layout
+        };
+
+        SimplePass {
+            pipeline: shared.pipeline(render, effect, "fs_main", layout),
+            uniforms,
+            texture,
+            bind_groups: Vec::new(),
+        }
+    }
+
+    fn resize(
+        &mut self,
+        render: &Render,
+        shared: &Shared,
+        targets: &[Framebuffer; 2],
+    ) {
+        self.bind_groups = targets
+            .iter()
+            .map(|target| match self.texture {
+                Some(ref texture) => {
+                    shared.dual_group(render, &self.uniforms, target, texture)
+                }
+                None => shared.single_group(render, &self.uniforms, target),
+            })
+            .collect();
+    }
+
+    fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        source: usize,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        commands
+            .configure_draw(&self.pipeline, output)
+            .bind_resources(&self.bind_groups[source])
+            .draw_vertices(0..3)
+    }
+}
+
+enum EffectPass {
+    Bloom(Box<BloomPass>),
+    Fxaa(FxaaConfig, SimplePass),
+    Vignette(VignetteConfig, SimplePass),
+    ColorGrading(SimplePass),
+}
+
+struct Effect {
+    name: &'static str,
+    enabled: bool,
+    pass: EffectPass,
+}
+
+/// Builds an identity lookup table of `size`^3 entries in the strip layout
+/// expected by the color grading shader
+fn identity_lut(size: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+    let scale = 255.0 / (size - 1) as f32;
+
+    for g in 0..size {
+        for b in 0..size {
+            for r in 0..size {
+                data.push((r as f32 * scale).round() as u8);
+                data.push((g as f32 * scale).round() as u8);
+                data.push((b as f32 * scale).round() as u8);
+                data.push(255);
+            }
+        }
+    }
+
+    data
+}
+
+/// Sequence of full-screen effects. The scene is drawn into `input()`, then
+/// each enabled effect reads the previous result from one of two offscreen
+/// targets and writes into the other, the last one writing to the output
+/// given to `draw`. The output has to use the chain's format
+pub struct PostProcessChain {
+    shared: Shared,
+    effects: Vec<Effect>,
+    targets: [Framebuffer; 2],
+    copy: Pipeline,
+    copy_layout: BindGroupLayout,
+    copy_group: BindGroup,
+}
+
+impl PostProcessChain {
+    pub fn new(
+        render: &Render,
+        config: &PostProcessConfig,
+        format: TextureFormat,
+    ) -> Result<PostProcessChain, String> {
+        let shared = Shared {
+            format,
+            sampler: render
+                .create_sampler(FilterMode::Linear, AddressMode::ClampToEdge),
+            single_layout: render.create_bind_group_layout_with(
+                &[
+                    BindingLayout::Uniform,
+                    BindingLayout::Texture,
+                    BindingLayout::Sampler,
+                ],
+                ShaderStages::FRAGMENT,
+            ),
+            dual_layout: render.create_bind_group_layout_with(
+                &[
+                    BindingLayout::Uniform,
+                    BindingLayout::Texture,
+                    BindingLayout::Sampler,
+                    BindingLayout::Texture,
+                ],
+                ShaderStages::FRAGMENT,
+            ),
+        };
+        let targets = PostProcessChain::create_targets(render, format);
+
+        let mut effects = Vec::new();
+        for effect in config.effects.iter() {
+            effects.push(match effect {
+                EffectConfig::Bloom(config) => Effect {
+                    name: "bloom",
+                    enabled: config.enabled,
+                    pass: EffectPass::Bloom(Box::new(BloomPass::new(
+                        render, &shared, config, &targets,
+                    ))),
+                },
+                EffectConfig::Fxaa(config) => Effect {
+                    name: "fxaa",
+                    enabled: config.enabled,
+                    pass: EffectPass::Fxaa(
+                        config.clone(),
+                        SimplePass::new(
+                            render,
+                            &shared,
+                            "fxaa",
+                            render.create_uniforms(&FxaaUniforms::zeroed()),
+                            None,
+                        ),
+                    ),
+                },
+                EffectConfig::Vignette(config) => Effect {
+                    name: "vignette",
+                    enabled: config.enabled,
+                    pass: EffectPass::Vignette(
+                        config.clone(),
+                        SimplePass::new(
+                            render,
+                            &shared,
+                            "vignette",
+                            render.create_uniforms(&VignetteUniforms::zeroed()),
+                            None,
+                        ),
+                    ),
+                },
+                EffectConfig::ColorGrading(config) => {
+                    // Lookup tables hold raw values, never decode them
+                    let lut = match config.lut {
+                        Some(ref path) => {
+                            render.load_texture(path, ColorSpace::Linear)?
+                        }
+                        None => render.create_texture(
+                            16 * 16,
+                            16,
+                            &identity_lut(16),
+                            ColorSpace::Linear,
+                        ),
+                    };
+                    let size = lut.height();
+                    if lut.width() != size * size {
+                        return Err(format!(
+                            "Lookup table must be {}x{} texels",
+                            size * size,
+                            size
+                        ));
+                    }
+
+                    let uniforms =
+                        render.create_uniforms(&ColorGradingUniforms {
+                            size: size as f32,
+                            strength: config.strength,
+                            decode_srgb: format.describe().srgb as u32,
+                            _padding: 0,
+                        });
+
+                    Effect {
+                        name: "color_grading",
+                        enabled: config.enabled,
+                        pass: EffectPass::ColorGrading(SimplePass::new(
+                            render,
+                            &shared,
+                            "color_grading",
+                            uniforms,
+                            Some(lut),
+                        )),
+                    }
+                }
+            });
+        }
+
+        let copy_shader =
+            render.create_wgsl_shader(&Shared::shader_source("copy"));
+        let copy_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Texture, BindingLayout::Sampler],
+            ShaderStages::FRAGMENT,
+        );
+        let copy = render
+            .build_pipeline(&copy_shader)
+            .bind_group_layout(&copy_layout)
+            .target(format)
+            .build();
+        let copy_group = PostProcessChain::create_copy_group(
+            render,
+            &shared,
+            &copy_layout,
+            &targets,
+        );
+
+        let mut chain = PostProcessChain {
+            shared,
+            effects,
+            targets,
+            copy,
+            copy_layout,
+            copy_group,
+        };
+        chain.resize(render);
+
+        Ok(chain)
+    }
+
+    fn create_targets(
+        render: &Render,
+        format: TextureFormat,
+    ) -> [Framebuffer; 2] {
+        let (width, height) = render.size();
+
+        [
+            render.create_offscreen_framebuffer(width, height, format),
+            render.create_offscreen_framebuffer(width, height, format),
+        ]
+    }
+
+    fn create_copy_group(
+        render: &Render,
+        shared: &Shared,
+        layout: &BindGroupLayout,
+        targets: &[Framebuffer; 2],
+    ) -> BindGroup {
+        render.create_bind_group_with(
+            layout,
+            &[
+                Binding::Texture(targets[0].get_texture().unwrap()),
+                Binding::Sampler(&shared.sampler),
+            ],
+        )
+    }
+
+    /// Framebuffer the scene has to be drawn into
+    pub fn input(&self) -> &Framebuffer {
+        &self.targets[0]
+    }
+
+    /// Recreates the targets at the swapchain size. Call after
+    /// `Render::reconfigure`
+    pub fn resize(&mut self, render: &Render) {
+        self.targets =
+            PostProcessChain::create_targets(render, self.shared.format);
+        self.copy_group = PostProcessChain::create_copy_group(
+            render,
+            &self.shared,
+            &self.copy_layout,
+            &self.targets,
+        );
+
+        let (width, height) = render.size();
+        let texel_size = [1.0 / width as f32, 1.0 / height as f32];
+        for effect in self.effects.iter_mut() {
+            match effect.pass {
+                EffectPass::Bloom(ref mut pass) => {
+                    pass.resize(render, &self.shared, &self.targets)
+                }
+                EffectPass::Fxaa(ref config, ref mut pass) => {
+                    render.write_buffer(
+                        &pass.uniforms,
+                        bytes_of(&FxaaUniforms {
+                            texel_size,
+                            edge_threshold: config.edge_threshold,
+                            edge_threshold_min: config.edge_threshold_min,
+                        }),
+                    );
+                    pass.resize(render, &self.shared, &self.targets);
+                }
+                EffectPass::Vignette(ref config, ref mut pass) => {
+                    render.write_buffer(
+                        &pass.uniforms,
+                        bytes_of(&VignetteUniforms {
+                            strength: config.strength,
+                            radius: config.radius,
+                            softness: config.softness,
+                            aspect: width as f32 / height.max(1) as f32,
+                        }),
+                    );
+                    pass.resize(render, &self.shared, &self.targets);
+                }
+                EffectPass::ColorGrading(ref mut pass) => {
+                    pass.resize(render, &self.shared, &self.targets)
+                }
+            }
+        }
+    }
+
+    /// Flips effect `index` on or off, returning its name and new state
+    pub fn toggle(&mut self, index: usize) -> Option<(&'static str, bool)> {
+        let effect = self.effects.get_mut(index)?;
+        effect.enabled = !effect.enabled;
+
+        Some((effect.name, effect.enabled))
+    }
+
+    /// Records every enabled effect, ending in `output`
+    pub fn draw<'a>(
+        &'a self,
+        mut commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let enabled = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .collect::<Vec<_>>();
+
+        if enabled.is_empty() {
+            return commands
+                .label("postfx")
+                .configure_draw(&self.copy, output)
+                .bind_resources(&self.copy_group)
+                .draw_vertices(0..3);
+        }
+
+        let mut source = 0;
+        for (i, effect) in enabled.iter().enumerate() {
+            let target = if i + 1 == enabled.len() {
+                output
+            } else {
+                &self.targets[1 - source]
+            };
+
+            commands = commands.label(effect.name);
+            commands = match effect.pass {
+                EffectPass::Bloom(ref pass) => {
+                    pass.draw(commands, source, target)
+                }
+                EffectPass::Fxaa(_, ref pass)
+                | EffectPass::Vignette(_, ref pass)
+                | EffectPass::ColorGrading(ref pass) => {
+                    pass.draw(commands, source, target)
+                }
+            };
+            source = 1 - source;
+        }
+
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = PostProcessConfig::from_json(
+            r#"{
+                "effects": [
+                    { "type": "bloom", "threshold": 1.5 },
+                    { "type": "fxaa", "enabled": false },
+                    { "type": "color_grading", "lut": "assets/lut.png" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.effects,
+            vec![
+                EffectConfig::Bloom(BloomConfig {
+                    threshold: 1.5,
+                    ..Default::default()
+                }),
+                EffectConfig::Fxaa(FxaaConfig {
+                    enabled: false,
+                    ..Default::default()
+                }),
+                EffectConfig::ColorGrading(ColorGradingConfig {
+                    enabled: true,
+                    lut: Some("assets/lut.png".to_string()),
+                    strength: 1.0,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_unknown_effect() {
+        assert!(PostProcessConfig::from_json(
+            r#"{ "effects": [{ "type": "lens_flare" }] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn identity_lut_layout() {
+        let lut = identity_lut(4);
+        let texel = |x: usize, y: usize| {
+            let i = (y * 16 + x) * 4;
+            [lut[i], lut[i + 1], lut[i + 2]]
+        };
+
+        assert_eq!(lut.len(), 16 * 4 * 4);
+        assert_eq!(texel(0, 0), [0, 0, 0]);
+        assert_eq!(texel(3, 0), [255, 0, 0]);
+        assert_eq!(texel(4, 0), [0, 0, 85]);
+        assert_eq!(texel(15, 3), [255, 255, 255]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use bytemuck::cast_slice;
+use wgpu::{
+    BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Features,
+    Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, QUERY_SIZE,
+};
+
+use super::{stats::Allocation, Render};
+
+/// Passes timed per frame, the ones past it aren't
+pub const MAX_PROFILED_PASSES: u32 = 64;
+/// Frames whose timestamps can wait to be read back at the same time. A
+/// frame finding them all busy goes unprofiled
+const FRAMES_IN_FLIGHT: usize = 3;
+
+type MapFuture =
+    Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+enum SlotState {
+    Free,
+    /// Handed to a `CommandBuffer` that hasn't been submitted yet
+    Recording,
+    /// Submitted, waiting for the GPU to finish so the results can be read
+    Mapping(MapFuture),
+}
+
+/// Timestamps of one frame, resolved into a buffer read back once the GPU
+/// is done with them
+struct Slot {
+    buffer: wgpu::Buffer,
+    _allocation: Allocation,
+    /// Name of each timed pass, in the order they were recorded
+    names: Vec<String>,
+    /// Number of the frame, to keep the newest results only
+    frame: u64,
+    state: SlotState,
+}
+
+/// Measures how long the GPU spends in each render and compute pass of the
+/// command buffers given to `CommandBuffer::profile`, with timestamps read
+/// back a few frames later without waiting for the GPU. Needs the adapter
+/// to support `Features::TIMESTAMP_QUERY`, see `Render::create_gpu_profiler`
+pub struct GpuProfiler {
+    query_set: QuerySet,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    slots: RefCell<Vec<Slot>>,
+    /// Command buffers profiled so far
+    frame: Cell<u64>,
+    /// Frame the timings come from
+    timings_frame: u64,
+    timings: Vec<(String, f32)>,
+}
+
+impl GpuProfiler {
+    /// `None` if the device wasn't created with `TIMESTAMP_QUERY`
+    pub(super) fn new(render: &Render) -> Option<GpuProfiler> {
+        if !render.device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let queries = 2 * MAX_PROFILED_PASSES;
+        let query_set = render.device.create_query_set(&QuerySetDescriptor {
+            label: Some("profiler"),
+            ty: QueryType::Timestamp,
+            count: queries * FRAMES_IN_FLIGHT as u32,
+        });
+        let size = (queries * QUERY_SIZE) as u64;
+        let slots = (0..FRAMES_IN_FLIGHT)
+            .map(|_| Slot {
+                buffer: render.device.create_buffer(&BufferDescriptor {
+                    label: Some("profiler"),
+                    size,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                _allocation: render.buffer_memory.allocate(size),
+                names: Vec::new(),
+                frame: 0,
+                state: SlotState::Free,
+            })
+            .collect();
+
+        Some(GpuProfiler {
+            query_set,
+            period: render.queue.get_timestamp_period(),
+            slots: RefCell::new(slots),
+            frame: Cell::new(0),
+            timings_frame: 0,
+            timings: Vec::new(),
+        })
+    }
+
+    /// Milliseconds the GPU spent in the passes of the newest frame read
+    /// back, passes sharing a label adding up, in the order they ran
+    pub fn timings(&self) -> &[(String, f32)] {
+        &self.timings
+    }
+
+    /// Reserves a slot for a command buffer until it is submitted, `None`
+    /// if all of them are still waiting for their results
+    pub(super) fn begin(&self) -> Option<usize> {
+        let mut slots = self.slots.borrow_mut();
+        let index = slots
+            .iter()
+            .position(|slot| matches!(slot.state, SlotState::Free))?;
+        self.frame.set(self.frame.get() + 1);
+        let slot = &mut slots[index];
+        slot.state = SlotState::Recording;
+        slot.names.clear();
+        slot.frame = self.frame.get();
+
+        Some(index)
+    }
+
+    /// Index into the query set of the timestamp at the start of the next
+    /// pass of `slot`, and records its name. `None` past
+    /// `MAX_PROFILED_PASSES`, its end timestamp being the next index
+    pub(super) fn begin_pass(&self, slot: usize, name: &str) -> Option<u32> {
+        let mut slots = self.slots.borrow_mut();
+        let names = &mut slots[slot].names;
+        if names.len() as u32 == MAX_PROFILED_PASSES {
+            return None;
+        }
+        names.push(name.to_string());
+
+        Some(first_query(slot) + 2 * (names.len() as u32 - 1))
+    }
+
+    pub(super) fn query_set(&self) -> &QuerySet {
+        &self.query_set
+    }
+
+    /// Resolves the timestamps of `slot` into its buffer, as the last
+    /// command of `encoder`
+    pub(super) fn resolve(&self, slot: usize, encoder: &mut CommandEncoder) {
+        let slots = self.slots.borrow();
+        let count = 2 * slots[slot].names.len() as u32;
+        if count > 0 {
+            let first = first_query(slot);
+            encoder.resolve_query_set(
+                &self.query_set,
+                first..first + count,
+                &slots[slot].buffer,
+                0,
+            );
+        }
+    }
+
+    /// Starts reading back the timestamps of `slot` once its command buffer
+    /// was submitted
+    pub(super) fn submitted(&self, slot: usize) {
+        let mut slots = self.slots.borrow_mut();
+        let slot = &mut slots[slot];
+        if slot.names.is_empty() {
+            slot.state = SlotState::Free;
+            return;
+        }
+
+        let size = (2 * slot.names.len() as u32 * QUERY_SIZE) as u64;
+        slot.state = SlotState::Mapping(Box::pin(
+            slot.buffer.slice(..size).map_async(MapMode::Read),
+        ));
+    }
+
+    /// Picks up the timestamps the GPU is done with, without blocking. Call
+    /// once per frame, after submitting
+    pub fn collect(&mut self, render: &Render) {
+        render.device.poll(Maintain::Poll);
+
+        let mut context = Context::from_waker(Waker::noop());
+        for slot in self.slots.get_mut() {
+            let result = match slot.state {
+                SlotState::Mapping(ref mut future) => {
+                    match future.as_mut().poll(&mut context) {
+                        Poll::Ready(result) => result,
+                        Poll::Pending => continue,
+                    }
+                }
+                _ => continue,
+            };
+            slot.state = SlotState::Free;
+            if result.is_err() {
+                continue;
+            }
+
+            let size = (2 * slot.names.len() as u32 * QUERY_SIZE) as u64;
+            let timestamps = {
+                let data = slot.buffer.slice(..size).get_mapped_range();
+                cast_slice::<u8, u64>(&data).to_vec()
+            };
+            slot.buffer.unmap();
+
+            if slot.frame >= self.timings_frame {
+                self.timings_frame = slot.frame;
+                self.timings =
+                    pass_timings(&slot.names, &timestamps, self.period);
+            }
+        }
+    }
+}
+
+fn first_query(slot: usize) -> u32 {
+    slot as u32 * 2 * MAX_PROFILED_PASSES
+}
+
+/// Milliseconds between the start and end timestamps of each pass, adding
+/// up the passes sharing a name
+fn pass_timings(
+    names: &[String],
+    timestamps: &[u64],
+    period: f32,
+) -> Vec<(String, f32)> {
+    let mut timings: Vec<(String, f32)> = Vec::new();
+
+    for (name, pair) in names.iter().zip(timestamps.chunks_exact(2)) {
+        // Some drivers reset their counters, giving ends before starts
+        let ticks = pair[1].saturating_sub(pair[0]);
+        let ms = ticks as f64 * period as f64 / 1e6;
+        match timings.iter_mut().find(|(other, _)| other == name) {
+            Some((_, total)) => *total += ms as f32,
+            None => timings.push((name.clone(), ms as f32)),
+        }
+    }
+
+    timings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_per_name() {
+        let names = ["shadows", "scene", "shadows"].map(String::from);
+        let timestamps = [100, 1100, 2000, 5000, 6000, 6500];
+        let timings = pass_timings(&names, &timestamps, 1000.0);
+
+        assert_eq!(
+            timings,
+            [("shadows".to_string(), 1.5), ("scene".to_string(), 3.0)]
+        );
+        assert_eq!(
+            pass_timings(&names[..1], &[10, 5], 1.0),
+            [("shadows".to_string(), 0.0)]
+        );
+    }
+
+    #[test]
+    fn queries_of_slots_dont_overlap() {
+        assert_eq!(first_query(0), 0);
+        assert_eq!(first_query(1), 2 * MAX_PROFILED_PASSES);
+        assert!(
+            first_query(FRAMES_IN_FLIGHT - 1) + 2 * MAX_PROFILED_PASSES
+                <= wgpu::QUERY_SET_MAX_QUERIES
+        );
+    }
+}
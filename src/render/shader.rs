@@ -3,10 +3,49 @@ use wgpu::ShaderModule;
 pub struct Shader {
     pub vert: ShaderModule,
     pub frag: Option<ShaderModule>,
+    vert_entry: &'static str,
+    frag_entry: Option<&'static str>,
 }
 
-impl<'a> Shader {
+impl Shader {
+    #[allow(dead_code)]
     pub fn new(vert: ShaderModule, frag: Option<ShaderModule>) -> Shader {
-        Shader { vert, frag }
+        let frag_entry = frag.as_ref().map(|_| "main");
+
+        Shader {
+            vert,
+            frag,
+            vert_entry: "main",
+            frag_entry,
+        }
+    }
+
+    /// Shader whose stages all live in `module`, as with WGSL sources
+    pub fn from_module(
+        module: ShaderModule,
+        vert_entry: &'static str,
+        frag_entry: Option<&'static str>,
+    ) -> Shader {
+        Shader {
+            vert: module,
+            frag: None,
+            vert_entry,
+            frag_entry,
+        }
+    }
+
+    /// Module holding every entry point of a WGSL shader, including
+    /// compute ones
+    pub fn module(&self) -> &ShaderModule {
+        &self.vert
+    }
+
+    pub fn vertex(&self) -> (&ShaderModule, &'static str) {
+        (&self.vert, self.vert_entry)
+    }
+
+    pub fn fragment(&self) -> Option<(&ShaderModule, &'static str)> {
+        self.frag_entry
+            .map(|entry| (self.frag.as_ref().unwrap_or(&self.vert), entry))
     }
 }
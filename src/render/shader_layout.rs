@@ -25,6 +25,10 @@ pub enum ShaderAttributeType {
     Vec2,
     Vec3,
     Vec4,
+    /// Four unsigned integers, read as `vec4<u32>` in shaders
+    UVec4,
+    /// Four bytes scaled to 0..1, read as `vec4<f32>` in shaders
+    UnormVec4,
 }
 
 impl ShaderAttributeType {
@@ -34,6 +38,8 @@ impl ShaderAttributeType {
             ShaderAttributeType::Vec2 => wgpu::VertexFormat::Float32x2,
             ShaderAttributeType::Vec3 => wgpu::VertexFormat::Float32x3,
             ShaderAttributeType::Vec4 => wgpu::VertexFormat::Float32x4,
+            ShaderAttributeType::UVec4 => wgpu::VertexFormat::Uint32x4,
+            ShaderAttributeType::UnormVec4 => wgpu::VertexFormat::Unorm8x4,
             _ => panic!(),
         }
     }
@@ -44,6 +50,8 @@ impl ShaderAttributeType {
             ShaderAttributeType::Vec2 => size_of::<f32>() * 2,
             ShaderAttributeType::Vec3 => size_of::<f32>() * 3,
             ShaderAttributeType::Vec4 => size_of::<f32>() * 4,
+            ShaderAttributeType::UVec4 => size_of::<u32>() * 4,
+            ShaderAttributeType::UnormVec4 => size_of::<u8>() * 4,
         }
     }
 }
@@ -0,0 +1,470 @@
+use std::mem::size_of;
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use cgmath::{
+    ortho, perspective, Deg, EuclideanSpace, InnerSpace, Matrix4, MetricSpace,
+    Point3, Transform, Vector3,
+};
+use wgpu::{BindGroup, BindGroupLayout, ShaderStages, TextureFormat};
+
+use super::{
+    light::{MAX_CASCADES, MAX_SPOT_SHADOWS},
+    Binding, BindingLayout, Buffer, CommandBuffer, Framebuffer, Light, Lights,
+    Mesh, MeshInstance, Pipeline, Render, Sampler, Texture,
+};
+use crate::camera::{Camera, Projection, OPENGL_TO_WGPU};
+
+const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// Cascades of the shadowed directional light, then spot lights
+const SHADOW_LAYERS: usize = MAX_CASCADES + MAX_SPOT_SHADOWS;
+/// Closest distance to a spot light that still casts shadows
+const SPOT_NEAR: f32 = 0.05;
+
+/// How shadow maps are rendered and filtered
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadowConfig {
+    /// Width and height of every shadow map layer
+    pub resolution: u32,
+    /// Distance from the camera at which each cascade of the directional
+    /// light ends, increasing. Splits past `MAX_CASCADES` are ignored and
+    /// nothing beyond the last one is shadowed
+    pub cascade_splits: Vec<f32>,
+    /// Texels sampled on each side of the looked up one, averaging
+    /// `(2 * pcf_radius + 1)^2` comparisons. 0 gives hard edges
+    pub pcf_radius: u32,
+    /// How far behind a cascade, towards the light, objects still cast
+    /// shadows into it
+    pub caster_distance: f32,
+    /// Depth bias of the shadow pass, see `PipelineBuilder::depth_bias`
+    pub depth_bias: i32,
+    pub slope_bias: f32,
+}
+
+impl ShadowConfig {
+    /// Splits `count` cascades between `near` and `far`, blending uniform
+    /// splits (`blend` of 0) with logarithmic ones (`blend` of 1), which
+    /// give close cascades more of the resolution
+    pub fn split_distances(
+        near: f32,
+        far: f32,
+        count: usize,
+        blend: f32,
+    ) -> Vec<f32> {
+        (1..=count)
+            .map(|i| {
+                let t = i as f32 / count as f32;
+                let uniform = near + (far - near) * t;
+                let logarithmic = near * (far / near).powf(t);
+
+                uniform + (logarithmic - uniform) * blend
+            })
+            .collect()
+    }
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        ShadowConfig {
+            resolution: 2048,
+            cascade_splits: ShadowConfig::split_distances(
+                0.1,
+                60.0,
+                MAX_CASCADES,
+                0.75,
+            ),
+            pcf_radius: 1,
+            caster_distance: 20.0,
+            depth_bias: 2,
+            slope_bias: 2.0,
+        }
+    }
+}
+
+/// Layout of the `Shadows` uniform block of the lit shaders
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ShadowUniforms {
+    view_projs: [[[f32; 4]; 4]; SHADOW_LAYERS],
+    /// Far distance of each cascade
+    splits: [f32; MAX_CASCADES],
+    /// Direction the camera looks at, to measure the distance of fragments
+    /// along it
+    forward: [f32; 3],
+    cascades: u32,
+    texel_size: f32,
+    pcf_radius: u32,
+    _padding: [f32; 2],
+}
+
+/// Depth textures rendered from each shadow casting light of a `Lights`,
+/// one array layer per cascade of the directional light and one per spot
+/// light, read by the lit shaders through a comparison sampler
+pub struct ShadowMaps {
+    config: ShadowConfig,
+    pipeline: Pipeline,
+    /// Deforms skinned meshes with the joint matrices of their object
+    skinned_pipeline: Pipeline,
+    /// Only holds the layers of the casters seen by `prepare` so far, a
+    /// single texel until one casts shadows
+    texture: Texture,
+    /// One per layer of `texture`
+    framebuffers: Vec<Framebuffer>,
+    sampler: Sampler,
+    uniforms: Buffer,
+    /// Light matrix of each layer, `stride` bytes apart
+    layers: Buffer,
+    layers_group: BindGroup,
+    stride: u64,
+    /// Layers rendered by the next `draw`
+    active: Vec<usize>,
+}
+
+impl ShadowMaps {
+    /// Shadow maps drawing meshes whose transforms and joint matrices are
+    /// bound at index 1 through `object_layout`, as with `SceneRenderer`
+    pub fn new(
+        render: &Render,
+        object_layout: &BindGroupLayout,
+        config: ShadowConfig,
+    ) -> ShadowMaps {
+        let layer_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::DynamicUniform],
+            ShaderStages::VERTEX,
+        );
+        let source = include_str!("../../shaders/shadow.wgsl");
+        let shader =
+            render.create_wgsl_shader_with_entries(source, "vs_main", None);
+        let pipeline = render
+            .build_pipeline(&shader)
+            .vertex_layout(&Mesh::layout(render))
+            .bind_group_layout(&layer_layout)
+            .bind_group_layout(object_layout)
+            .depth(DEPTH_FORMAT)
+            .depth_bias(config.depth_bias, config.slope_bias)
+            .build();
+        let skinned_shader =
+            render.create_wgsl_shader_with_entries(source, "vs_skinned", None);
+        let skinned_pipeline = render
+            .build_pipeline(&skinned_shader)
+            .vertex_layout(&Mesh::layout(render))
+            .vertex_layout(&Mesh::skin_layout(render))
+            .bind_group_layout(&layer_layout)
+            .bind_group_layout(object_layout)
+            .depth(DEPTH_FORMAT)
+            .depth_bias(config.depth_bias, config.slope_bias)
+            .build();
+
+        let (texture, framebuffers) =
+            render.create_layered_depth(1, 1, 1, DEPTH_FORMAT);
+
+        let alignment = render.uniform_alignment() as u64;
+        let size = size_of::<[[f32; 4]; 4]>() as u64;
+        let stride = size.div_ceil(alignment) * alignment;
+        let layers =
+            render.create_uniform_buffer(SHADOW_LAYERS as u64 * stride);
+        let layers_group = render.create_bind_group_with(
+            &layer_layout,
+            &[Binding::BufferRange(&layers, size)],
+        );
+
+        ShadowMaps {
+            pipeline,
+            skinned_pipeline,
+            texture,
+            framebuffers,
+            sampler: render.create_comparison_sampler(),
+            uniforms: render.create_uniforms(&ShadowUniforms::zeroed()),
+            layers,
+            layers_group,
+            stride,
+            active: Vec::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ShadowConfig {
+        &self.config
+    }
+
+    /// Bind group layout entries read by the lit shaders, in the order of
+    /// `bindings`
+    pub fn binding_layouts() -> [BindingLayout; 3] {
+        [
+            BindingLayout::Uniform,
+            BindingLayout::DepthTextureArray,
+            BindingLayout::ComparisonSampler,
+        ]
+    }
+
+    pub fn bindings(&self) -> [Binding<'_>; 3] {
+        [
+            Binding::Buffer(&self.uniforms),
+            Binding::Texture(&self.texture),
+            Binding::Sampler(&self.sampler),
+        ]
+    }
+
+    /// Fits the shadow maps of the shadow casting `lights` around what
+    /// `camera` sees and uploads their matrices. Call once per frame
+    /// before `draw`. Returns whether the texture grew to fit the layers
+    /// of the casters, in which case bind groups made from `bindings` must
+    /// be created again
+    pub fn prepare(
+        &mut self,
+        render: &Render,
+        lights: &Lights,
+        camera: &Camera,
+    ) -> bool {
+        let cascades = &self.config.cascade_splits
+            [..self.config.cascade_splits.len().min(MAX_CASCADES)];
+        let mut uniforms = ShadowUniforms::zeroed();
+        self.active.clear();
+
+        for (light, first) in lights.shadow_casters() {
+            let first = first as usize;
+            let view_projs = match light {
+                Light::Directional { direction, .. } => cascade_view_projs(
+                    direction,
+                    camera,
+                    cascades,
+                    self.config.resolution,
+                    self.config.caster_distance,
+                ),
+                Light::Spot {
+                    position,
+                    direction,
+                    range,
+                    outer,
+                    ..
+                } => vec![spot_view_proj(position, direction, range, outer)],
+                Light::Point { .. } => Vec::new(),
+            };
+
+            for (i, view_proj) in view_projs.into_iter().enumerate() {
+                uniforms.view_projs[first + i] = view_proj.into();
+                self.active.push(first + i);
+            }
+        }
+
+        uniforms.splits[..cascades.len()].copy_from_slice(cascades);
+        uniforms.forward = camera.forward().into();
+        uniforms.cascades = cascades.len() as u32;
+        uniforms.texel_size = 1.0 / self.config.resolution as f32;
+        uniforms.pcf_radius = self.config.pcf_radius;
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+
+        let mut data = vec![0; SHADOW_LAYERS * self.stride as usize];
+        for (i, view_proj) in uniforms.view_projs.iter().enumerate() {
+            let offset = i * self.stride as usize;
+            data[offset..offset + size_of::<[[f32; 4]; 4]>()]
+                .copy_from_slice(bytes_of(view_proj));
+        }
+        render.write_buffer(&self.layers, &data);
+
+        let layers = self.active.iter().max().map_or(0, |&layer| layer + 1);
+        let resized = self.texture.width() != self.config.resolution;
+        if layers == 0 || (!resized && layers <= self.framebuffers.len()) {
+            return false;
+        }
+
+        let (texture, framebuffers) = render.create_layered_depth(
+            self.config.resolution,
+            self.config.resolution,
+            layers.max(self.framebuffers.len()) as u32,
+            DEPTH_FORMAT,
+        );
+        self.texture = texture;
+        self.framebuffers = framebuffers;
+        true
+    }
+
+    /// Records one depth-only pass per layer filled by the last `prepare`,
+    /// drawing `meshes` with the transforms at their offset into `objects`
+    pub fn draw<'a>(
+        &'a self,
+        mut commands: CommandBuffer<'a>,
+        objects: &'a BindGroup,
+        meshes: &[MeshInstance<'a>],
+    ) -> CommandBuffer<'a> {
+        for &layer in self.active.iter() {
+            commands = commands
+                .label("shadows")
+                .configure_draw(&self.pipeline, &self.framebuffers[layer])
+                .bind_resources_with_offsets(
+                    0,
+                    &self.layers_group,
+                    &[(layer as u64 * self.stride) as u32],
+                );
+            for instance in meshes {
+                let pipeline = if instance.is_skinned() {
+                    &self.skinned_pipeline
+                } else {
+                    &self.pipeline
+                };
+                commands = instance.draw(
+                    commands
+                        .set_pipeline(pipeline)
+                        .bind_resources_with_offsets(
+                            1,
+                            objects,
+                            &[instance.offset],
+                        ),
+                );
+            }
+        }
+
+        commands
+    }
+}
+
+/// Orthographic light matrix of each cascade of a directional light
+/// travelling along `direction`. Every cascade covers a sphere around its
+/// slice of the view, so its size doesn't change as the camera turns, and
+/// moves in whole texels, so its edges don't shimmer as the camera moves
+fn cascade_view_projs(
+    direction: Vector3<f32>,
+    camera: &Camera,
+    splits: &[f32],
+    resolution: u32,
+    caster_distance: f32,
+) -> Vec<Matrix4<f32>> {
+    let near = match camera.projection {
+        Projection::Perspective { near, .. }
+        | Projection::Orthographic { near, .. } => near,
+    };
+    let view = Matrix4::look_to_rh(
+        Point3::origin(),
+        direction.normalize(),
+        up_for(direction),
+    );
+
+    let mut start = near;
+    splits
+        .iter()
+        .map(|&end| {
+            let corners = camera.frustum_corners(start, end);
+            start = end;
+
+            let center = Point3::centroid(&corners);
+            let radius = corners
+                .iter()
+                .map(|corner| corner.distance(center))
+                .fold(0.0, f32::max);
+            // Rounded so the texel size only changes in steps
+            let radius = (radius * 16.0).ceil() / 16.0;
+            let texel = 2.0 * radius / resolution as f32;
+
+            let center = view.transform_point(center);
+            let (x, y) = (
+                (center.x / texel).floor() * texel,
+                (center.y / texel).floor() * texel,
+            );
+            // The light looks down -Z, so depths are negated
+            let projection = ortho(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                -center.z - radius - caster_distance,
+                -center.z + radius,
+            );
+
+            OPENGL_TO_WGPU * projection * view
+        })
+        .collect()
+}
+
+/// Perspective light matrix covering the cone of a spot light
+fn spot_view_proj(
+    position: Point3<f32>,
+    direction: Vector3<f32>,
+    range: f32,
+    outer: Deg<f32>,
+) -> Matrix4<f32> {
+    let view =
+        Matrix4::look_to_rh(position, direction.normalize(), up_for(direction));
+    let fovy = Deg((outer.0 * 2.0).clamp(1.0, 170.0));
+
+    OPENGL_TO_WGPU * perspective(fovy, 1.0, SPOT_NEAR, range) * view
+}
+
+/// Any direction not parallel to `direction`
+fn up_for(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{point3, vec3};
+
+    #[test]
+    fn split_distances() {
+        let uniform = ShadowConfig::split_distances(1.0, 100.0, 4, 0.0);
+        assert_eq!(uniform, [25.75, 50.5, 75.25, 100.0]);
+
+        let logarithmic = ShadowConfig::split_distances(1.0, 100.0, 2, 1.0);
+        assert!((logarithmic[0] - 10.0).abs() < 1e-4);
+        assert!((logarithmic[1] - 100.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn cascades_contain_their_slice() {
+        let mut camera = Camera::new(
+            point3(1.0, 2.0, 3.0),
+            Projection::Perspective {
+                fovy: Deg(60.0),
+                near: 0.1,
+                far: 100.0,
+            },
+            16.0 / 9.0,
+        );
+        camera.look_at(point3(4.0, 0.0, -5.0));
+        let splits = [2.0, 8.0, 30.0];
+
+        let view_projs = cascade_view_projs(
+            vec3(-0.3, -1.0, 0.2),
+            &camera,
+            &splits,
+            1024,
+            10.0,
+        );
+        assert_eq!(view_projs.len(), splits.len());
+
+        let mut start = 0.1;
+        for (view_proj, &end) in view_projs.iter().zip(splits.iter()) {
+            for corner in camera.frustum_corners(start, end) {
+                let ndc = view_proj.transform_point(corner);
+                assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0);
+                assert!((0.0..=1.0).contains(&ndc.z));
+            }
+            start = end;
+        }
+    }
+
+    #[test]
+    fn spot_covers_its_cone() {
+        let view_proj = spot_view_proj(
+            point3(0.0, 5.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            10.0,
+            Deg(30.0),
+        );
+
+        let center = view_proj.transform_point(point3(0.0, 0.0, 0.0));
+        assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+        assert!(center.z > 0.0 && center.z < 1.0);
+
+        // On the edge of the cone, 5 units below the light
+        let edge = 5.0 * 30.0_f32.to_radians().tan();
+        let ndc = view_proj.transform_point(point3(edge, 0.0, 0.0));
+        assert!(
+            (ndc.x.abs() - 1.0).abs() < 1e-4
+                || (ndc.y.abs() - 1.0).abs() < 1e-4
+        );
+    }
+}
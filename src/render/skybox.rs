@@ -0,0 +1,152 @@
+use bytemuck::{bytes_of, Pod, Zeroable};
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use wgpu::{
+    AddressMode, BindGroup, CompareFunction, FilterMode, ShaderStages,
+    TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, CommandBuffer, Pipeline, Render, Texture,
+};
+use crate::camera::Camera;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct SkyUniforms {
+    inverse_view_proj: [[f32; 4]; 4],
+    encode_srgb: u32,
+    _padding: [u32; 3],
+}
+
+/// Background drawn from a cubemap at the far plane, so it only shows
+/// where nothing else was drawn. With a depth attachment it is drawn after
+/// the opaque geometry, skipping the pixels they cover; without one it
+/// has to come first
+pub struct Skybox {
+    pipeline: Pipeline,
+    uniforms: Buffer,
+    bind_group: BindGroup,
+    encode_srgb: bool,
+}
+
+impl Skybox {
+    /// Skybox showing `cubemap`, drawn into framebuffers of `format` with a
+    /// depth attachment of `depth` if given
+    pub fn new(
+        render: &Render,
+        cubemap: &Texture,
+        format: TextureFormat,
+        depth: Option<TextureFormat>,
+    ) -> Skybox {
+        let layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Cubemap,
+                BindingLayout::Sampler,
+            ],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/skybox.wgsl"),
+        ));
+        let mut builder = render
+            .build_pipeline(&shader)
+            .bind_group_layout(&layout)
+            .target(format);
+        if let Some(depth) = depth {
+            // The far plane only passes where the depth is still cleared
+            builder = builder
+                .depth(depth)
+                .depth_compare(CompareFunction::LessEqual, false);
+        }
+        let pipeline = builder.build();
+
+        let uniforms = render.create_uniforms(&SkyUniforms::zeroed());
+        let sampler =
+            render.create_sampler(FilterMode::Linear, AddressMode::ClampToEdge);
+        let bind_group = render.create_bind_group_with(
+            &layout,
+            &[
+                Binding::Buffer(&uniforms),
+                Binding::Texture(cubemap),
+                Binding::Sampler(&sampler),
+            ],
+        );
+
+        Skybox {
+            pipeline,
+            uniforms,
+            bind_group,
+            encode_srgb: render.encodes_srgb(format),
+        }
+    }
+
+    /// Uploads the orientation of `camera`. Call once per frame before
+    /// `draw`
+    pub fn prepare(&self, render: &Render, camera: &Camera) {
+        let uniforms = SkyUniforms {
+            inverse_view_proj: sky_matrix(camera).into(),
+            encode_srgb: self.encode_srgb as u32,
+            _padding: [0; 3],
+        };
+
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+    }
+
+    /// Draws into the render pass being recorded, leaving the skybox
+    /// pipeline and bind group set
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+    ) -> CommandBuffer<'a> {
+        commands
+            .set_pipeline(&self.pipeline)
+            .bind_resources(&self.bind_group)
+            .draw_vertices(0..3)
+    }
+}
+
+/// Maps clip space positions to world space directions from the camera, by
+/// inverting the projection and the rotation of the view, leaving the
+/// position out so the sky stays infinitely far away
+fn sky_matrix(camera: &Camera) -> Matrix4<f32> {
+    let mut view = camera.view();
+    view.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+
+    let view_proj = camera.projection_matrix() * view;
+    view_proj.invert().unwrap_or(view_proj)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{point3, vec3, Deg, InnerSpace, Vector3};
+
+    use super::*;
+    use crate::camera::Projection;
+
+    fn direction(camera: &Camera, x: f32, y: f32) -> Vector3<f32> {
+        let far = sky_matrix(camera) * Vector4::new(x, y, 1.0, 1.0);
+        (far.truncate() / far.w).normalize()
+    }
+
+    #[test]
+    fn sky_ignores_camera_position() {
+        let projection = Projection::Perspective {
+            fovy: Deg(90.0),
+            near: 0.1,
+            far: 100.0,
+        };
+        let mut camera = Camera::new(point3(0.0, 0.0, 0.0), projection, 1.0);
+        camera.look_at(point3(1.0, 0.0, 0.0));
+        let center = direction(&camera, 0.0, 0.0);
+        let top = direction(&camera, 0.0, 1.0);
+
+        camera.position = point3(50.0, -20.0, 7.0);
+        camera.look_at(point3(51.0, -20.0, 7.0));
+        assert!((direction(&camera, 0.0, 0.0) - center).magnitude() < 1e-4);
+        assert!((center - vec3(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+        // 45 degrees up at the top edge of a 90 degree field of view
+        assert!((top.y - top.x).abs() < 1e-4 && top.y > 0.0);
+    }
+}
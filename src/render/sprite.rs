@@ -0,0 +1,377 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::Range;
+
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BlendState, FilterMode,
+    ShaderStages, TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, Color, CommandBuffer, Framebuffer,
+    Pipeline, Render, Sampler, ShaderAttribute, ShaderAttributeType, Texture,
+};
+use crate::camera::Camera2d;
+
+/// Sprites drawn by one call at most, as many as 16-bit indices reach
+const MAX_BATCH: usize = (u16::MAX as usize + 1) / 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct SpriteUniforms {
+    view_proj: [[f32; 4]; 4],
+    encode_srgb: u32,
+    _padding: [u32; 3],
+}
+
+/// Texture added to a `SpriteBatch` with `SpriteBatch::add_texture`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpriteTexture(usize);
+
+/// Textured quad queued with `SpriteBatch::sprite`
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub texture: SpriteTexture,
+    /// Where `origin` lands, in world units of the camera
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Counterclockwise turn around `origin`, in radians
+    pub rotation: f32,
+    /// Point of the sprite placed at `position`, from 0 to 1 from its top
+    /// left corner. Defaults to the center
+    pub origin: [f32; 2],
+    /// Corners of the part of the texture shown, top left then bottom
+    /// right, from 0 to 1
+    pub uv: [[f32; 2]; 2],
+    /// Multiplies the texture
+    pub color: Color,
+    /// Sprites with a greater depth are drawn first, behind the others
+    pub depth: f32,
+}
+
+impl Sprite {
+    pub fn new(
+        texture: SpriteTexture,
+        position: [f32; 2],
+        size: [f32; 2],
+    ) -> Sprite {
+        Sprite {
+            texture,
+            position,
+            size,
+            rotation: 0.0,
+            origin: [0.5, 0.5],
+            uv: [[0.0, 0.0], [1.0, 1.0]],
+            color: Color::WHITE,
+            depth: 0.0,
+        }
+    }
+}
+
+/// Draws 2D sprites with one call per run of sprites sharing a texture,
+/// after sorting them back to front and by texture. Sprites are queued
+/// during the frame, uploaded by `prepare` and recorded by `draw`
+pub struct SpriteBatch {
+    pipeline: Pipeline,
+    texture_layout: BindGroupLayout,
+    uniforms: Buffer,
+    view_group: BindGroup,
+    samplers: HashMap<FilterMode, Sampler>,
+    textures: Vec<BindGroup>,
+    queued: Vec<Sprite>,
+    vertices: Buffer,
+    /// Sprites `vertices` has room for
+    capacity: usize,
+    /// Two triangles for each of `MAX_BATCH` quads
+    indices: Buffer,
+    /// Texture and sprites of every draw of the last `prepare`
+    batches: Vec<(SpriteTexture, Range<u32>)>,
+    encode_srgb: bool,
+}
+
+impl SpriteBatch {
+    /// Sprite batch drawing into framebuffers of `format` without a depth
+    /// attachment
+    pub fn new(render: &Render, format: TextureFormat) -> SpriteBatch {
+        let view_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let texture_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Texture, BindingLayout::Sampler],
+            ShaderStages::FRAGMENT,
+        );
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/sprite.wgsl"),
+        ));
+        let vertex_layout = render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 0),
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 1),
+            ShaderAttribute::new(ShaderAttributeType::Vec4, 2),
+        ]);
+        let pipeline = render
+            .build_pipeline(&shader)
+            .vertex_layout(&vertex_layout)
+            .bind_group_layout(&view_layout)
+            .bind_group_layout(&texture_layout)
+            .blended_target(format, Some(BlendState::ALPHA_BLENDING))
+            .build();
+        let uniforms = render.create_uniforms(&SpriteUniforms::zeroed());
+        let view_group = render.create_bind_group_with(
+            &view_layout,
+            &[Binding::Buffer(&uniforms)],
+        );
+        let indices = (0..MAX_BATCH as u16)
+            .flat_map(|quad| {
+                let first = quad * 4;
+                [first, first + 1, first + 2, first, first + 2, first + 3]
+            })
+            .collect::<Vec<_>>();
+        let capacity = 256;
+
+        SpriteBatch {
+            pipeline,
+            texture_layout,
+            uniforms,
+            view_group,
+            samplers: HashMap::new(),
+            textures: Vec::new(),
+            queued: Vec::new(),
+            vertices: render.create_dynamic_vertex_buffer(
+                (4 * capacity * size_of::<SpriteVertex>()) as u64,
+            ),
+            capacity,
+            indices: render.create_index_buffer(cast_slice(&indices)),
+            batches: Vec::new(),
+            encode_srgb: render.encodes_srgb(format),
+        }
+    }
+
+    /// Makes `texture` usable by sprites, sampled with `filter`, e.g.
+    /// `FilterMode::Nearest` for pixel art
+    pub fn add_texture(
+        &mut self,
+        render: &Render,
+        texture: &Texture,
+        filter: FilterMode,
+    ) -> SpriteTexture {
+        let sampler = self.samplers.entry(filter).or_insert_with(|| {
+            render.create_sampler(filter, AddressMode::ClampToEdge)
+        });
+        self.textures.push(render.create_bind_group_with(
+            &self.texture_layout,
+            &[Binding::Texture(texture), Binding::Sampler(sampler)],
+        ));
+
+        SpriteTexture(self.textures.len() - 1)
+    }
+
+    /// Queues `sprite` for the next `prepare`
+    pub fn sprite(&mut self, sprite: &Sprite) {
+        self.queued.push(*sprite);
+    }
+
+    /// Sorts and uploads the sprites queued since the last call, seen by
+    /// `camera`. Call once per frame before `draw`
+    pub fn prepare(&mut self, render: &Render, camera: &Camera2d) {
+        let (width, height) = render.size();
+        let uniforms = SpriteUniforms {
+            view_proj: camera
+                .view_proj(width as f32 / height.max(1) as f32)
+                .into(),
+            encode_srgb: self.encode_srgb as u32,
+            _padding: [0; 3],
+        };
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+
+        sort_sprites(&mut self.queued);
+        self.batches = batches(&self.queued);
+
+        let y_down = camera.height < 0.0;
+        let vertices = self
+            .queued
+            .iter()
+            .flat_map(|sprite| {
+                let color = sprite.color.to_array();
+                let [[u0, v0], [u1, v1]] = sprite.uv;
+                let uvs = [[u0, v0], [u0, v1], [u1, v1], [u1, v0]];
+                corners(sprite, y_down).into_iter().zip(uvs).map(
+                    move |(position, uv)| SpriteVertex {
+                        position,
+                        uv,
+                        color,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if self.queued.len() > self.capacity {
+            self.capacity = self.queued.len().next_power_of_two();
+            self.vertices = render.create_dynamic_vertex_buffer(
+                (4 * self.capacity * size_of::<SpriteVertex>()) as u64,
+            );
+        }
+        if !vertices.is_empty() {
+            render.write_buffer(&self.vertices, cast_slice(&vertices));
+        }
+        self.queued.clear();
+    }
+
+    /// Draws the sprites over what `output` already holds, in a render pass
+    /// of its own
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        if self.batches.is_empty() {
+            return commands;
+        }
+
+        let mut commands = commands
+            .label("sprites")
+            .configure_draw_over(&self.pipeline, output)
+            .bind_resources(&self.view_group)
+            .set_vertices(&self.vertices)
+            .set_indices(&self.indices);
+        for (texture, sprites) in &self.batches {
+            commands = commands
+                .bind_resources_at(1, &self.textures[texture.0])
+                .draw_from(
+                    0..6 * sprites.len() as u32,
+                    4 * sprites.start as i32,
+                );
+        }
+
+        commands
+    }
+}
+
+/// Back to front, then grouped by texture. Sprites equal on both keep the
+/// order they were queued in
+fn sort_sprites(sprites: &mut [Sprite]) {
+    sprites.sort_by(|a, b| {
+        b.depth
+            .partial_cmp(&a.depth)
+            .unwrap_or(Ordering::Equal)
+            .then(a.texture.cmp(&b.texture))
+    });
+}
+
+/// Runs of consecutive sprites sharing a texture, split to fit the index
+/// buffer
+fn batches(sprites: &[Sprite]) -> Vec<(SpriteTexture, Range<u32>)> {
+    let mut batches: Vec<(SpriteTexture, Range<u32>)> = Vec::new();
+
+    for (i, sprite) in sprites.iter().enumerate() {
+        match batches.last_mut() {
+            Some((texture, range))
+                if *texture == sprite.texture && range.len() < MAX_BATCH =>
+            {
+                range.end += 1;
+            }
+            _ => batches.push((sprite.texture, i as u32..i as u32 + 1)),
+        }
+    }
+
+    batches
+}
+
+/// World positions of the top left, bottom left, bottom right and top right
+/// corners of `sprite`, as seen on screen
+fn corners(sprite: &Sprite, y_down: bool) -> [[f32; 2]; 4] {
+    let [width, height] = sprite.size;
+    let [origin_x, origin_y] = sprite.origin;
+    let (sin, cos) = sprite.rotation.sin_cos();
+
+    [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]].map(|[u, v]| {
+        // Relative to the origin, with Y up on screen
+        let x = (u - origin_x) * width;
+        let y = (origin_y - v) * height;
+        let (x, y) = (x * cos - y * sin, x * sin + y * cos);
+        let y = if y_down { -y } else { y };
+
+        [sprite.position[0] + x, sprite.position[1] + y]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn sprite(texture: usize, depth: f32) -> Sprite {
+        Sprite {
+            depth,
+            ..Sprite::new(SpriteTexture(texture), [0.0, 0.0], [1.0, 1.0])
+        }
+    }
+
+    #[test]
+    fn sorted_back_to_front_then_by_texture() {
+        let mut sprites = [
+            sprite(1, 0.0),
+            sprite(0, 0.0),
+            sprite(1, 5.0),
+            sprite(1, 0.0),
+            sprite(0, 1.0),
+        ];
+        sort_sprites(&mut sprites);
+        let keys = sprites
+            .iter()
+            .map(|sprite| (sprite.texture.0, sprite.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [(1, 5.0), (0, 1.0), (0, 0.0), (1, 0.0), (1, 0.0)]);
+
+        assert_eq!(
+            batches(&sprites),
+            [
+                (SpriteTexture(1), 0..1),
+                (SpriteTexture(0), 1..3),
+                (SpriteTexture(1), 3..5),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_fit_the_indices() {
+        let sprites = vec![sprite(0, 0.0); MAX_BATCH + 10];
+        let batches = batches(&sprites);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].1, 0..MAX_BATCH as u32);
+        assert_eq!(batches[1].1.len(), 10);
+    }
+
+    #[test]
+    fn corners_rotate_around_the_origin() {
+        let mut quad = Sprite::new(SpriteTexture(0), [10.0, 20.0], [4.0, 2.0]);
+        assert_eq!(
+            corners(&quad, false),
+            [[8.0, 21.0], [8.0, 19.0], [12.0, 19.0], [12.0, 21.0]]
+        );
+        // Y down keeps the top of the texture at the top of the screen
+        assert_eq!(corners(&quad, true)[0], [8.0, 19.0]);
+
+        quad.origin = [0.0, 0.0];
+        quad.rotation = std::f32::consts::FRAC_PI_2;
+        let [top_left, _, bottom_right, top_right] = corners(&quad, false);
+        assert_eq!(top_left, [10.0, 20.0]);
+        // The top edge turned to point up
+        assert_abs_diff_eq!(top_right[0], 10.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(top_right[1], 24.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(bottom_right[0], 12.0, epsilon = 1e-5);
+        assert_abs_diff_eq!(bottom_right[1], 24.0, epsilon = 1e-5);
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use wgpu::TextureDescriptor;
+
+/// Draws recorded into a `CommandBuffer`, see `CommandBuffer::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    /// Counted as if every draw was of triangle lists
+    pub triangles: u64,
+    pub dispatches: u32,
+}
+
+impl DrawStats {
+    pub(super) fn add_draw(&mut self, vertices: u32, instances: u32) {
+        self.draw_calls += 1;
+        self.triangles += (vertices / 3) as u64 * instances as u64;
+    }
+}
+
+/// Objects a renderer drew or skipped for being out of view in its last
+/// frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+/// Bytes of GPU memory held by the buffers and textures alive, as created
+/// by `Render`. Textures are estimated from their size, format and mip
+/// levels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub buffers: u64,
+    pub textures: u64,
+}
+
+/// Running total of the bytes of one kind of resource
+#[derive(Clone, Default)]
+pub(super) struct MemoryCounter(Arc<AtomicU64>);
+
+impl MemoryCounter {
+    pub fn allocate(&self, bytes: u64) -> Allocation {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+
+        Allocation {
+            counter: self.0.clone(),
+            bytes,
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes counted by a `MemoryCounter` until the resource holding them is
+/// dropped
+pub(super) struct Allocation {
+    counter: Arc<AtomicU64>,
+    bytes: u64,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// Bytes taken by the texels of every layer and mip level of a texture
+pub(super) fn texture_bytes(descriptor: &TextureDescriptor) -> u64 {
+    let info = descriptor.format.describe();
+    let [block_width, block_height] = [
+        info.block_dimensions.0 as u32,
+        info.block_dimensions.1 as u32,
+    ];
+    let size = descriptor.size;
+
+    (0..descriptor.mip_level_count)
+        .map(|level| {
+            let width = (size.width >> level).max(1);
+            let height = (size.height >> level).max(1);
+            let blocks = width.div_ceil(block_width) as u64
+                * height.div_ceil(block_height) as u64;
+
+            blocks * info.block_size as u64
+        })
+        .sum::<u64>()
+        * size.depth_or_array_layers as u64
+        * descriptor.sample_count as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+
+    use super::*;
+
+    #[test]
+    fn allocations_are_given_back() {
+        let counter = MemoryCounter::default();
+        let first = counter.allocate(100);
+        let second = counter.allocate(20);
+        assert_eq!(counter.bytes(), 120);
+
+        drop(first);
+        assert_eq!(counter.bytes(), 20);
+        drop(second);
+        assert_eq!(counter.bytes(), 0);
+    }
+
+    #[test]
+    fn texture_sizes() {
+        let mut descriptor = TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 256,
+                height: 128,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING,
+        };
+        assert_eq!(texture_bytes(&descriptor), 256 * 128 * 4);
+
+        // Cube with every mip level down to 1x1
+        descriptor.size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 6,
+        };
+        descriptor.mip_level_count = 3;
+        descriptor.format = TextureFormat::Rgba16Float;
+        assert_eq!(texture_bytes(&descriptor), (16 + 4 + 1) * 8 * 6);
+    }
+}
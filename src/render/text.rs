@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use fontdue::FontSettings;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BlendState, FilterMode,
+    ShaderStages, TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, Color, CommandBuffer, Framebuffer,
+    Pipeline, Render, Sampler, ShaderAttribute, ShaderAttributeType, Texture,
+};
+
+const ATLAS_WIDTH: u32 = 512;
+/// The atlas doubles its height when full, up to this
+const MAX_ATLAS_HEIGHT: u32 = 4096;
+/// Empty pixels around every glyph, so filtering doesn't bleed neighbours in
+const GLYPH_PADDING: u32 = 1;
+
+/// Where lines are placed relative to the position of the text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+    /// Lines start at the position
+    Left,
+    /// Lines are centered on the position
+    Center,
+    /// Lines end at the position
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub color: Color,
+    /// Multiplies the pixel size the font was loaded with
+    pub scale: f32,
+    pub align: TextAlign,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            color: Color::WHITE,
+            scale: 1.0,
+            align: TextAlign::Left,
+        }
+    }
+}
+
+/// Glyph rasterized into the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Glyph {
+    /// Top left corner in the atlas, in pixels
+    origin: [u32; 2],
+    /// In pixels, zero for glyphs covering nothing like spaces
+    size: [u32; 2],
+    /// From the pen on the baseline to the bottom left corner, y up
+    offset: [f32; 2],
+    advance: f32,
+}
+
+/// Glyph placed by `Font::layout`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+    /// Top left corner in pixels from the position of the text, y down
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Top left corner in the atlas, in pixels
+    pub uv: [f32; 2],
+    /// In pixels of the atlas
+    pub uv_size: [f32; 2],
+}
+
+/// Grayscale image the glyphs are packed into, row after row
+struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    /// Where the next glyph goes
+    cursor: [u32; 2],
+    /// Height of the tallest glyph of the current row
+    row_height: u32,
+    /// Changes whenever `pixels` does
+    generation: u32,
+}
+
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        let height = 256;
+        GlyphAtlas {
+            width: ATLAS_WIDTH,
+            height,
+            pixels: vec![0; (ATLAS_WIDTH * height) as usize],
+            cursor: [GLYPH_PADDING; 2],
+            row_height: 0,
+            generation: 0,
+        }
+    }
+
+    /// Copies `width` by `height` tightly packed pixels into the atlas,
+    /// returning their top left corner. `None` once the atlas is full
+    fn insert(
+        &mut self,
+        width: u32,
+        height: u32,
+        coverage: &[u8],
+    ) -> Option<[u32; 2]> {
+        if width + 2 * GLYPH_PADDING > self.width {
+            return None;
+        }
+        if self.cursor[0] + width + GLYPH_PADDING > self.width {
+            self.cursor = [
+                GLYPH_PADDING,
+                self.cursor[1] + self.row_height + GLYPH_PADDING,
+            ];
+            self.row_height = 0;
+        }
+        while self.cursor[1] + height + GLYPH_PADDING > self.height {
+            if self.height * 2 > MAX_ATLAS_HEIGHT {
+                return None;
+            }
+            // Rows are stored one after the other, so growing keeps
+            // every glyph in place
+            self.height *= 2;
+            self.pixels.resize((self.width * self.height) as usize, 0);
+        }
+
+        let [x, y] = self.cursor;
+        for (row, line) in coverage.chunks(width as usize).enumerate() {
+            let start = ((y + row as u32) * self.width + x) as usize;
+            self.pixels[start..start + width as usize].copy_from_slice(line);
+        }
+        self.cursor[0] += width + GLYPH_PADDING;
+        self.row_height = self.row_height.max(height);
+        self.generation += 1;
+
+        Some([x, y])
+    }
+}
+
+/// TrueType or OpenType font rasterized at one pixel size, glyph by glyph
+/// as text uses them
+pub struct Font {
+    font: fontdue::Font,
+    /// Pixel size of the rasterized glyphs
+    size: f32,
+    ascent: f32,
+    /// Distance between the baselines of two lines
+    line_height: f32,
+    glyphs: HashMap<char, Glyph>,
+    atlas: GlyphAtlas,
+    /// Center of a fully covered texel of the atlas, in pixels
+    solid: [f32; 2],
+}
+
+impl Font {
+    /// Loads a TTF or OTF file, rasterizing its glyphs `size` pixels tall
+    pub fn load<P: AsRef<Path>>(path: P, size: f32) -> Result<Font, String> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|err| {
+            format!("Failed to load {}: {}", path.display(), err)
+        })?;
+
+        Font::from_bytes(&data, size).map_err(|err| {
+            format!("Failed to load {}: {}", path.display(), err)
+        })
+    }
+
+    pub fn from_bytes(data: &[u8], size: f32) -> Result<Font, String> {
+        let font = fontdue::Font::from_bytes(
+            data,
+            FontSettings {
+                scale: size,
+                ..FontSettings::default()
+            },
+        )?;
+        let metrics = font
+            .horizontal_line_metrics(size)
+            .ok_or("Font has no horizontal metrics")?;
+        // Solid block sampled by `TextRenderer::rect`, wide enough for its
+        // center texel to stay clear of the padding when filtered
+        let mut atlas = GlyphAtlas::new();
+        let [x, y] = atlas.insert(3, 3, &[255; 9]).unwrap();
+
+        Ok(Font {
+            font,
+            size,
+            ascent: metrics.ascent,
+            line_height: metrics.new_line_size,
+            glyphs: HashMap::new(),
+            atlas,
+            solid: [x as f32 + 1.5, y as f32 + 1.5],
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    #[allow(dead_code)]
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Rasterizes `character` into the atlas the first time it is used
+    fn glyph(&mut self, character: char) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&character) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = self.font.rasterize(character, self.size);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let origin = if width > 0 && height > 0 {
+            self.atlas.insert(width, height, &coverage)
+        } else {
+            None
+        };
+        // Glyphs that didn't fit still take their room on the line
+        let glyph = Glyph {
+            origin: origin.unwrap_or([0, 0]),
+            size: origin.map_or([0, 0], |_| [width, height]),
+            offset: [metrics.xmin as f32, metrics.ymin as f32],
+            advance: metrics.advance_width,
+        };
+        self.glyphs.insert(character, glyph);
+
+        glyph
+    }
+
+    /// Adjustment of the advance between `previous` and `character`, in
+    /// pixels of the font size
+    fn kerning(&self, previous: Option<char>, character: char) -> f32 {
+        previous
+            .and_then(|previous| {
+                self.font.horizontal_kern(previous, character, self.size)
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Places the glyphs of `text`, breaking lines at `\n` and kerning
+    /// pairs of characters. The position of the text is the top of the
+    /// first line, where `align` puts the lines
+    pub fn layout(
+        &mut self,
+        text: &str,
+        scale: f32,
+        align: TextAlign,
+    ) -> Vec<GlyphQuad> {
+        let mut quads = Vec::new();
+
+        for (i, line) in text.split('\n').enumerate() {
+            let baseline = (self.ascent + i as f32 * self.line_height) * scale;
+            let first = quads.len();
+            let mut pen = 0.0;
+            let mut previous = None;
+
+            for character in line.chars() {
+                pen += self.kerning(previous, character) * scale;
+                previous = Some(character);
+
+                let glyph = self.glyph(character);
+                if glyph.size != [0, 0] {
+                    let [width, height] = glyph.size;
+                    let top = glyph.offset[1] + height as f32;
+                    quads.push(GlyphQuad {
+                        position: [
+                            pen + glyph.offset[0] * scale,
+                            baseline - top * scale,
+                        ],
+                        size: [width as f32 * scale, height as f32 * scale],
+                        uv: [glyph.origin[0] as f32, glyph.origin[1] as f32],
+                        uv_size: [width as f32, height as f32],
+                    });
+                }
+                pen += glyph.advance * scale;
+            }
+
+            let shift = match align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -pen / 2.0,
+                TextAlign::Right => -pen,
+            };
+            for quad in &mut quads[first..] {
+                quad.position[0] = (quad.position[0] + shift).round();
+                quad.position[1] = quad.position[1].round();
+            }
+        }
+
+        quads
+    }
+
+    /// Width of the longest line of `text` and height of all of them, in
+    /// pixels
+    pub fn measure(&mut self, text: &str, scale: f32) -> [f32; 2] {
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+
+        for line in text.split('\n') {
+            let mut pen = 0.0;
+            let mut previous = None;
+            for character in line.chars() {
+                pen += self.kerning(previous, character);
+                previous = Some(character);
+                pen += self.glyph(character).advance;
+            }
+            width = width.max(pen * scale);
+            lines += 1;
+        }
+
+        [width, lines as f32 * self.line_height * scale]
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ScreenUniforms {
+    size: [f32; 2],
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+/// Draws text in screen space with one font, batching every string and
+/// rectangle queued during the frame into a single draw
+pub struct TextRenderer {
+    font: Font,
+    pipeline: Pipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    uniforms: Buffer,
+    /// Atlas texture with its bind group, and the generation it holds
+    atlas: Option<(Texture, BindGroup, u32)>,
+    vertices: Buffer,
+    /// Vertices `vertices` has room for
+    capacity: usize,
+    queued: Vec<TextVertex>,
+    /// Vertices uploaded by the last `prepare`
+    vertex_count: u32,
+    encode_srgb: bool,
+}
+
+impl TextRenderer {
+    /// Text renderer drawing `font` into framebuffers of `format` without
+    /// a depth attachment
+    pub fn new(
+        render: &Render,
+        font: Font,
+        format: TextureFormat,
+    ) -> TextRenderer {
+        let layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Texture,
+                BindingLayout::Sampler,
+            ],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/text.wgsl"),
+        ));
+        let vertex_layout = render.create_shader_layout([
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 0),
+            ShaderAttribute::new(ShaderAttributeType::Vec2, 1),
+            ShaderAttribute::new(ShaderAttributeType::Vec4, 2),
+        ]);
+        let pipeline = render
+            .build_pipeline(&shader)
+            .vertex_layout(&vertex_layout)
+            .bind_group_layout(&layout)
+            .blended_target(format, Some(BlendState::ALPHA_BLENDING))
+            .build();
+        let capacity = 6 * 256;
+
+        TextRenderer {
+            font,
+            pipeline,
+            layout,
+            sampler: render
+                .create_sampler(FilterMode::Linear, AddressMode::ClampToEdge),
+            uniforms: render.create_uniforms(&ScreenUniforms::zeroed()),
+            atlas: None,
+            vertices: render.create_dynamic_vertex_buffer(
+                (capacity * size_of::<TextVertex>()) as u64,
+            ),
+            capacity,
+            queued: Vec::new(),
+            vertex_count: 0,
+            encode_srgb: render.encodes_srgb(format),
+        }
+    }
+
+    pub fn font(&mut self) -> &mut Font {
+        &mut self.font
+    }
+
+    /// Queues `text` for the next `prepare`, `position` being in pixels
+    /// from the top left corner of the screen, see `Font::layout`
+    pub fn text(&mut self, text: &str, position: [f32; 2], style: &TextStyle) {
+        let color = style.color.to_array();
+        let [x, y] = position;
+
+        for quad in self.font.layout(text, style.scale, style.align) {
+            let [left, top] = [x + quad.position[0], y + quad.position[1]];
+            let [right, bottom] = [left + quad.size[0], top + quad.size[1]];
+            let [u0, v0] = quad.uv;
+            let [u1, v1] = [u0 + quad.uv_size[0], v0 + quad.uv_size[1]];
+            let vertex = |position, uv| TextVertex {
+                position,
+                uv,
+                color,
+            };
+
+            self.queued.extend_from_slice(&[
+                vertex([left, top], [u0, v0]),
+                vertex([left, bottom], [u0, v1]),
+                vertex([right, bottom], [u1, v1]),
+                vertex([left, top], [u0, v0]),
+                vertex([right, bottom], [u1, v1]),
+                vertex([right, top], [u1, v0]),
+            ]);
+        }
+    }
+
+    /// Queues a rectangle filled with `color`, drawn along with the text
+    /// in the order they were queued
+    pub fn rect(&mut self, position: [f32; 2], size: [f32; 2], color: Color) {
+        let color = color.to_array();
+        let uv = self.font.solid;
+        let [left, top] = position;
+        let [right, bottom] = [left + size[0], top + size[1]];
+        let vertex = |position| TextVertex {
+            position,
+            uv,
+            color,
+        };
+
+        self.queued.extend_from_slice(&[
+            vertex([left, top]),
+            vertex([left, bottom]),
+            vertex([right, bottom]),
+            vertex([left, top]),
+            vertex([right, bottom]),
+            vertex([right, top]),
+        ]);
+    }
+
+    /// Uploads the text queued since the last call, along with the glyphs
+    /// it added to the atlas. Call once per frame before `draw`
+    pub fn prepare(&mut self, render: &Render) {
+        let generation = self.font.atlas.generation;
+        if self.atlas.as_ref().map(|(_, _, current)| *current)
+            != Some(generation)
+        {
+            let atlas = &self.font.atlas;
+            let texture = render.create_texture_with_format(
+                atlas.width,
+                atlas.height,
+                &atlas.pixels,
+                TextureFormat::R8Unorm,
+            );
+            let bind_group = render.create_bind_group_with(
+                &self.layout,
+                &[
+                    Binding::Buffer(&self.uniforms),
+                    Binding::Texture(&texture),
+                    Binding::Sampler(&self.sampler),
+                ],
+            );
+            self.atlas = Some((texture, bind_group, generation));
+        }
+
+        let (width, height) = render.size();
+        let uniforms = ScreenUniforms {
+            size: [width as f32, height as f32],
+            encode_srgb: self.encode_srgb as u32,
+            _padding: 0,
+        };
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+
+        if self.queued.len() > self.capacity {
+            self.capacity = self.queued.len().next_power_of_two();
+            self.vertices = render.create_dynamic_vertex_buffer(
+                (self.capacity * size_of::<TextVertex>()) as u64,
+            );
+        }
+        if !self.queued.is_empty() {
+            render.write_buffer(&self.vertices, cast_slice(&self.queued));
+        }
+        self.vertex_count = self.queued.len() as u32;
+        self.queued.clear();
+    }
+
+    /// Draws the text over what `output` already holds, in a render pass of
+    /// its own
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let bind_group = match self.atlas {
+            Some((_, ref bind_group, _)) if self.vertex_count > 0 => bind_group,
+            _ => return commands,
+        };
+
+        commands
+            .label("text")
+            .configure_draw_over(&self.pipeline, output)
+            .bind_resources(bind_group)
+            .set_vertices(&self.vertices)
+            .draw_vertices(0..self.vertex_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Font {
+        Font::load("assets/DejaVuSansMono.ttf", 16.0).unwrap()
+    }
+
+    #[test]
+    fn layout_lines_and_alignment() {
+        let mut font = font();
+        let left = font.layout("ab\ncd", 1.0, TextAlign::Left);
+        assert_eq!(left.len(), 4);
+        assert!(left[0].position[0] < left[1].position[0]);
+        assert!(left[2].position[1] > left[0].position[1]);
+        // Both lines start at the position
+        assert!((left[0].position[0] - left[2].position[0]).abs() <= 1.0);
+
+        let [width, height] = font.measure("ab\ncd", 1.0);
+        assert_eq!(height, 2.0 * font.line_height());
+        let right = font.layout("ab\ncd", 1.0, TextAlign::Right);
+        let end = right[1].position[0] + right[1].size[0];
+        assert!(end <= 0.0 && end > -width / 2.0);
+
+        let twice = font.layout("ab", 2.0, TextAlign::Left);
+        assert_eq!(twice[0].size[0], 2.0 * left[0].size[0]);
+        assert_eq!(twice[0].uv, left[0].uv);
+    }
+
+    #[test]
+    fn spaces_take_room_without_quads() {
+        let mut font = font();
+        let quads = font.layout("a a", 1.0, TextAlign::Left);
+        assert_eq!(quads.len(), 2);
+        assert_eq!(quads[0].uv, quads[1].uv);
+        assert!(quads[1].position[0] > quads[0].position[0] + quads[0].size[0]);
+    }
+
+    #[test]
+    fn atlas_packs_rows_and_grows() {
+        let mut atlas = GlyphAtlas::new();
+        let coverage = vec![255; 100 * 100];
+        let origins = (0..12)
+            .map(|_| atlas.insert(100, 100, &coverage).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(origins[0], [1, 1]);
+        assert_eq!(origins[1], [102, 1]);
+        // Five glyphs per row of 512 pixels
+        assert_eq!(origins[5], [1, 102]);
+        assert_eq!(atlas.height, 512);
+        assert_eq!(atlas.generation, 12);
+        assert_eq!(atlas.pixels[(102 * atlas.width + 1) as usize], 255);
+        assert_eq!(atlas.pixels[(101 * atlas.width + 1) as usize], 0);
+        assert!(atlas.insert(600, 10, &coverage).is_none());
+    }
+}
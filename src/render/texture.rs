@@ -1 +1,86 @@
-pub struct Texture;
+use wgpu::{
+    Sampler as WgpuSampler, Texture as WgpuTexture, TextureFormat, TextureView,
+};
+
+use super::stats::Allocation;
+
+/// How the texels of an 8-bit texture are encoded. Anything meant to be
+/// looked at (albedo, UI, sprites) is `Srgb`, anything read as numbers
+/// (normal maps, roughness, lookup tables) is `Linear`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(self) -> TextureFormat {
+        match self {
+            ColorSpace::Srgb => TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+pub struct Texture {
+    texture: WgpuTexture,
+    view: TextureView,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    _allocation: Allocation,
+}
+
+impl<'a> Texture {
+    pub(super) fn new(
+        texture: WgpuTexture,
+        view: TextureView,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        allocation: Allocation,
+    ) -> Texture {
+        Texture {
+            texture,
+            view,
+            format,
+            width,
+            height,
+            _allocation: allocation,
+        }
+    }
+
+    pub fn get_texture(&'a self) -> &'a WgpuTexture {
+        &self.texture
+    }
+
+    pub fn get_view(&'a self) -> &'a TextureView {
+        &self.view
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+pub struct Sampler {
+    sampler: WgpuSampler,
+}
+
+impl<'a> Sampler {
+    pub fn new(sampler: WgpuSampler) -> Sampler {
+        Sampler { sampler }
+    }
+
+    pub fn get_sampler(&'a self) -> &'a WgpuSampler {
+        &self.sampler
+    }
+}
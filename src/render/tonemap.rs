@@ -0,0 +1,180 @@
+use std::str::FromStr;
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, FilterMode, ShaderStages,
+    TextureFormat,
+};
+
+use super::{
+    Binding, BindingLayout, Buffer, CommandBuffer, Framebuffer, Pipeline,
+    Render, Sampler,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TonemapOperator {
+    Reinhard,
+    Aces,
+    /// No curve at all, anything above 1.0 is clipped
+    Clamp,
+}
+
+impl TonemapOperator {
+    fn to_shader(self) -> u32 {
+        match self {
+            TonemapOperator::Reinhard => 0,
+            TonemapOperator::Aces => 1,
+            TonemapOperator::Clamp => 2,
+        }
+    }
+
+    pub fn next(self) -> TonemapOperator {
+        match self {
+            TonemapOperator::Reinhard => TonemapOperator::Aces,
+            TonemapOperator::Aces => TonemapOperator::Clamp,
+            TonemapOperator::Clamp => TonemapOperator::Reinhard,
+        }
+    }
+}
+
+impl FromStr for TonemapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reinhard" => Ok(TonemapOperator::Reinhard),
+            "aces" => Ok(TonemapOperator::Aces),
+            "clamp" | "none" => Ok(TonemapOperator::Clamp),
+            _ => Err(format!("Unknown tonemap operator '{}'", s)),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct TonemapUniforms {
+    exposure: f32,
+    operator: u32,
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+/// Full-screen pass turning an HDR framebuffer into displayable colors.
+/// `set_input` has to be called again whenever the input is recreated
+pub struct Tonemapper {
+    pipeline: Pipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    uniforms: Buffer,
+    bind_group: Option<BindGroup>,
+    operator: TonemapOperator,
+    exposure: f32,
+    encode_srgb: bool,
+}
+
+impl Tonemapper {
+    pub fn new(render: &Render, target_format: TextureFormat) -> Tonemapper {
+        let shader = render.create_wgsl_shader(concat!(
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/fullscreen.wgsl"),
+            include_str!("../../shaders/tonemap.wgsl"),
+        ));
+        let bind_group_layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Texture,
+                BindingLayout::Sampler,
+            ],
+            ShaderStages::FRAGMENT,
+        );
+        let pipeline = render
+            .build_pipeline(&shader)
+            .bind_group_layout(&bind_group_layout)
+            .target(target_format)
+            .build();
+
+        let operator = TonemapOperator::Aces;
+        let exposure = 1.0;
+        let encode_srgb = !target_format.describe().srgb;
+        let uniforms = render.create_uniforms(&TonemapUniforms {
+            exposure,
+            operator: operator.to_shader(),
+            encode_srgb: encode_srgb as u32,
+            _padding: 0,
+        });
+
+        Tonemapper {
+            pipeline,
+            bind_group_layout,
+            sampler: render
+                .create_sampler(FilterMode::Linear, AddressMode::ClampToEdge),
+            uniforms,
+            bind_group: None,
+            operator,
+            exposure,
+            encode_srgb,
+        }
+    }
+
+    pub fn set_input(&mut self, render: &Render, input: &Framebuffer) {
+        let texture = input
+            .get_texture()
+            .expect("Tonemapper input must be an offscreen framebuffer");
+
+        self.bind_group = Some(render.create_bind_group_with(
+            &self.bind_group_layout,
+            &[
+                Binding::Buffer(&self.uniforms),
+                Binding::Texture(texture),
+                Binding::Sampler(&self.sampler),
+            ],
+        ));
+    }
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.operator
+    }
+
+    pub fn set_operator(&mut self, render: &Render, operator: TonemapOperator) {
+        self.operator = operator;
+        self.write_uniforms(render);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn set_exposure(&mut self, render: &Render, exposure: f32) {
+        self.exposure = exposure.max(0.0);
+        self.write_uniforms(render);
+    }
+
+    fn write_uniforms(&self, render: &Render) {
+        let uniforms = TonemapUniforms {
+            exposure: self.exposure,
+            operator: self.operator.to_shader(),
+            encode_srgb: self.encode_srgb as u32,
+            _padding: 0,
+        };
+
+        render.write_buffer(&self.uniforms, bytes_of(&uniforms));
+    }
+
+    /// Records the tonemapping pass into `output`
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let bind_group = self
+            .bind_group
+            .as_ref()
+            .expect("Tonemapper::set_input was never called");
+
+        commands
+            .label("tonemap")
+            .configure_draw(&self.pipeline, output)
+            .bind_resources(bind_group)
+            .draw_vertices(0..3)
+    }
+}
@@ -0,0 +1,391 @@
+use cgmath::{Matrix4, One, Quaternion, SquareMatrix, Vector3};
+
+mod renderer;
+
+pub use renderer::SceneRenderer;
+
+use crate::render::ModelNode;
+
+/// Handle to a node of the `Scene` that created it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Position, orientation and size of a node relative to its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vector3<f32>) -> Transform {
+        Transform {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    /// Scales, then rotates, then translates
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(
+                self.scale.x,
+                self.scale.y,
+                self.scale.z,
+            )
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+pub struct Node {
+    pub name: String,
+    /// Index into the meshes given to `SceneRenderer::draw`, `None` for
+    /// nodes only grouping others
+    pub mesh: Option<usize>,
+    /// Index into the materials given to `SceneRenderer::draw`, replacing
+    /// the one the mesh was loaded with
+    pub material: Option<usize>,
+    /// Index into `Model::skeletons` of the skeleton deforming the mesh
+    pub skin: Option<usize>,
+    /// Matrix palette skinning the mesh, see `Skeleton::joint_matrices`.
+    /// Empty for rigid meshes and skinned ones at rest
+    pub joints: Vec<Matrix4<f32>>,
+    /// Weights of the morph targets of the mesh, see
+    /// `Mesh::with_morph_targets`. Empty to draw the mesh as it was loaded
+    pub weights: Vec<f32>,
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f32>,
+    dirty: bool,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Changes the local transform. The world matrices of the node and its
+    /// descendants follow on the next `Scene::update`
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.dirty = true;
+    }
+
+    #[allow(dead_code)]
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Transform from the node's space to world space as of the last
+    /// `Scene::update`
+    pub fn world(&self) -> Matrix4<f32> {
+        self.world
+    }
+}
+
+/// Hierarchy of nodes, each placed relative to its parent
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene { nodes: Vec::new() }
+    }
+
+    pub fn add_node(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        transform: Transform,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            mesh: None,
+            material: None,
+            skin: None,
+            joints: Vec::new(),
+            weights: Vec::new(),
+            transform,
+            parent,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+            dirty: true,
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+
+        id
+    }
+
+    /// Adds the nodes of a model file under `parent`, keeping their
+    /// hierarchy and their mesh indices into `Model::meshes`. A node
+    /// drawing several meshes gets a child for each one past the first,
+    /// sharing its skin and morph target weights. Returns the ids in the
+    /// order of `nodes`
+    pub fn add_model(
+        &mut self,
+        nodes: &[ModelNode],
+        parent: Option<NodeId>,
+    ) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = Vec::with_capacity(nodes.len());
+
+        for node in nodes {
+            let id = self.add_node(
+                &node.name,
+                node.parent.map(|i| ids[i]).or(parent),
+                Transform {
+                    translation: node.translation,
+                    rotation: node.rotation,
+                    scale: node.scale,
+                },
+            );
+            ids.push(id);
+
+            let mut meshes = node.meshes.iter();
+            self.nodes[id.0].mesh = meshes.next().copied();
+            self.nodes[id.0].skin = node.skin;
+            self.nodes[id.0].weights = node.weights.clone();
+            for (i, mesh) in meshes.enumerate() {
+                let extra = self.add_node(
+                    &format!("{}.{}", node.name, i + 1),
+                    Some(id),
+                    Transform::default(),
+                );
+                self.nodes[extra.0].mesh = Some(*mesh);
+                self.nodes[extra.0].skin = node.skin;
+                self.nodes[extra.0].weights = node.weights.clone();
+            }
+        }
+
+        ids
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .map(NodeId)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (NodeId(i), node))
+    }
+
+    /// Moves `id` under `parent`, or to the top of the hierarchy. Fails if
+    /// `parent` is `id` itself or one of its descendants
+    #[allow(dead_code)]
+    pub fn set_parent(
+        &mut self,
+        id: NodeId,
+        parent: Option<NodeId>,
+    ) -> Result<(), String> {
+        let mut ancestor = parent;
+        while let Some(node) = ancestor {
+            if node == id {
+                return Err(format!(
+                    "'{}' cannot be its own ancestor",
+                    self.nodes[id.0].name
+                ));
+            }
+            ancestor = self.nodes[node.0].parent;
+        }
+
+        if let Some(old) = self.nodes[id.0].parent {
+            self.nodes[old.0].children.retain(|child| *child != id);
+        }
+        if let Some(new) = parent {
+            self.nodes[new.0].children.push(id);
+        }
+
+        let node = &mut self.nodes[id.0];
+        node.parent = parent;
+        node.dirty = true;
+
+        Ok(())
+    }
+
+    /// Recomputes the world matrices of the nodes whose transform changed
+    /// and of their descendants. Returns how many were recomputed
+    pub fn update(&mut self) -> usize {
+        let mut updated = 0;
+        let mut stack = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(i, _)| (i, Matrix4::identity(), false))
+            .collect::<Vec<_>>();
+
+        while let Some((i, parent_world, parent_changed)) = stack.pop() {
+            let node = &mut self.nodes[i];
+            let changed = node.dirty || parent_changed;
+            if changed {
+                node.world = parent_world * node.transform.matrix();
+                node.dirty = false;
+                updated += 1;
+            }
+
+            let world = node.world;
+            stack.extend(
+                node.children.iter().map(|child| (child.0, world, changed)),
+            );
+        }
+
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{assert_abs_diff_eq, vec3, Deg, Rotation3};
+
+    fn hierarchy() -> (Scene, NodeId, NodeId, NodeId) {
+        let mut scene = Scene::new();
+        let root = scene.add_node(
+            "root",
+            None,
+            Transform::from_translation(vec3(1.0, 0.0, 0.0)),
+        );
+        let child = scene.add_node(
+            "child",
+            Some(root),
+            Transform {
+                rotation: Quaternion::from_angle_y(Deg(90.0)),
+                ..Default::default()
+            },
+        );
+        let grandchild = scene.add_node(
+            "grandchild",
+            Some(child),
+            Transform::from_translation(vec3(0.0, 0.0, 2.0)),
+        );
+
+        (scene, root, child, grandchild)
+    }
+
+    #[test]
+    fn world_matrices_follow_parents() {
+        let (mut scene, _, _, grandchild) = hierarchy();
+        assert_eq!(scene.update(), 3);
+
+        let origin =
+            scene.node(grandchild).world() * vec3(0.0, 0.0, 0.0).extend(1.0);
+        assert_abs_diff_eq!(
+            origin.truncate(),
+            vec3(3.0, 0.0, 0.0),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn only_dirty_subtrees_update() {
+        let (mut scene, root, child, grandchild) = hierarchy();
+        scene.update();
+        assert_eq!(scene.update(), 0);
+
+        scene
+            .node_mut(child)
+            .set_transform(Transform::from_translation(vec3(0.0, 1.0, 0.0)));
+        assert_eq!(scene.update(), 2);
+
+        let origin =
+            scene.node(grandchild).world() * vec3(0.0, 0.0, 0.0).extend(1.0);
+        assert_abs_diff_eq!(
+            origin.truncate(),
+            vec3(1.0, 1.0, 2.0),
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(
+            scene.node(root).world(),
+            Matrix4::from_translation(vec3(1.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn reparenting() {
+        let (mut scene, root, child, grandchild) = hierarchy();
+        scene.update();
+
+        assert!(scene.set_parent(root, Some(grandchild)).is_err());
+        assert!(scene.set_parent(child, Some(child)).is_err());
+        assert_eq!(scene.node(root).parent(), None);
+
+        scene.set_parent(grandchild, None).unwrap();
+        assert_eq!(scene.node(grandchild).parent(), None);
+        assert!(scene.node(child).children().is_empty());
+        // Only the moved node is recomputed
+        assert_eq!(scene.update(), 1);
+        assert_abs_diff_eq!(
+            scene.node(grandchild).world(),
+            Matrix4::from_translation(vec3(0.0, 0.0, 2.0))
+        );
+        assert_eq!(scene.find("grandchild"), Some(grandchild));
+
+        scene.set_parent(grandchild, Some(root)).unwrap();
+        assert_eq!(scene.node(grandchild).parent(), Some(root));
+        assert_eq!(scene.node(root).children(), [child, grandchild]);
+        assert_eq!(scene.update(), 1);
+        assert_abs_diff_eq!(
+            scene.node(grandchild).world(),
+            Matrix4::from_translation(vec3(1.0, 0.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn model_nodes() {
+        let mut scene = Scene::new();
+        let world = scene.add_node("world", None, Transform::default());
+        let nodes = [
+            ModelNode::root("body", vec![0, 1]),
+            ModelNode {
+                parent: Some(0),
+                translation: vec3(0.0, 2.0, 0.0),
+                ..ModelNode::root("head", vec![2])
+            },
+        ];
+
+        let ids = scene.add_model(&nodes, Some(world));
+        assert_eq!(scene.node(ids[0]).parent(), Some(world));
+        assert_eq!(scene.node(ids[1]).parent(), Some(ids[0]));
+        assert_eq!(scene.node(ids[0]).mesh, Some(0));
+        assert_eq!(scene.node(ids[1]).mesh, Some(2));
+
+        let extra = scene.find("body.1").unwrap();
+        assert_eq!(scene.node(extra).parent(), Some(ids[0]));
+        assert_eq!(scene.node(extra).mesh, Some(1));
+        assert_eq!(scene.node(ids[0]).children(), [extra, ids[1]]);
+    }
+}
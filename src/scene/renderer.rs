@@ -0,0 +1,849 @@
+use std::{collections::HashMap, mem::size_of};
+
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use cgmath::{EuclideanSpace, Matrix, Point3, SquareMatrix, Transform};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BlendState, Face, FilterMode,
+    ShaderStages, TextureFormat,
+};
+
+use crate::{
+    camera::Camera,
+    render::{
+        Binding, BindingLayout, Buffer, Color, ColorSpace, CommandBuffer,
+        CullStats, Environment, Framebuffer, Lights, Material, Mesh,
+        MeshInstance, MorphInstance, Morpher, PbrMaterial, Pipeline, Render,
+        Sampler, ShadowConfig, ShadowMaps, Skybox, Texture, TextureMap,
+    },
+};
+
+use super::{NodeId, Scene};
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct CameraUniforms {
+    view_proj: [[f32; 4]; 4],
+    position: [f32; 3],
+    encode_srgb: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct ObjectUniforms {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 4]; 4],
+    /// First matrix of the node's palette in the joint buffer
+    joint_offset: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct MaterialUniforms {
+    ambient: [f32; 4],
+    /// a = opacity
+    diffuse: [f32; 4],
+    /// w = shininess
+    specular: [f32; 4],
+    emissive: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct PbrMaterialUniforms {
+    /// a = opacity
+    base_color: [f32; 4],
+    emissive: [f32; 3],
+    /// 0 without a normal map
+    normal_scale: f32,
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    _padding: f32,
+}
+
+/// How a `SceneRenderer` shades surfaces
+enum Shading {
+    BlinnPhong,
+    /// Metallic-roughness materials, lit by the environment as well
+    Pbr {
+        environment: Box<Environment>,
+        environment_group: BindGroup,
+    },
+}
+
+/// Mesh node uploaded by `SceneRenderer::prepare`
+struct NodeDraw {
+    /// Index into the meshes given to `draw`
+    mesh: usize,
+    /// Index into the materials given to `draw`, replacing the mesh's
+    material: Option<usize>,
+    /// Offset of the node's uniforms into the object buffer
+    offset: u32,
+    /// Whether the node has joint matrices for a skinned mesh
+    posed: bool,
+    /// Key of the node's blended vertices in `SceneRenderer::morphs`
+    morph: Option<NodeId>,
+    /// Whether the node may be in view of the camera. Nodes out of view
+    /// still cast shadows
+    visible: bool,
+    /// Distance of the node's bounds in front of the camera, transparent
+    /// nodes are drawn farthest first
+    depth: f32,
+}
+
+/// `Material` or `PbrMaterial` uploaded for a `SceneRenderer`, with its
+/// texture maps loaded
+pub struct SceneMaterial {
+    _uniforms: Buffer,
+    _maps: Vec<Texture>,
+    bind_group: BindGroup,
+    transparent: bool,
+}
+
+/// Draws every mesh node of a `Scene` with Blinn-Phong shading, or with
+/// metallic-roughness materials under image-based lighting when created
+/// `with_environment`. The uniforms of all nodes share one buffer, each
+/// draw binding its own slice through a dynamic offset. Skinned meshes are
+/// deformed on the GPU by the joint matrices of their node, gathered in one
+/// storage buffer, and nodes with morph target weights draw vertices
+/// blended by a compute pass. Opaque nodes cast shadows from the lights
+/// that have them enabled. Nodes whose bounds are out of the camera's
+/// frustum are skipped. An optional skybox fills the background
+pub struct SceneRenderer {
+    /// Lights shading the next `prepare`d frame
+    pub lights: Lights,
+    /// Skips nodes out of view from the next `prepare` on
+    pub culling: bool,
+    pipeline: Pipeline,
+    /// Same shading, vertices deformed by the joint matrices of the node
+    skinned_pipeline: Pipeline,
+    shading: Shading,
+    camera: Buffer,
+    light_buffer: Buffer,
+    camera_layout: BindGroupLayout,
+    camera_group: BindGroup,
+    shadows: ShadowMaps,
+    object_layout: BindGroupLayout,
+    objects: Buffer,
+    objects_group: BindGroup,
+    /// Number of objects `objects` has room for
+    capacity: usize,
+    /// Distance between two objects in `objects`
+    stride: u64,
+    /// Joint matrices of every posed node, one palette after the other
+    joints: Buffer,
+    /// Number of matrices `joints` has room for
+    joint_capacity: usize,
+    material_layout: BindGroupLayout,
+    sampler: Sampler,
+    /// Stands in for missing texture maps
+    white: Texture,
+    /// Used by nodes whose mesh has no material
+    default_material: SceneMaterial,
+    encode_srgb: bool,
+    format: TextureFormat,
+    depth: Option<TextureFormat>,
+    skybox: Option<Skybox>,
+    morpher: Morpher,
+    /// Blended vertices of each node with morph target weights
+    morphs: HashMap<NodeId, MorphInstance>,
+    draws: Vec<NodeDraw>,
+    cull_stats: CullStats,
+}
+
+impl SceneRenderer {
+    /// Renderer drawing into framebuffers of `format`, with a depth
+    /// attachment of `depth` if given
+    pub fn new(
+        render: &Render,
+        format: TextureFormat,
+        depth: Option<TextureFormat>,
+    ) -> SceneRenderer {
+        SceneRenderer::create(render, format, depth, None)
+    }
+
+    /// PBR renderer, lighting the scene with `environment` on top of its
+    /// lights. Blinn-Phong materials are converted with
+    /// `PbrMaterial::from_phong`
+    pub fn with_environment(
+        render: &Render,
+        format: TextureFormat,
+        depth: Option<TextureFormat>,
+        environment: Environment,
+    ) -> SceneRenderer {
+        SceneRenderer::create(render, format, depth, Some(environment))
+    }
+
+    fn create(
+        render: &Render,
+        format: TextureFormat,
+        depth: Option<TextureFormat>,
+        environment: Option<Environment>,
+    ) -> SceneRenderer {
+        let [shadow_uniforms, shadow_maps, shadow_sampler] =
+            ShadowMaps::binding_layouts();
+        let camera_layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::Uniform,
+                BindingLayout::Uniform,
+                shadow_uniforms,
+                shadow_maps,
+                shadow_sampler,
+            ],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        let object_layout = render.create_bind_group_layout_with(
+            &[
+                BindingLayout::DynamicUniform,
+                BindingLayout::Storage { read_only: true },
+            ],
+            ShaderStages::VERTEX_FRAGMENT,
+        );
+        // One texture map per channel of the material, then a sampler
+        let map_count = if environment.is_some() { 5 } else { 2 };
+        let material_layout = render.create_bind_group_layout_with(
+            &[BindingLayout::Uniform]
+                .into_iter()
+                .chain([BindingLayout::Texture; 5].into_iter().take(map_count))
+                .chain([BindingLayout::Sampler])
+                .collect::<Vec<_>>(),
+            ShaderStages::FRAGMENT,
+        );
+        let environment_layout = render.create_bind_group_layout_with(
+            &Environment::binding_layouts(),
+            ShaderStages::FRAGMENT,
+        );
+
+        let source = if environment.is_some() {
+            include_str!("../../shaders/pbr.wgsl")
+        } else {
+            include_str!("../../shaders/lit.wgsl")
+        };
+        let source = [
+            include_str!("../../shaders/srgb.wgsl"),
+            include_str!("../../shaders/scene.wgsl"),
+            source,
+        ]
+        .concat();
+        let shader = render.create_wgsl_shader(&source);
+        let skinned_shader = render.create_wgsl_shader_with_entries(
+            &source,
+            "vs_skinned",
+            Some("fs_main"),
+        );
+        let build = |shader, skinned| {
+            let mut builder = render
+                .build_pipeline(shader)
+                .vertex_layout(&Mesh::layout(render));
+            if skinned {
+                builder = builder.vertex_layout(&Mesh::skin_layout(render));
+            }
+            builder = builder
+                .bind_group_layout(&camera_layout)
+                .bind_group_layout(&object_layout)
+                .bind_group_layout(&material_layout)
+                .blended_target(format, Some(BlendState::ALPHA_BLENDING))
+                .cull_mode(Face::Back);
+            if environment.is_some() {
+                builder = builder.bind_group_layout(&environment_layout);
+            }
+            if let Some(depth) = depth {
+                builder = builder.depth(depth);
+            }
+            builder.build()
+        };
+        let pipeline = build(&shader, false);
+        let skinned_pipeline = build(&skinned_shader, true);
+        let shading = match environment {
+            Some(environment) => Shading::Pbr {
+                environment_group: render.create_bind_group_with(
+                    &environment_layout,
+                    &environment.bindings(),
+                ),
+                environment: Box::new(environment),
+            },
+            None => Shading::BlinnPhong,
+        };
+
+        let lights = Lights::new(Color::new(0.05, 0.05, 0.05, 1.0));
+        let camera = render.create_uniforms(&CameraUniforms::zeroed());
+        let light_buffer = render.create_uniforms(&lights.to_uniforms());
+
+        let alignment = render.uniform_alignment() as u64;
+        let size = size_of::<ObjectUniforms>() as u64;
+        let stride = size.div_ceil(alignment) * alignment;
+        let capacity = 16;
+        let objects = render.create_uniform_buffer(capacity as u64 * stride);
+        let joint_capacity = 64;
+        let joints = render.create_storage_buffer(
+            (joint_capacity * size_of::<[[f32; 4]; 4]>()) as u64,
+        );
+        let objects_group = SceneRenderer::create_objects_group(
+            render,
+            &object_layout,
+            &objects,
+            &joints,
+        );
+        let shadows =
+            ShadowMaps::new(render, &object_layout, ShadowConfig::default());
+        let camera_group = SceneRenderer::create_camera_group(
+            render,
+            &camera_layout,
+            &camera,
+            &light_buffer,
+            &shadows,
+        );
+
+        let sampler =
+            render.create_sampler(FilterMode::Linear, AddressMode::Repeat);
+        let white = render.create_texture(
+            1,
+            1,
+            &[255, 255, 255, 255],
+            ColorSpace::Linear,
+        );
+        let default_material = match shading {
+            Shading::BlinnPhong => SceneMaterial::new(
+                render,
+                &material_layout,
+                &sampler,
+                &white,
+                &Material::default(),
+            ),
+            Shading::Pbr { .. } => SceneMaterial::pbr(
+                render,
+                &material_layout,
+                &sampler,
+                &white,
+                &PbrMaterial::default(),
+            ),
+        }
+        .expect("The default material has no maps to load");
+
+        SceneRenderer {
+            lights,
+            culling: true,
+            pipeline,
+            skinned_pipeline,
+            shading,
+            camera,
+            light_buffer,
+            camera_layout,
+            camera_group,
+            shadows,
+            object_layout,
+            objects,
+            objects_group,
+            capacity,
+            stride,
+            joints,
+            joint_capacity,
+            material_layout,
+            sampler,
+            white,
+            default_material,
+            encode_srgb: render.encodes_srgb(format),
+            format,
+            depth,
+            skybox: None,
+            morpher: Morpher::new(render),
+            morphs: HashMap::new(),
+            draws: Vec::new(),
+            cull_stats: CullStats::default(),
+        }
+    }
+
+    fn create_camera_group(
+        render: &Render,
+        layout: &BindGroupLayout,
+        camera: &Buffer,
+        lights: &Buffer,
+        shadows: &ShadowMaps,
+    ) -> BindGroup {
+        let [shadow_uniforms, shadow_maps, shadow_sampler] = shadows.bindings();
+
+        render.create_bind_group_with(
+            layout,
+            &[
+                Binding::Buffer(camera),
+                Binding::Buffer(lights),
+                shadow_uniforms,
+                shadow_maps,
+                shadow_sampler,
+            ],
+        )
+    }
+
+    fn create_objects_group(
+        render: &Render,
+        layout: &BindGroupLayout,
+        objects: &Buffer,
+        joints: &Buffer,
+    ) -> BindGroup {
+        render.create_bind_group_with(
+            layout,
+            &[
+                Binding::BufferRange(
+                    objects,
+                    size_of::<ObjectUniforms>() as u64,
+                ),
+                Binding::Buffer(joints),
+            ],
+        )
+    }
+
+    /// Nodes drawn and culled by the last `prepare`
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    pub fn shadow_config(&self) -> &ShadowConfig {
+        self.shadows.config()
+    }
+
+    /// Recreates the shadow maps, e.g. to change their resolution or the
+    /// cascade splits
+    pub fn set_shadow_config(&mut self, render: &Render, config: ShadowConfig) {
+        self.shadows = ShadowMaps::new(render, &self.object_layout, config);
+        self.camera_group = SceneRenderer::create_camera_group(
+            render,
+            &self.camera_layout,
+            &self.camera,
+            &self.light_buffer,
+            &self.shadows,
+        );
+    }
+
+    /// Draws `cubemap` behind the scene, or clears to black without one
+    pub fn set_skybox(&mut self, render: &Render, cubemap: Option<&Texture>) {
+        self.skybox = cubemap.map(|cubemap| {
+            Skybox::new(render, cubemap, self.format, self.depth)
+        });
+    }
+
+    /// Draws the cubemap the environment was generated from behind the
+    /// scene. Does nothing with Blinn-Phong shading
+    pub fn set_environment_skybox(&mut self, render: &Render) {
+        if let Shading::Pbr {
+            ref environment, ..
+        } = self.shading
+        {
+            self.skybox = Some(Skybox::new(
+                render,
+                environment.cubemap(),
+                self.format,
+                self.depth,
+            ));
+        }
+    }
+
+    /// Uploads `material` and loads its texture maps, converting it first
+    /// when shading with PBR
+    pub fn create_material(
+        &self,
+        render: &Render,
+        material: &Material,
+    ) -> Result<SceneMaterial, String> {
+        match self.shading {
+            Shading::BlinnPhong => SceneMaterial::new(
+                render,
+                &self.material_layout,
+                &self.sampler,
+                &self.white,
+                material,
+            ),
+            Shading::Pbr { .. } => self.create_pbr_material(
+                render,
+                &PbrMaterial::from_phong(material),
+            ),
+        }
+    }
+
+    /// Uploads a metallic-roughness `material` and loads its texture maps.
+    /// Only renderers created `with_environment` can draw them
+    pub fn create_pbr_material(
+        &self,
+        render: &Render,
+        material: &PbrMaterial,
+    ) -> Result<SceneMaterial, String> {
+        match self.shading {
+            Shading::BlinnPhong => Err(format!(
+                "Can't draw the PBR material '{}' with Blinn-Phong shading",
+                material.name
+            )),
+            Shading::Pbr { .. } => SceneMaterial::pbr(
+                render,
+                &self.material_layout,
+                &self.sampler,
+                &self.white,
+                material,
+            ),
+        }
+    }
+
+    /// Updates the world matrices of `scene` and uploads the camera, the
+    /// lights, their shadow matrices and the transforms, joint matrices
+    /// and morph target weights of every mesh node, whose meshes are
+    /// looked up in `meshes`. Nodes are culled against the bounds of their
+    /// mesh, except posed and morphed ones whose vertices move past them.
+    /// Call once per frame before `draw`
+    pub fn prepare(
+        &mut self,
+        render: &Render,
+        scene: &mut Scene,
+        meshes: &[Mesh],
+        camera: &Camera,
+    ) {
+        scene.update();
+        let frustum = camera.frustum();
+        let view = camera.view();
+        if self.shadows.prepare(render, &self.lights, camera) {
+            self.camera_group = SceneRenderer::create_camera_group(
+                render,
+                &self.camera_layout,
+                &self.camera,
+                &self.light_buffer,
+                &self.shadows,
+            );
+        }
+        if let Some(ref skybox) = self.skybox {
+            skybox.prepare(render, camera);
+        }
+
+        let camera = CameraUniforms {
+            view_proj: camera.view_proj().into(),
+            position: camera.position.into(),
+            encode_srgb: self.encode_srgb as u32,
+        };
+        render.write_buffer(&self.camera, bytes_of(&camera));
+        render.write_buffer(
+            &self.light_buffer,
+            bytes_of(&self.lights.to_uniforms()),
+        );
+
+        let mut data = Vec::new();
+        let mut joints: Vec<[[f32; 4]; 4]> = Vec::new();
+        self.draws.clear();
+        self.cull_stats = CullStats::default();
+        // Nodes no longer morphed drop their vertices
+        let mut morphs = std::mem::take(&mut self.morphs);
+        for (id, node) in scene.nodes() {
+            let mesh = match node.mesh {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let morph = match morphs.remove(&id) {
+                _ if node.weights.is_empty() => None,
+                Some(morph) if morph.mesh == mesh => Some(morph),
+                _ => meshes.get(mesh).and_then(|gpu_mesh| {
+                    self.morpher.create_instance(render, mesh, gpu_mesh)
+                }),
+            };
+            if let Some(morph) = morph {
+                morph.set_weights(render, &node.weights);
+                self.morphs.insert(id, morph);
+            }
+
+            let offset = data.len();
+            let world = node.world();
+            let uniforms = ObjectUniforms {
+                model: world.into(),
+                normal: world.invert().unwrap_or(world).transpose().into(),
+                joint_offset: joints.len() as u32,
+                _padding: [0; 3],
+            };
+            joints.extend(
+                node.joints.iter().map(|&m| -> [[f32; 4]; 4] { m.into() }),
+            );
+            data.extend_from_slice(bytes_of(&uniforms));
+            data.resize(offset + self.stride as usize, 0);
+            let posed = !node.joints.is_empty();
+            let morph = self.morphs.contains_key(&id).then_some(id);
+            let visible = match meshes.get(mesh) {
+                Some(gpu_mesh) if self.culling && !posed && morph.is_none() => {
+                    frustum.intersects_sphere(
+                        &gpu_mesh.bounding_sphere().transform(&world),
+                    ) && frustum
+                        .intersects_aabb(&gpu_mesh.bounds().transform(&world))
+                }
+                _ => true,
+            };
+            let center =
+                meshes.get(mesh).map_or(Point3::origin(), |gpu_mesh| {
+                    gpu_mesh.bounding_sphere().center
+                });
+            if visible {
+                self.cull_stats.drawn += 1;
+            } else {
+                self.cull_stats.culled += 1;
+            }
+            self.draws.push(NodeDraw {
+                mesh,
+                material: node.material,
+                offset: offset as u32,
+                posed,
+                morph,
+                visible,
+                depth: -view.transform_point(world.transform_point(center)).z,
+            });
+        }
+
+        let grow_objects = self.draws.len() > self.capacity;
+        if grow_objects {
+            self.capacity = self.draws.len().next_power_of_two();
+            self.objects = render
+                .create_uniform_buffer(self.capacity as u64 * self.stride);
+        }
+        let grow_joints = joints.len() > self.joint_capacity;
+        if grow_joints {
+            self.joint_capacity = joints.len().next_power_of_two();
+            self.joints = render.create_storage_buffer(
+                (self.joint_capacity * size_of::<[[f32; 4]; 4]>()) as u64,
+            );
+        }
+        if grow_objects || grow_joints {
+            self.objects_group = SceneRenderer::create_objects_group(
+                render,
+                &self.object_layout,
+                &self.objects,
+                &self.joints,
+            );
+        }
+        if !data.is_empty() {
+            render.write_buffer(&self.objects, &data);
+        }
+        if !joints.is_empty() {
+            render.write_buffer(&self.joints, cast_slice(&joints));
+        }
+    }
+
+    /// Records the nodes uploaded by the last `prepare`, looking their
+    /// meshes up in `meshes` and their materials in `materials`. Morph
+    /// targets are blended first, then the shadow maps are rendered, then
+    /// opaque nodes in view are drawn so the skybox and transparent ones
+    /// blend over them, farthest first
+    pub fn draw<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+        meshes: &'a [Mesh],
+        materials: &'a [SceneMaterial],
+        output: &'a Framebuffer,
+    ) -> CommandBuffer<'a> {
+        let draws = self.draws.iter().map(|draw| {
+            let mesh = &meshes[draw.mesh];
+            let material = draw
+                .material
+                .or_else(|| mesh.material())
+                .and_then(|material| materials.get(material))
+                .unwrap_or(&self.default_material);
+            let instance = MeshInstance {
+                mesh,
+                vertices: draw.morph.map(|id| self.morphs[&id].vertices()),
+                offset: draw.offset,
+                posed: draw.posed,
+            };
+
+            (instance, material, draw.visible, draw.depth)
+        });
+        let (mut transparent, opaque): (Vec<_>, Vec<_>) =
+            draws.partition(|(_, material, _, _)| material.transparent);
+        // Blending needs what is behind drawn first
+        transparent.sort_by(|a, b| b.3.total_cmp(&a.3));
+
+        let casters = opaque
+            .iter()
+            .map(|(instance, _, _, _)| *instance)
+            .collect::<Vec<_>>();
+        let visible = |draws: Vec<_>| {
+            draws
+                .into_iter()
+                .filter(|(_, _, visible, _)| *visible)
+                .map(|(instance, material, _, _)| (instance, material))
+                .collect::<Vec<_>>()
+        };
+        let (opaque, transparent) = (visible(opaque), visible(transparent));
+        let commands = self.morpher.dispatch(commands, self.morphs.values());
+        let mut commands = self
+            .shadows
+            .draw(commands, &self.objects_group, &casters)
+            .label("scene")
+            .configure_draw(&self.pipeline, output);
+
+        // Without depth testing the sky goes first and everything covers it
+        let (sky_first, sky_after_opaque) = match self.skybox {
+            Some(ref skybox) if self.depth.is_none() => (Some(skybox), None),
+            ref skybox => (None, skybox.as_ref()),
+        };
+        if let Some(skybox) = sky_first {
+            commands = skybox.draw(commands);
+        }
+        commands = self.draw_nodes(self.bind_scene(commands), &opaque);
+        if let Some(skybox) = sky_after_opaque {
+            commands = self.bind_scene(skybox.draw(commands));
+        }
+
+        self.draw_nodes(commands, &transparent)
+    }
+
+    /// Sets the scene pipeline and the groups shared by every node, after
+    /// the skybox replaced them
+    fn bind_scene<'a>(
+        &'a self,
+        commands: CommandBuffer<'a>,
+    ) -> CommandBuffer<'a> {
+        let commands = commands
+            .set_pipeline(&self.pipeline)
+            .bind_resources(&self.camera_group);
+
+        match self.shading {
+            Shading::Pbr {
+                ref environment_group,
+                ..
+            } => commands.bind_resources_at(3, environment_group),
+            Shading::BlinnPhong => commands,
+        }
+    }
+
+    /// Draws each node with the rigid or the skinned pipeline, which share
+    /// their bind group layouts
+    fn draw_nodes<'a>(
+        &'a self,
+        mut commands: CommandBuffer<'a>,
+        nodes: &[(MeshInstance<'a>, &'a SceneMaterial)],
+    ) -> CommandBuffer<'a> {
+        for (instance, material) in nodes.iter() {
+            let pipeline = if instance.is_skinned() {
+                &self.skinned_pipeline
+            } else {
+                &self.pipeline
+            };
+            commands = instance.draw(
+                commands
+                    .set_pipeline(pipeline)
+                    .bind_resources_with_offsets(
+                        1,
+                        &self.objects_group,
+                        &[instance.offset],
+                    )
+                    .bind_resources_at(2, &material.bind_group),
+            );
+        }
+
+        commands
+    }
+}
+
+impl SceneMaterial {
+    fn new(
+        render: &Render,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        white: &Texture,
+        material: &Material,
+    ) -> Result<SceneMaterial, String> {
+        let uniforms = render.create_uniforms(&MaterialUniforms {
+            ambient: material.ambient.to_array(),
+            diffuse: [
+                material.diffuse.r,
+                material.diffuse.g,
+                material.diffuse.b,
+                material.opacity,
+            ],
+            specular: [
+                material.specular.r,
+                material.specular.g,
+                material.specular.b,
+                material.shininess,
+            ],
+            emissive: material.emissive.to_array(),
+        });
+
+        // Diffuse maps hold colors, specular maps hold intensities
+        let load = |map: &Option<TextureMap>, color_space| {
+            map.as_ref()
+                .map(|map| map.load(render, color_space))
+                .transpose()
+        };
+        let diffuse_map = load(&material.diffuse_map, ColorSpace::Srgb)?;
+        let specular_map = load(&material.specular_map, ColorSpace::Linear)?;
+
+        let bind_group = render.create_bind_group_with(
+            layout,
+            &[
+                Binding::Buffer(&uniforms),
+                Binding::Texture(diffuse_map.as_ref().unwrap_or(white)),
+                Binding::Texture(specular_map.as_ref().unwrap_or(white)),
+                Binding::Sampler(sampler),
+            ],
+        );
+
+        Ok(SceneMaterial {
+            _uniforms: uniforms,
+            _maps: [diffuse_map, specular_map].into_iter().flatten().collect(),
+            bind_group,
+            transparent: material.is_transparent(),
+        })
+    }
+
+    fn pbr(
+        render: &Render,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        white: &Texture,
+        material: &PbrMaterial,
+    ) -> Result<SceneMaterial, String> {
+        let [r, g, b, _] = material.emissive.to_array();
+        let uniforms = render.create_uniforms(&PbrMaterialUniforms {
+            base_color: material.base_color.to_array(),
+            emissive: [r, g, b],
+            normal_scale: if material.normal_map.is_some() {
+                material.normal_scale
+            } else {
+                0.0
+            },
+            metallic: material.metallic,
+            roughness: material.roughness,
+            occlusion_strength: if material.occlusion_map.is_some() {
+                material.occlusion_strength
+            } else {
+                0.0
+            },
+            _padding: 0.0,
+        });
+
+        // Only the colors are stored as sRGB
+        let load = |map: &Option<TextureMap>, color_space| {
+            map.as_ref()
+                .map(|map| map.load(render, color_space))
+                .transpose()
+        };
+        let maps = [
+            load(&material.base_color_map, ColorSpace::Srgb)?,
+            load(&material.metallic_roughness_map, ColorSpace::Linear)?,
+            load(&material.normal_map, ColorSpace::Linear)?,
+            load(&material.occlusion_map, ColorSpace::Linear)?,
+            load(&material.emissive_map, ColorSpace::Srgb)?,
+        ];
+
+        let bind_group =
+            render.create_bind_group_with(
+                layout,
+                &[Binding::Buffer(&uniforms)]
+                    .into_iter()
+                    .chain(maps.iter().map(|map| {
+                        Binding::Texture(map.as_ref().unwrap_or(white))
+                    }))
+                    .chain([Binding::Sampler(sampler)])
+                    .collect::<Vec<_>>(),
+            );
+
+        Ok(SceneMaterial {
+            _uniforms: uniforms,
+            _maps: maps.into_iter().flatten().collect(),
+            bind_group,
+            transparent: material.is_transparent(),
+        })
+    }
+}
@@ -18,3 +18,8 @@ pub fn rand() -> u32 {
         XORSHIFT_STATE
     }
 }
+
+/// Random number in [0, 1]
+pub fn randf() -> f32 {
+    rand() as f32 / u32::MAX as f32
+}
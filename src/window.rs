@@ -1,11 +1,14 @@
-use input::Key;
+use input::{Key, MouseButton};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use sdl2::{
     event::{Event, WindowEvent},
+    keyboard::Scancode,
+    mouse::Cursor,
     video::Window as SdlWindow,
     EventPump, Sdl,
 };
 
+mod gui_input;
 pub mod input;
 
 pub struct Window {
@@ -13,8 +16,17 @@ pub struct Window {
     pub event_pump: EventPump,
     pub should_close: bool,
     pub did_resize: bool,
+    pressed_keys: Vec<Scancode>,
+    /// Mouse movement in pixels since the last `update`
+    pub mouse_delta: (i32, i32),
+    /// Wheel movement since the last `update`, positive away from the user
+    pub scroll_delta: i32,
     pub width: i32,
     pub height: i32,
+    /// Events of the last `update`, replayed to the GUI
+    events: Vec<Event>,
+    /// Cursor asked for by the GUI, kept alive while it is shown
+    cursor: Option<(egui::CursorIcon, Cursor)>,
 }
 
 unsafe impl HasRawWindowHandle for Window {
@@ -42,18 +54,41 @@ impl Window {
             event_pump,
             should_close: false,
             did_resize: false,
+            pressed_keys: Vec::new(),
+            mouse_delta: (0, 0),
+            scroll_delta: 0,
             width,
             height,
+            events: Vec::new(),
+            cursor: None,
         }
     }
 
     pub fn update(&mut self) {
         self.did_resize = false;
+        self.pressed_keys.clear();
+        self.mouse_delta = (0, 0);
+        self.scroll_delta = 0;
+        self.events.clear();
 
         for event in self.event_pump.poll_iter() {
+            self.events.push(event.clone());
             match event {
                 Event::Quit { .. } => self.should_close = true,
 
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } => self.pressed_keys.push(scancode),
+
+                Event::MouseMotion { xrel, yrel, .. } => {
+                    self.mouse_delta.0 += xrel;
+                    self.mouse_delta.1 += yrel;
+                }
+
+                Event::MouseWheel { y, .. } => self.scroll_delta += y,
+
                 Event::Window { win_event, .. } => match win_event {
                     WindowEvent::Resized(width, height) => {
                         self.width = width;
@@ -78,6 +113,21 @@ impl Window {
         }
     }
 
+    /// Whether `key` went down since the last `update`, ignoring key repeat
+    pub fn keypressed(&self, key: Key) -> bool {
+        if let Some(sdl_key) = key.to_sdl2() {
+            self.pressed_keys.contains(&sdl_key)
+        } else {
+            false
+        }
+    }
+
+    pub fn getbutton(&self, button: MouseButton) -> bool {
+        self.event_pump
+            .mouse_state()
+            .is_mouse_button_pressed(button.to_sdl2())
+    }
+
     pub fn close(&mut self) {
         self.should_close = true;
     }
@@ -0,0 +1,410 @@
+use egui::{
+    pos2, vec2, CursorIcon, Event as GuiEvent, Key as GuiKey, Modifiers,
+    MouseWheelUnit, PlatformOutput, PointerButton, RawInput, Rect,
+};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    mouse::{Cursor, MouseButton, MouseWheelDirection, SystemCursor},
+};
+
+use super::Window;
+
+/// DPI of a display shown at a scale of 1
+const BASE_DPI: f32 = 96.0;
+
+impl Window {
+    /// Input of an `egui::Context` from the events of the last `update`,
+    /// window coordinates being divided by `pixels_per_point`
+    pub fn gui_input(&self, pixels_per_point: f32) -> RawInput {
+        let video = self.window.subsystem();
+        let clipboard = || video.clipboard().clipboard_text().ok();
+        let size = vec2(self.width as f32, self.height as f32);
+        let modifiers = modifiers(video.sdl().keyboard().mod_state());
+
+        RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                pos2(0.0, 0.0),
+                size / pixels_per_point,
+            )),
+            modifiers,
+            events: self
+                .events
+                .iter()
+                .filter_map(|event| {
+                    gui_event(event, pixels_per_point, modifiers, clipboard)
+                })
+                .collect(),
+            ..RawInput::default()
+        }
+    }
+
+    /// Does what the GUI asks of the platform: changing the mouse cursor
+    /// and copying text to the clipboard
+    pub fn handle_gui_output(
+        &mut self,
+        output: &PlatformOutput,
+    ) -> Result<(), String> {
+        let video = self.window.subsystem();
+        let icon = output.cursor_icon;
+        if self.cursor.as_ref().map(|(current, _)| *current) != Some(icon) {
+            let mouse = video.sdl().mouse();
+            mouse.show_cursor(icon != CursorIcon::None);
+            self.cursor =
+                Cursor::from_system(system_cursor(icon)).ok().map(|cursor| {
+                    cursor.set();
+                    (icon, cursor)
+                });
+        }
+
+        if output.copied_text.is_empty() {
+            return Ok(());
+        }
+        video
+            .clipboard()
+            .set_clipboard_text(&output.copied_text)
+            .map_err(|err| format!("Failed to copy to the clipboard: {}", err))
+    }
+
+    /// Scale keeping the GUI at the same physical size on high DPI
+    /// displays, from the DPI SDL reports for the display of the window
+    pub fn dpi_scale(&self) -> f32 {
+        let video = self.window.subsystem();
+        self.window
+            .display_index()
+            .and_then(|index| video.display_dpi(index))
+            .map_or(1.0, |(_, horizontal, _)| (horizontal / BASE_DPI).max(1.0))
+    }
+}
+
+/// Translates an SDL event, `None` for the ones the GUI has no use for.
+/// Mouse events carry no modifiers of their own and get the `modifiers`
+/// held while translating them. Copy, cut and paste shortcuts become the
+/// matching GUI events, pasting the text `clipboard` returns
+fn gui_event(
+    event: &Event,
+    pixels_per_point: f32,
+    held: Modifiers,
+    clipboard: impl Fn() -> Option<String>,
+) -> Option<GuiEvent> {
+    let position = |x: i32, y: i32| {
+        pos2(x as f32 / pixels_per_point, y as f32 / pixels_per_point)
+    };
+
+    match *event {
+        Event::MouseMotion { x, y, .. } => {
+            Some(GuiEvent::PointerMoved(position(x, y)))
+        }
+
+        Event::MouseButtonDown {
+            mouse_btn, x, y, ..
+        }
+        | Event::MouseButtonUp {
+            mouse_btn, x, y, ..
+        } => Some(GuiEvent::PointerButton {
+            pos: position(x, y),
+            button: pointer_button(mouse_btn)?,
+            pressed: matches!(event, Event::MouseButtonDown { .. }),
+            modifiers: held,
+        }),
+
+        Event::MouseWheel {
+            x, y, direction, ..
+        } => {
+            let sign = match direction {
+                MouseWheelDirection::Flipped => -1.0,
+                _ => 1.0,
+            };
+            Some(GuiEvent::MouseWheel {
+                unit: MouseWheelUnit::Line,
+                delta: vec2(x as f32, y as f32) * sign,
+                modifiers: held,
+            })
+        }
+
+        Event::TextInput { ref text, .. } => Some(GuiEvent::Text(text.clone())),
+
+        Event::KeyDown {
+            keycode: Some(keycode),
+            keymod,
+            repeat,
+            ..
+        } => {
+            let modifiers = modifiers(keymod);
+            match keycode {
+                Keycode::C if modifiers.command => Some(GuiEvent::Copy),
+                Keycode::X if modifiers.command => Some(GuiEvent::Cut),
+                Keycode::V if modifiers.command => {
+                    clipboard().map(GuiEvent::Paste)
+                }
+                _ => Some(GuiEvent::Key {
+                    key: gui_key(keycode)?,
+                    physical_key: None,
+                    pressed: true,
+                    repeat,
+                    modifiers,
+                }),
+            }
+        }
+
+        Event::KeyUp {
+            keycode: Some(keycode),
+            keymod,
+            ..
+        } => Some(GuiEvent::Key {
+            key: gui_key(keycode)?,
+            physical_key: None,
+            pressed: false,
+            repeat: false,
+            modifiers: modifiers(keymod),
+        }),
+
+        Event::Window { win_event, .. } => match win_event {
+            WindowEvent::Leave => Some(GuiEvent::PointerGone),
+            WindowEvent::FocusGained => Some(GuiEvent::WindowFocused(true)),
+            WindowEvent::FocusLost => Some(GuiEvent::WindowFocused(false)),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    let gui = keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD);
+    let mac = cfg!(target_os = "macos");
+
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: mac && gui,
+        command: if mac { gui } else { ctrl },
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::X1 => Some(PointerButton::Extra1),
+        MouseButton::X2 => Some(PointerButton::Extra2),
+        _ => None,
+    }
+}
+
+fn gui_key(keycode: Keycode) -> Option<GuiKey> {
+    match keycode {
+        Keycode::Down => Some(GuiKey::ArrowDown),
+        Keycode::Left => Some(GuiKey::ArrowLeft),
+        Keycode::Right => Some(GuiKey::ArrowRight),
+        Keycode::Up => Some(GuiKey::ArrowUp),
+        Keycode::Escape => Some(GuiKey::Escape),
+        Keycode::Tab => Some(GuiKey::Tab),
+        Keycode::Backspace => Some(GuiKey::Backspace),
+        Keycode::Return | Keycode::KpEnter => Some(GuiKey::Enter),
+        Keycode::Space => Some(GuiKey::Space),
+        Keycode::Insert => Some(GuiKey::Insert),
+        Keycode::Delete => Some(GuiKey::Delete),
+        Keycode::Home => Some(GuiKey::Home),
+        Keycode::End => Some(GuiKey::End),
+        Keycode::PageUp => Some(GuiKey::PageUp),
+        Keycode::PageDown => Some(GuiKey::PageDown),
+        Keycode::Comma => Some(GuiKey::Comma),
+        Keycode::Backslash => Some(GuiKey::Backslash),
+        Keycode::Slash => Some(GuiKey::Slash),
+        Keycode::LeftBracket => Some(GuiKey::OpenBracket),
+        Keycode::RightBracket => Some(GuiKey::CloseBracket),
+        Keycode::Backquote => Some(GuiKey::Backtick),
+        Keycode::Minus | Keycode::KpMinus => Some(GuiKey::Minus),
+        Keycode::Period => Some(GuiKey::Period),
+        Keycode::Plus | Keycode::KpPlus => Some(GuiKey::Plus),
+        Keycode::Equals => Some(GuiKey::Equals),
+        Keycode::Semicolon => Some(GuiKey::Semicolon),
+        Keycode::Quote => Some(GuiKey::Quote),
+        Keycode::Num0 | Keycode::Kp0 => Some(GuiKey::Num0),
+        Keycode::Num1 | Keycode::Kp1 => Some(GuiKey::Num1),
+        Keycode::Num2 | Keycode::Kp2 => Some(GuiKey::Num2),
+        Keycode::Num3 | Keycode::Kp3 => Some(GuiKey::Num3),
+        Keycode::Num4 | Keycode::Kp4 => Some(GuiKey::Num4),
+        Keycode::Num5 | Keycode::Kp5 => Some(GuiKey::Num5),
+        Keycode::Num6 | Keycode::Kp6 => Some(GuiKey::Num6),
+        Keycode::Num7 | Keycode::Kp7 => Some(GuiKey::Num7),
+        Keycode::Num8 | Keycode::Kp8 => Some(GuiKey::Num8),
+        Keycode::Num9 | Keycode::Kp9 => Some(GuiKey::Num9),
+        Keycode::A => Some(GuiKey::A),
+        Keycode::B => Some(GuiKey::B),
+        Keycode::C => Some(GuiKey::C),
+        Keycode::D => Some(GuiKey::D),
+        Keycode::E => Some(GuiKey::E),
+        Keycode::F => Some(GuiKey::F),
+        Keycode::G => Some(GuiKey::G),
+        Keycode::H => Some(GuiKey::H),
+        Keycode::I => Some(GuiKey::I),
+        Keycode::J => Some(GuiKey::J),
+        Keycode::K => Some(GuiKey::K),
+        Keycode::L => Some(GuiKey::L),
+        Keycode::M => Some(GuiKey::M),
+        Keycode::N => Some(GuiKey::N),
+        Keycode::O => Some(GuiKey::O),
+        Keycode::P => Some(GuiKey::P),
+        Keycode::Q => Some(GuiKey::Q),
+        Keycode::R => Some(GuiKey::R),
+        Keycode::S => Some(GuiKey::S),
+        Keycode::T => Some(GuiKey::T),
+        Keycode::U => Some(GuiKey::U),
+        Keycode::V => Some(GuiKey::V),
+        Keycode::W => Some(GuiKey::W),
+        Keycode::X => Some(GuiKey::X),
+        Keycode::Y => Some(GuiKey::Y),
+        Keycode::Z => Some(GuiKey::Z),
+        Keycode::F1 => Some(GuiKey::F1),
+        Keycode::F2 => Some(GuiKey::F2),
+        Keycode::F3 => Some(GuiKey::F3),
+        Keycode::F4 => Some(GuiKey::F4),
+        Keycode::F5 => Some(GuiKey::F5),
+        Keycode::F6 => Some(GuiKey::F6),
+        Keycode::F7 => Some(GuiKey::F7),
+        Keycode::F8 => Some(GuiKey::F8),
+        Keycode::F9 => Some(GuiKey::F9),
+        Keycode::F10 => Some(GuiKey::F10),
+        Keycode::F11 => Some(GuiKey::F11),
+        Keycode::F12 => Some(GuiKey::F12),
+        _ => None,
+    }
+}
+
+fn system_cursor(icon: CursorIcon) -> SystemCursor {
+    match icon {
+        CursorIcon::Text | CursorIcon::VerticalText => SystemCursor::IBeam,
+        CursorIcon::PointingHand => SystemCursor::Hand,
+        CursorIcon::Wait => SystemCursor::Wait,
+        CursorIcon::Progress => SystemCursor::WaitArrow,
+        CursorIcon::Crosshair | CursorIcon::Cell => SystemCursor::Crosshair,
+        CursorIcon::NotAllowed | CursorIcon::NoDrop => SystemCursor::No,
+        CursorIcon::Move
+        | CursorIcon::AllScroll
+        | CursorIcon::Grab
+        | CursorIcon::Grabbing => SystemCursor::SizeAll,
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => SystemCursor::SizeWE,
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => SystemCursor::SizeNS,
+        CursorIcon::ResizeNeSw
+        | CursorIcon::ResizeNorthEast
+        | CursorIcon::ResizeSouthWest => SystemCursor::SizeNESW,
+        CursorIcon::ResizeNwSe
+        | CursorIcon::ResizeNorthWest
+        | CursorIcon::ResizeSouthEast => SystemCursor::SizeNWSE,
+        _ => SystemCursor::Arrow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointer_events_in_points() {
+        let moved = Event::MouseMotion {
+            timestamp: 0,
+            window_id: 1,
+            which: 0,
+            mousestate: sdl2::mouse::MouseState::from_sdl_state(0),
+            x: 200,
+            y: 100,
+            xrel: 0,
+            yrel: 0,
+        };
+        assert_eq!(
+            gui_event(&moved, 2.0, Modifiers::default(), || None),
+            Some(GuiEvent::PointerMoved(pos2(100.0, 50.0)))
+        );
+
+        let released = Event::MouseButtonUp {
+            timestamp: 0,
+            window_id: 1,
+            which: 0,
+            mouse_btn: MouseButton::Right,
+            clicks: 1,
+            x: 10,
+            y: 20,
+        };
+        assert_eq!(
+            gui_event(&released, 1.0, Modifiers::SHIFT, || None),
+            Some(GuiEvent::PointerButton {
+                pos: pos2(10.0, 20.0),
+                button: PointerButton::Secondary,
+                pressed: false,
+                modifiers: Modifiers::SHIFT,
+            })
+        );
+    }
+
+    #[test]
+    fn keys_and_shortcuts() {
+        let key_down = |keycode, keymod| Event::KeyDown {
+            timestamp: 0,
+            window_id: 1,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod,
+            repeat: false,
+        };
+        let command = if cfg!(target_os = "macos") {
+            Mod::LGUIMOD
+        } else {
+            Mod::LCTRLMOD
+        };
+        let clipboard = || Some("pasted".to_string());
+
+        assert_eq!(
+            gui_event(
+                &key_down(Keycode::V, command),
+                1.0,
+                Modifiers::default(),
+                clipboard
+            ),
+            Some(GuiEvent::Paste("pasted".to_string()))
+        );
+        assert_eq!(
+            gui_event(
+                &key_down(Keycode::C, command),
+                1.0,
+                Modifiers::default(),
+                clipboard
+            ),
+            Some(GuiEvent::Copy)
+        );
+        assert_eq!(
+            gui_event(
+                &key_down(Keycode::Return, Mod::LSHIFTMOD),
+                1.0,
+                Modifiers::default(),
+                || None
+            ),
+            Some(GuiEvent::Key {
+                key: GuiKey::Enter,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: Modifiers::SHIFT,
+            })
+        );
+        assert_eq!(
+            gui_event(
+                &key_down(Keycode::CapsLock, Mod::NOMOD),
+                1.0,
+                Modifiers::default(),
+                || None
+            ),
+            None
+        );
+    }
+}
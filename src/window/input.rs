@@ -246,3 +246,20 @@ impl Key {
         }
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+impl MouseButton {
+    pub fn to_sdl2(self) -> sdl2::mouse::MouseButton {
+        match self {
+            MouseButton::Left => sdl2::mouse::MouseButton::Left,
+            MouseButton::Middle => sdl2::mouse::MouseButton::Middle,
+            MouseButton::Right => sdl2::mouse::MouseButton::Right,
+        }
+    }
+}